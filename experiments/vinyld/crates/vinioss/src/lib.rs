@@ -7,7 +7,10 @@ pub use error::Error;
 
 use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
-use std::{fmt::Debug, time::Duration};
+use std::{
+    fmt::Debug,
+    time::{Duration, SystemTime},
+};
 use tokio::io::AsyncRead;
 use url::Url;
 
@@ -16,12 +19,87 @@ use url::Url;
 #[serde(transparent)]
 pub struct ObjectKey(pub String);
 
+/// A range of bytes in an object.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ByteRange {
+    /// Bytes from the offset to the end of the object.
+    From(u64),
+
+    /// Bytes between the two offsets, both inclusive.
+    Inclusive(u64, u64),
+
+    /// The last bytes of the object, in given length.
+    Suffix(u64),
+}
+
+/// A downloaded range of an object.
+pub struct ObjectRange {
+    /// Stream of the bytes in the range.
+    pub stream: Box<dyn AsyncRead + Send + Unpin>,
+
+    /// Offset of the first byte in the range.
+    pub start: u64,
+
+    /// Length of the range.
+    pub len: u64,
+
+    /// Size of the whole object.
+    pub total_len: u64,
+
+    /// Entity tag of the object, if the object storage provides one.
+    pub e_tag: Option<String>,
+
+    /// MIME type of the object, if the object storage provides one.
+    pub content_type: Option<String>,
+}
+impl Debug for ObjectRange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ObjectRange")
+            .field("start", &self.start)
+            .field("len", &self.len)
+            .field("total_len", &self.total_len)
+            .field("e_tag", &self.e_tag)
+            .field("content_type", &self.content_type)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Metadata of an object.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ObjectMeta {
+    /// Size of the object.
+    pub len: u64,
+
+    /// Entity tag of the object, if the object storage provides one.
+    pub e_tag: Option<String>,
+
+    /// MIME type of the object, if the object storage provides one.
+    pub content_type: Option<String>,
+
+    /// Time when the object was modified last time, if the object storage provides one.
+    pub last_modified: Option<SystemTime>,
+}
+
 /// An OSS client.
 #[async_trait]
 pub trait Objects: Debug + Send + Sync {
     /// Returns a presigned URL for the given object key.
     async fn get_url(&self, key: ObjectKey, expires_in: Duration) -> Result<Url, Error>;
 
+    /// Returns metadata of an object without downloading it.
+    async fn head(&self, key: ObjectKey) -> Result<ObjectMeta, Error>;
+
+    /// Downloads an object from the object storage as a stream.
+    async fn get_stream(&self, key: ObjectKey) -> Result<Box<dyn AsyncRead + Send + Unpin>, Error>;
+
+    /// Downloads a range of an object from the object storage as a stream. If `range` is `None`, the whole object is
+    /// downloaded.
+    async fn get_range(
+        &self,
+        key: ObjectKey,
+        range: Option<ByteRange>,
+    ) -> Result<ObjectRange, Error>;
+
     /// Uploads a stream to the object storage.
     async fn put_stream(
         &self,
@@ -1,7 +1,7 @@
-use crate::{ObjectKey, Objects, error::Error};
+use crate::{ByteRange, ObjectKey, ObjectMeta, ObjectRange, Objects, error::Error};
 use async_trait::async_trait;
 use aws_sdk_s3::{
-    operation::get_object::GetObjectError,
+    operation::{get_object::GetObjectError, head_object::HeadObjectError},
     presigning::PresigningConfig,
     primitives::ByteStream,
     types::{CompletedMultipartUpload, CompletedPart},
@@ -94,6 +94,93 @@ impl Objects for S3 {
             .map_err(|err| Error::Unrecognized(Box::new(err)))
     }
 
+    async fn head(&self, key: ObjectKey) -> Result<ObjectMeta, Error> {
+        let output = self
+            .0
+            .head_object()
+            .bucket(s3_bucket()?)
+            .key(key.0)
+            .send()
+            .await
+            .map_err(|err| match err.into_service_error() {
+                HeadObjectError::NotFound(_) => Error::ObjectNotFound,
+                err => Error::Unrecognized(err.into()),
+            })?;
+
+        Ok(ObjectMeta {
+            len: output
+                .content_length
+                .ok_or_else(|| Error::Unrecognized("no content length from s3 server".into()))?
+                as u64,
+            e_tag: output.e_tag,
+            content_type: output.content_type,
+            last_modified: output.last_modified.and_then(|x| x.try_into().ok()),
+        })
+    }
+
+    async fn get_stream(&self, key: ObjectKey) -> Result<Box<dyn AsyncRead + Send + Unpin>, Error> {
+        let output = self
+            .0
+            .get_object()
+            .bucket(s3_bucket()?)
+            .key(key.0)
+            .send()
+            .await
+            .map_err(|err| match err.into_service_error() {
+                GetObjectError::NoSuchKey(_) => Error::ObjectNotFound,
+                err => Error::Unrecognized(err.into()),
+            })?;
+
+        Ok(Box::new(output.body.into_async_read()))
+    }
+
+    async fn get_range(
+        &self,
+        key: ObjectKey,
+        range: Option<ByteRange>,
+    ) -> Result<ObjectRange, Error> {
+        let output = self
+            .0
+            .get_object()
+            .bucket(s3_bucket()?)
+            .key(key.0)
+            .set_range(range.map(|range| match range {
+                ByteRange::From(start) => format!("bytes={start}-"),
+                ByteRange::Inclusive(start, end) => format!("bytes={start}-{end}"),
+                ByteRange::Suffix(len) => format!("bytes=-{len}"),
+            }))
+            .send()
+            .await
+            .map_err(|err| {
+                if err.raw_response().map(|x| x.status().as_u16()) == Some(416) {
+                    return Error::InvalidRange;
+                }
+                match err.into_service_error() {
+                    GetObjectError::NoSuchKey(_) => Error::ObjectNotFound,
+                    err => Error::Unrecognized(err.into()),
+                }
+            })?;
+
+        let len = output
+            .content_length
+            .ok_or_else(|| Error::Unrecognized("no content length from s3 server".into()))?
+            as u64;
+        let (start, total_len) = match &output.content_range {
+            Some(content_range) => parse_content_range(content_range)
+                .ok_or_else(|| Error::Unrecognized("malformed content range".into()))?,
+            None => (0, len),
+        };
+
+        Ok(ObjectRange {
+            stream: Box::new(output.body.into_async_read()),
+            start,
+            len,
+            total_len,
+            e_tag: output.e_tag,
+            content_type: output.content_type,
+        })
+    }
+
     async fn put_stream(
         &self,
         key: ObjectKey,
@@ -159,6 +246,13 @@ impl Objects for S3 {
     }
 }
 
+/// Parses a `Content-Range` header in the form of `bytes <start>-<end>/<total>`, returning the start and the total size.
+fn parse_content_range(content_range: &str) -> Option<(u64, u64)> {
+    let (range, total) = content_range.strip_prefix("bytes ")?.split_once('/')?;
+    let (start, _) = range.split_once('-')?;
+    Some((start.parse().ok()?, total.parse().ok()?))
+}
+
 fn s3_bucket() -> Result<String, Error> {
     std::env::var("S3_BUCKET")
         .map_err(|_| Error::InvalidConfiguration("S3_BUCKET environment variable not set".into()))
@@ -1,13 +1,23 @@
 use crate::{
     AppState,
-    database::entity::album,
+    app_settings::MandatoryAlbumCensorship,
+    audit::Actor,
+    database::{
+        entity::{album, song},
+        search,
+    },
     error::Error,
-    policy::{Condition::MatchUid, ObjectPolicy, PolicyItem},
-    user::Uid,
+    moderation::ModerationStatus,
+    policy::{Condition::MatchUid, ObjectPolicy, PolicyItem, Subject},
+    user::{Uid, User},
+    util::page::{Page, PageQuery},
 };
 use axum::body::Bytes;
 use chrono::Utc;
-use sea_orm::{ActiveModelTrait, ActiveValue::NotSet, EntityTrait, IntoActiveModel, Set};
+use sea_orm::{
+    ActiveModelTrait, ActiveValue::NotSet, ColumnTrait, Condition, ConnectionTrait, EntityTrait,
+    IntoActiveModel, Order, QueryFilter, QueryOrder, QuerySelect, Set,
+};
 use serde::{Deserialize, Serialize};
 use std::io::Cursor;
 use vinioss::ObjectKey;
@@ -19,16 +29,26 @@ impl Albums<'_> {
     const COVER_HQ_RESOLUTION: (u32, u32) = (640, 640);
 
     /// Creates a new album.
-    pub async fn create(&self, uploader: Uid, create: Create) -> Result<AlbumId, Error> {
+    ///
+    /// If album censorship is mandatory, the album is held for review unless the uploader is an administrator.
+    pub async fn create(&self, uploader: &User, create: Create) -> Result<AlbumId, Error> {
+        let mandatory = self
+            .0
+            .app_settings()
+            .get::<MandatoryAlbumCensorship>()
+            .await;
+        let status = ModerationStatus::initial_for(uploader, mandatory);
         Ok(AlbumId(
             album::Entity::insert(album::ActiveModel {
                 album_id: NotSet,
                 title: Set(create.title),
-                uploader: Set(uploader.0),
+                uploader: Set(uploader.uid.0),
                 description: Set(create.description),
                 created_at: Set(Utc::now().naive_utc()),
-                write_policy: Set(write_policy(uploader).json()),
+                write_policy: Set(write_policy(uploader.uid).json()),
                 cover: Set(None),
+                status: Set(status.as_str().into()),
+                rejection_reason: Set(None),
             })
             .exec(&*self.0.database.conn)
             .await?
@@ -37,57 +57,204 @@ impl Albums<'_> {
     }
 
     /// Returns the HQ cover of the album.
-    pub async fn cover_hq(&self, album_id: AlbumId) -> Result<ObjectKey, Error> {
-        album::Entity::find_by_id(album_id.0)
-            .one(&*self.0.database.conn)
+    pub async fn cover_hq(&self, album_id: AlbumId, subject: &Subject) -> Result<ObjectKey, Error> {
+        self.visible(album_id, subject)
             .await?
-            .ok_or_else(|| Error::not_found())?
             .cover
             .ok_or_else(|| Error::not_found())
             .map(ObjectKey)
     }
 
-    /// Sets the HQ cover of the album.
-    pub async fn set_cover(&self, album_id: AlbumId, image: Bytes) -> Result<(), Error> {
+    /// Sets the HQ cover of the album. Only the uploader or an administrator can do this.
+    ///
+    /// If album censorship is mandatory, an album whose cover is changed by the uploader is held for review again.
+    pub async fn set_cover(
+        &self,
+        actor: &Actor,
+        album_id: AlbumId,
+        image: Bytes,
+        administrator: bool,
+    ) -> Result<(), Error> {
         let model = album::Entity::find_by_id(album_id.0)
             .one(&*self.0.database.conn)
             .await?
             .ok_or_else(|| Error::not_found())?;
+        if model.uploader != actor.uid.0 && !administrator {
+            return Err(Error::restricted_user());
+        }
 
         let mut buffer = Cursor::new(crate::util::image::recompress(
+            image.clone(),
+            Self::COVER_HQ_RESOLUTION,
+        )?);
+        let mut embedded = Cursor::new(crate::util::image::recompress_jpeg(
             image,
             Self::COVER_HQ_RESOLUTION,
         )?);
 
-        if let Some(origin) = &model.cover {
-            self.0.objects.remove(ObjectKey(origin.clone())).await?;
-        }
-
         let object_key = ObjectKey(vinutie::random::filename("album_cover", "avif"));
         self.0
             .objects
             .put_stream(object_key.clone(), &mut buffer)
             .await?;
+        if let Err(err) = self
+            .0
+            .objects
+            .put_stream(embedded_cover(&object_key.0), &mut embedded)
+            .await
+        {
+            self.remove_cover(&object_key.0).await;
+            return Err(err.into());
+        }
 
+        let mandatory = self
+            .0
+            .app_settings()
+            .get::<MandatoryAlbumCensorship>()
+            .await;
+        let before = model.cover.clone();
         let mut active_model = model.into_active_model();
-        active_model.cover = Set(Some(object_key.0));
-        active_model.insert(&*self.0.database.conn).await?;
+        active_model.cover = Set(Some(object_key.0.clone()));
+        if mandatory && !administrator {
+            active_model.status = Set(ModerationStatus::Pending.as_str().into());
+            active_model.rejection_reason = Set(None);
+        }
+        if let Err(err) = active_model.update(&*self.0.database.conn).await {
+            self.remove_cover(&object_key.0).await;
+            return Err(err.into());
+        }
+        // The old cover is only removed once the album no longer refers to it.
+        if let Some(origin) = &before {
+            self.remove_cover(origin).await;
+        }
+        self.0
+            .songs()
+            .invalidate_renditions(Condition::all().add(song::Column::Album.eq(album_id.0)))
+            .await?;
 
-        Ok(())
+        self.0
+            .audit_log()
+            .record(
+                actor,
+                "album.set_cover",
+                format!("album/{}", album_id.0),
+                serde_json::json!(before),
+                serde_json::json!(object_key.0),
+            )
+            .await
     }
 
     /// Returns the profile of the album.
-    pub async fn profile(&self, album_id: AlbumId) -> Result<Profile, Error> {
+    pub async fn profile(&self, album_id: AlbumId, subject: &Subject) -> Result<Profile, Error> {
+        Profile::try_from(self.visible(album_id, subject).await?)
+    }
+
+    /// Lists albums created by the user, from the newest.
+    ///
+    /// Albums that are not approved are only listed to the uploader and administrators.
+    pub async fn list_by_uploader(
+        &self,
+        uploader: Uid,
+        subject: &Subject,
+        query: &PageQuery,
+    ) -> Result<Page<Profile>, Error> {
+        let limit = query.limit();
+        let mut select = album::Entity::find()
+            .filter(album::Column::Uploader.eq(uploader.0))
+            .order_by_desc(album::Column::AlbumId)
+            .limit(limit + 1);
+        // Albums that are not approved are visible to the same subjects, whatever status they are in.
+        if !ModerationStatus::Pending.visible_to(uploader, subject) {
+            select = select.filter(album::Column::Status.eq(ModerationStatus::Approved.as_str()));
+        }
+        if let Some(position) = query.position()? {
+            select = select.filter(album::Column::AlbumId.lt(position));
+        }
+
+        let mut models = select.all(&*self.0.database.conn).await?;
+        let next_position = if models.len() as u64 > limit {
+            models.truncate(limit as _);
+            models.last().map(|x| x.album_id)
+        } else {
+            None
+        };
+
+        Ok(Page::new(
+            models
+                .into_iter()
+                .map(Profile::try_from)
+                .collect::<Result<_, _>>()?,
+            next_position,
+        ))
+    }
+
+    /// Searches approved albums by title and description, from the most relevant.
+    pub async fn search(&self, text: &str, query: &PageQuery) -> Result<Page<Profile>, Error> {
+        let limit = query.limit();
+        let offset = query.position()?.unwrap_or(0);
+        let backend = self.0.database.conn.get_database_backend();
+
+        let mut models = album::Entity::find()
+            .filter(search::ALBUM.matches(backend, text))
+            .filter(album::Column::Status.eq(ModerationStatus::Approved.as_str()))
+            .order_by(search::ALBUM.rank(backend, text), Order::Desc)
+            .order_by_desc(album::Column::AlbumId)
+            .offset(offset as u64)
+            .limit(limit + 1)
+            .all(&*self.0.database.conn)
+            .await?;
+        let next_position = if models.len() as u64 > limit {
+            models.truncate(limit as _);
+            Some(offset + limit as i64)
+        } else {
+            None
+        };
+
+        Ok(Page::new(
+            models
+                .into_iter()
+                .map(Profile::try_from)
+                .collect::<Result<_, _>>()?,
+            next_position,
+        ))
+    }
+
+    /// Sets the moderation status of the album.
+    pub async fn set_status(
+        &self,
+        actor: &Actor,
+        album_id: AlbumId,
+        status: ModerationStatus,
+        reason: Option<String>,
+    ) -> Result<(), Error> {
         let model = album::Entity::find_by_id(album_id.0)
             .one(&*self.0.database.conn)
             .await?
             .ok_or_else(|| Error::not_found())?;
 
-        Ok(Profile {
-            title: model.title.clone(),
-            uploader: Uid(model.uploader),
-            translated_title: model.title,
-        })
+        let before = serde_json::json! {{
+            "status": model.status,
+            "rejection_reason": model.rejection_reason,
+        }};
+        let after = serde_json::json! {{
+            "status": status,
+            "rejection_reason": reason,
+        }};
+        let mut active_model = model.into_active_model();
+        active_model.status = Set(status.as_str().into());
+        active_model.rejection_reason = Set(reason);
+        active_model.update(&*self.0.database.conn).await?;
+
+        self.0
+            .audit_log()
+            .record(
+                actor,
+                "album.set_status",
+                format!("album/{}", album_id.0),
+                before,
+                after,
+            )
+            .await
     }
 
     /// Returns write policy of the album.
@@ -99,6 +266,41 @@ impl Albums<'_> {
 
         serde_json::from_value(model.write_policy).map_err(Error::internal)
     }
+
+    /// Removes a cover object along with its embedded copy, logging failures instead of returning them.
+    async fn remove_cover(&self, key: &str) {
+        for object_key in [ObjectKey(key.into()), embedded_cover(key)] {
+            if let Err(err) = self.0.objects.remove(object_key.clone()).await {
+                tracing::warn!("failed to remove album cover `{}`: {err}", object_key.0);
+            }
+        }
+    }
+
+    /// Returns the model of the album, if it's visible to the subject.
+    async fn visible(&self, album_id: AlbumId, subject: &Subject) -> Result<album::Model, Error> {
+        let model = album::Entity::find_by_id(album_id.0)
+            .one(&*self.0.database.conn)
+            .await?
+            .ok_or_else(|| Error::not_found())?;
+        if !model
+            .status
+            .parse::<ModerationStatus>()?
+            .visible_to(Uid(model.uploader), subject)
+        {
+            return Err(Error::not_found());
+        }
+
+        Ok(model)
+    }
+}
+
+/// Returns the key of the JPEG copy of an album cover, which is embedded into audio files instead of the AVIF cover,
+/// since few players can show AVIF covers.
+pub fn embedded_cover(cover: &str) -> ObjectKey {
+    ObjectKey(format!(
+        "{}.jpg",
+        cover.strip_suffix(".avif").unwrap_or(cover)
+    ))
 }
 
 /// Request of album creation.
@@ -121,6 +323,9 @@ pub struct AlbumId(pub i64);
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Profile {
+    /// ID of the album.
+    pub album_id: AlbumId,
+
     /// Title of the album, in its original language.
     pub title: String,
 
@@ -132,6 +337,30 @@ pub struct Profile {
     /// If the user didn't specify a language when requesting, or the user's language is same as the album's original language, this field
     /// will contain the same value as `title`.
     pub translated_title: String,
+
+    /// Description of the album.
+    pub description: Option<String>,
+
+    /// Moderation status of the album.
+    pub status: ModerationStatus,
+
+    /// Reason of rejecting the album, if it was rejected.
+    pub rejection_reason: Option<String>,
+}
+impl TryFrom<album::Model> for Profile {
+    type Error = Error;
+
+    fn try_from(model: album::Model) -> Result<Self, Error> {
+        Ok(Self {
+            album_id: AlbumId(model.album_id),
+            title: model.title.clone(),
+            uploader: Uid(model.uploader),
+            translated_title: model.title,
+            description: model.description,
+            status: model.status.parse()?,
+            rejection_reason: model.rejection_reason,
+        })
+    }
 }
 
 fn write_policy(uploader: Uid) -> ObjectPolicy {
@@ -1,16 +1,26 @@
 use crate::{
     AppState,
     app_settings::{self, *},
+    audit::{Actor, Filter, Record},
+    comment::{Comment, CommentId},
     error::Error,
-    user::{extract::Authorization, session::Session},
+    moderation::{ContentKind, Item, ModerationStatus},
+    user::{
+        Uid,
+        admin::{BanRequest, Details},
+        extract::Authorization,
+        session::Session,
+    },
+    util::page::{Page, PageQuery},
 };
 use axum::{
     Json, Router,
-    extract::{Request, State},
+    extract::{Path, Query, Request, State},
     middleware::Next,
     response::Response,
-    routing::get,
+    routing::{get, post, put},
 };
+use serde::{Deserialize, Serialize};
 use std::sync::Arc;
 
 /// Macro for creating a route for an app setting.
@@ -19,8 +29,12 @@ macro_rules! route_entry {
         get(async |State(state): State<Arc<AppState>>| Json(state.app_settings().get::<$t>().await))
             .put(
                 async |State(state): State<Arc<AppState>>,
+                       Authorization(session): Authorization<Session>,
                        data: Json<<$t as app_settings::Entry>::Ty>| {
-                    state.app_settings().set::<$t>(&*data).await
+                    state
+                        .app_settings()
+                        .set::<$t>(&Actor::from(&session), &*data)
+                        .await
                 },
             )
     };
@@ -50,6 +64,157 @@ pub fn router(state: Arc<AppState>) -> Router<Arc<AppState>> {
             "/mandatory_comment_censorship",
             route_entry!(MandatoryCommentCensorship),
         )
+        .route(
+            "/mandatory_email_verification",
+            route_entry!(MandatoryEmailVerification),
+        )
+        .route(
+            "/mandatory_wheel_two_factor",
+            route_entry!(MandatoryWheelTwoFactor),
+        )
         .route("/license_html", route_entry!(LicenseHTML))
+        .route("/rate_limits", route_entry!(RateLimits))
+        .route("/comments/pending.json", get(pending_comments))
+        .route("/comments/{id}/review", post(review_comment))
+        .route("/moderation/{kind}/pending.json", get(moderation_queue))
+        .route("/moderation/{kind}/{id}/approve", post(approve))
+        .route("/moderation/{kind}/{id}/reject", post(reject))
+        .route("/audit.json", get(audit))
+        .route("/users/search.json", get(search_users))
+        .route("/users/{uid}/details.json", get(user_details))
+        .route("/users/{uid}/ban", post(ban_user).delete(unban_user))
+        .route(
+            "/users/{uid}/groups/{group}",
+            put(add_user_group).delete(remove_user_group),
+        )
         .layer(axum::middleware::from_fn_with_state(state, requires_admin))
 }
+
+/// Result of reviewing a piece of content.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Review {
+    /// New moderation status of the content.
+    status: ModerationStatus,
+}
+
+async fn pending_comments(
+    State(state): State<Arc<AppState>>,
+    Query(page): Query<PageQuery>,
+) -> Result<Json<Page<Comment>>, Error> {
+    state.comments().list_pending(&page).await.map(Json)
+}
+
+async fn review_comment(
+    State(state): State<Arc<AppState>>,
+    Authorization(session): Authorization<Session>,
+    Path(comment_id): Path<CommentId>,
+    Json(review): Json<Review>,
+) -> Result<(), Error> {
+    state
+        .comments()
+        .review(&Actor::from(&session), comment_id, review.status)
+        .await
+}
+
+/// Request of rejecting content.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Rejection {
+    /// Reason of the rejection, shown to the uploader.
+    reason: String,
+}
+
+async fn moderation_queue(
+    State(state): State<Arc<AppState>>,
+    Path(kind): Path<ContentKind>,
+    Query(page): Query<PageQuery>,
+) -> Result<Json<Page<Item>>, Error> {
+    state.moderation().queue(kind, &page).await.map(Json)
+}
+
+async fn approve(
+    State(state): State<Arc<AppState>>,
+    Authorization(session): Authorization<Session>,
+    Path((kind, id)): Path<(ContentKind, i64)>,
+) -> Result<(), Error> {
+    state
+        .moderation()
+        .approve(&Actor::from(&session), kind, id)
+        .await
+}
+
+async fn reject(
+    State(state): State<Arc<AppState>>,
+    Authorization(session): Authorization<Session>,
+    Path((kind, id)): Path<(ContentKind, i64)>,
+    Json(rejection): Json<Rejection>,
+) -> Result<(), Error> {
+    state
+        .moderation()
+        .reject(&Actor::from(&session), kind, id, rejection.reason)
+        .await
+}
+
+async fn audit(
+    State(state): State<Arc<AppState>>,
+    Query(filter): Query<Filter>,
+    Query(page): Query<PageQuery>,
+) -> Result<Json<Page<Record>>, Error> {
+    state.audit_log().query(&filter, &page).await.map(Json)
+}
+
+async fn search_users(
+    State(state): State<Arc<AppState>>,
+    Query(search): Query<super::SearchQuery>,
+    Query(page): Query<PageQuery>,
+) -> Result<Json<Page<Details>>, Error> {
+    state.users().search(&search.q, &page).await.map(Json)
+}
+
+async fn user_details(
+    State(state): State<Arc<AppState>>,
+    Path(uid): Path<Uid>,
+) -> Result<Json<Details>, Error> {
+    state.users().details(uid).await.map(Json)
+}
+
+async fn ban_user(
+    State(state): State<Arc<AppState>>,
+    Authorization(session): Authorization<Session>,
+    Path(uid): Path<Uid>,
+    Json(request): Json<BanRequest>,
+) -> Result<(), Error> {
+    state
+        .users()
+        .ban(&Actor::from(&session), uid, request)
+        .await
+}
+
+async fn unban_user(
+    State(state): State<Arc<AppState>>,
+    Authorization(session): Authorization<Session>,
+    Path(uid): Path<Uid>,
+) -> Result<(), Error> {
+    state.users().unban(&Actor::from(&session), uid).await
+}
+
+async fn add_user_group(
+    State(state): State<Arc<AppState>>,
+    Authorization(session): Authorization<Session>,
+    Path((uid, group)): Path<(Uid, String)>,
+) -> Result<(), Error> {
+    state
+        .users()
+        .add_group(&Actor::from(&session), uid, &group)
+        .await
+}
+
+async fn remove_user_group(
+    State(state): State<Arc<AppState>>,
+    Authorization(session): Authorization<Session>,
+    Path((uid, group)): Path<(Uid, String)>,
+) -> Result<(), Error> {
+    state
+        .users()
+        .remove_group(&Actor::from(&session), uid, &group)
+        .await
+}
@@ -1,17 +1,22 @@
 use crate::{
     AppState,
     album::{AlbumId, Create, Profile},
+    audit::Actor,
     error::Error,
+    policy::Subject,
+    song,
     user::{extract::Authorization, session::Session},
+    util::page::{Page, PageQuery},
 };
 use axum::{
     Json, Router,
     body::Bytes,
-    extract::{DefaultBodyLimit, Path, State},
-    response::Redirect,
+    extract::{DefaultBodyLimit, Path, Query, State},
+    http::HeaderMap,
+    response::Response,
     routing::{get, post},
 };
-use std::{sync::Arc, time::Duration};
+use std::sync::Arc;
 
 pub fn router() -> Router<Arc<AppState>> {
     Router::new()
@@ -23,6 +28,8 @@ pub fn router() -> Router<Arc<AppState>> {
                 .layer(DefaultBodyLimit::max(512 * 1024)),
         )
         .route("/{id}/profile.json", get(profile))
+        .route("/{id}/songs.json", get(songs))
+        .route("/search.json", get(search))
 }
 
 async fn create(
@@ -31,26 +38,20 @@ async fn create(
     Json(create): Json<Create>,
 ) -> Result<Json<AlbumId>, Error> {
     session.will_create_album()?;
-    state
-        .albums()
-        .create(session.user.uid, create.into())
-        .await
-        .map(Json)
+    state.albums().create(&session.user, create).await.map(Json)
 }
 
 async fn cover_hq(
     State(state): State<Arc<AppState>>,
+    Authorization(session): Authorization<Option<Session>>,
     Path(album_id): Path<AlbumId>,
-) -> Result<Redirect, Error> {
-    state
-        .objects
-        .get_url(
-            state.albums().cover_hq(album_id).await?,
-            Duration::from_secs(10 * 60),
-        )
-        .await
-        .map(|url| Redirect::to(url.as_str()))
-        .map_err(Into::into)
+    headers: HeaderMap,
+) -> Result<Response, Error> {
+    let subject = session
+        .map(|x| Subject::from_user(&x.user))
+        .unwrap_or_else(Subject::anon);
+    let key = state.albums().cover_hq(album_id, &subject).await?;
+    super::delivery::deliver(&state, key, &headers).await
 }
 
 async fn set_cover(
@@ -59,12 +60,44 @@ async fn set_cover(
     Path(album_id): Path<AlbumId>,
     image: Bytes,
 ) -> Result<(), Error> {
-    state.albums().set_cover(album_id, image).await
+    let administrator = session.will_administrate().is_ok();
+    state
+        .albums()
+        .set_cover(&Actor::from(&session), album_id, image, administrator)
+        .await
 }
 
 async fn profile(
     State(state): State<Arc<AppState>>,
+    Authorization(session): Authorization<Option<Session>>,
     Path(album_id): Path<AlbumId>,
 ) -> Result<Json<Profile>, Error> {
-    state.albums().profile(album_id).await.map(Json)
+    let subject = session
+        .map(|x| Subject::from_user(&x.user))
+        .unwrap_or_else(Subject::anon);
+    state.albums().profile(album_id, &subject).await.map(Json)
+}
+
+async fn songs(
+    State(state): State<Arc<AppState>>,
+    Authorization(session): Authorization<Option<Session>>,
+    Path(album_id): Path<AlbumId>,
+    Query(page): Query<PageQuery>,
+) -> Result<Json<Page<song::Profile>>, Error> {
+    let subject = session
+        .map(|x| Subject::from_user(&x.user))
+        .unwrap_or_else(Subject::anon);
+    state
+        .songs()
+        .list_by_album(album_id, &subject, &page)
+        .await
+        .map(Json)
+}
+
+async fn search(
+    State(state): State<Arc<AppState>>,
+    Query(search): Query<super::SearchQuery>,
+    Query(page): Query<PageQuery>,
+) -> Result<Json<Page<Profile>>, Error> {
+    state.albums().search(&search.q, &page).await.map(Json)
 }
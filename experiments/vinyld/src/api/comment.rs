@@ -0,0 +1,118 @@
+use crate::{
+    AppState,
+    comment::{Comment, CommentId, Comments, Post},
+    error::Error,
+    user::{extract::Authorization, session::Session},
+    util::page::{Page, PageQuery},
+};
+use axum::{
+    Json, Router,
+    extract::{DefaultBodyLimit, Multipart, Path, Query, State},
+    http::HeaderMap,
+    response::Response,
+    routing::{delete, get, post},
+};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+pub fn router() -> Router<Arc<AppState>> {
+    Router::new()
+        .route(
+            "/post",
+            post(post_comment).layer(DefaultBodyLimit::max(
+                Comments::MAX_ATTACHMENTS * 4 * 1024 * 1024,
+            )),
+        )
+        .route("/{id}", delete(remove))
+        .route("/{id}/text", post(edit))
+        .route("/{id}/replies.json", get(replies))
+        .route("/{id}/attachments/{index}", get(attachment))
+}
+
+/// Request of editing a comment.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Edit {
+    /// New text of the comment.
+    text: String,
+}
+
+async fn post_comment(
+    State(state): State<Arc<AppState>>,
+    Authorization(session): Authorization<Session>,
+    mut multipart: Multipart,
+) -> Result<Json<CommentId>, Error> {
+    let mut info = None;
+    let mut attachments = Vec::new();
+
+    while let Some(field) = multipart.next_field().await? {
+        if field.name() == Some("info") {
+            info = Some(
+                serde_json::from_slice::<Post>(&field.bytes().await?)
+                    .map_err(Error::bad_request)?,
+            );
+        } else if field.name() == Some("attachment") {
+            attachments.push(field.bytes().await?);
+        }
+    }
+
+    let Some(info) = info else {
+        return Err(Error::bad_request("Form entry `info` was not written."));
+    };
+    state
+        .comments()
+        .post(&session.user, info, attachments)
+        .await
+        .map(Json)
+}
+
+async fn remove(
+    State(state): State<Arc<AppState>>,
+    Authorization(session): Authorization<Session>,
+    Path(comment_id): Path<CommentId>,
+) -> Result<(), Error> {
+    let administrator = session.will_administrate().is_ok();
+    state
+        .comments()
+        .remove(comment_id, &session.user, administrator)
+        .await
+}
+
+async fn edit(
+    State(state): State<Arc<AppState>>,
+    Authorization(session): Authorization<Session>,
+    Path(comment_id): Path<CommentId>,
+    Json(edit): Json<Edit>,
+) -> Result<(), Error> {
+    state
+        .comments()
+        .edit(comment_id, &session.user, edit.text)
+        .await
+}
+
+async fn replies(
+    State(state): State<Arc<AppState>>,
+    Authorization(session): Authorization<Option<Session>>,
+    Path(comment_id): Path<CommentId>,
+    Query(page): Query<PageQuery>,
+) -> Result<Json<Page<Comment>>, Error> {
+    let viewer = session.map(|x| x.user);
+    state
+        .comments()
+        .replies(comment_id, viewer.as_ref(), &page)
+        .await
+        .map(Json)
+}
+
+async fn attachment(
+    State(state): State<Arc<AppState>>,
+    Authorization(session): Authorization<Option<Session>>,
+    Path((comment_id, index)): Path<(CommentId, usize)>,
+    headers: HeaderMap,
+) -> Result<Response, Error> {
+    let viewer = session.map(|x| x.user);
+    let key = state
+        .comments()
+        .attachment(comment_id, index, viewer.as_ref())
+        .await?;
+    super::delivery::deliver(&state, key, &headers).await
+}
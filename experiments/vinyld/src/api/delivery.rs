@@ -0,0 +1,307 @@
+//! Delivery of objects to clients.
+
+use crate::{AppState, error::Error};
+use axum::{
+    body::Body,
+    http::{HeaderMap, HeaderValue, StatusCode, header},
+    response::{IntoResponse, Redirect, Response},
+};
+use chrono::{DateTime, Utc};
+use std::time::{Duration, SystemTime};
+use tokio_util::io::ReaderStream;
+use vinioss::{ByteRange, ObjectKey, ObjectMeta};
+
+/// Expiry of presigned URLs that clients are redirected to.
+const PRESIGNED_URL_EXPIRY: Duration = Duration::from_secs(10 * 60);
+
+/// Way of delivering objects to clients.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ObjectDelivery {
+    /// Clients are redirected to presigned URLs of the object storage.
+    #[default]
+    Redirect,
+
+    /// Objects are streamed by the server itself. This is useful when the object storage is not reachable from
+    /// clients.
+    Proxy,
+}
+
+/// Returns a response that delivers the object to the client, in the configured way.
+///
+/// In proxy mode, single-range `Range` requests, `If-Range`, `If-None-Match` and `If-Modified-Since` are supported, so
+/// clients can seek inside audio files and revalidate cached objects.
+pub async fn deliver(
+    state: &AppState,
+    key: ObjectKey,
+    headers: &HeaderMap,
+) -> Result<Response, Error> {
+    match state.object_delivery {
+        ObjectDelivery::Redirect => {
+            let url = state.objects.get_url(key, PRESIGNED_URL_EXPIRY).await?;
+            Ok(Redirect::to(url.as_str()).into_response())
+        }
+        ObjectDelivery::Proxy => proxy(state, key, headers).await,
+    }
+}
+
+/// Streams the object to the client.
+///
+/// Preconditions are checked against metadata of the object first, so no download is started for `304` and `416`
+/// responses.
+async fn proxy(state: &AppState, key: ObjectKey, headers: &HeaderMap) -> Result<Response, Error> {
+    let meta = state.objects.head(key.clone()).await?;
+
+    if not_modified(headers, &meta) {
+        let mut response = StatusCode::NOT_MODIFIED.into_response();
+        insert_validators(response.headers_mut(), &meta)?;
+        return Ok(response);
+    }
+
+    let mut range = headers
+        .get(header::RANGE)
+        .and_then(|x| x.to_str().ok())
+        .and_then(parse_range);
+    let if_range = headers.get(header::IF_RANGE).and_then(|x| x.to_str().ok());
+    if if_range.is_some_and(|x| meta.e_tag.as_deref() != Some(x)) {
+        // The client's copy is outdated, so the whole object is sent instead.
+        range = None;
+    }
+    if range.is_some_and(|x| !satisfiable(x, meta.len)) {
+        return range_not_satisfiable(meta.len);
+    }
+
+    let object = match state.objects.get_range(key.clone(), range).await {
+        Err(vinioss::Error::InvalidRange) => return range_not_satisfiable(meta.len),
+        object => object?,
+    };
+
+    let content_type = content_type(&key)
+        .map(String::from)
+        .or(object.content_type)
+        .unwrap_or_else(|| "application/octet-stream".into());
+    let mut response = Response::new(Body::from_stream(ReaderStream::new(object.stream)));
+    let headers = response.headers_mut();
+    headers.insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_str(&content_type).map_err(Error::internal)?,
+    );
+    headers.insert(header::CONTENT_LENGTH, object.len.into());
+    headers.insert(header::ACCEPT_RANGES, HeaderValue::from_static("bytes"));
+    insert_validators(headers, &meta)?;
+    if let Some(e_tag) = &object.e_tag {
+        headers.insert(
+            header::ETAG,
+            HeaderValue::from_str(e_tag).map_err(Error::internal)?,
+        );
+    }
+    if range.is_some() {
+        let content_range = format!(
+            "bytes {}-{}/{}",
+            object.start,
+            (object.start + object.len).saturating_sub(1),
+            object.total_len
+        );
+        headers.insert(
+            header::CONTENT_RANGE,
+            HeaderValue::from_str(&content_range).map_err(Error::internal)?,
+        );
+        *response.status_mut() = StatusCode::PARTIAL_CONTENT;
+    }
+
+    Ok(response)
+}
+
+/// Returns `true` if the client's cached copy of the object is still fresh, as told by `If-None-Match` or, if that is
+/// absent, `If-Modified-Since`.
+fn not_modified(headers: &HeaderMap, meta: &ObjectMeta) -> bool {
+    if let Some(if_none_match) = headers.get(header::IF_NONE_MATCH) {
+        let Some(e_tag) = &meta.e_tag else {
+            return false;
+        };
+        // `If-None-Match` uses weak comparison, so weak validators are accepted as well.
+        let matches = |x: &str| {
+            let x = x.trim().trim_start_matches("W/");
+            x == "*" || x == e_tag
+        };
+        return if_none_match
+            .to_str()
+            .is_ok_and(|x| x.split(',').any(matches));
+    }
+
+    let since = headers
+        .get(header::IF_MODIFIED_SINCE)
+        .and_then(|x| x.to_str().ok())
+        .and_then(|x| DateTime::parse_from_rfc2822(x).ok());
+    match (since, meta.last_modified) {
+        // HTTP dates have a precision of seconds.
+        (Some(since), Some(last_modified)) => {
+            DateTime::<Utc>::from(last_modified).timestamp() <= since.timestamp()
+        }
+        _ => false,
+    }
+}
+
+/// Inserts `ETag` and `Last-Modified` of the object into the headers.
+fn insert_validators(headers: &mut HeaderMap, meta: &ObjectMeta) -> Result<(), Error> {
+    if let Some(e_tag) = &meta.e_tag {
+        headers.insert(
+            header::ETAG,
+            HeaderValue::from_str(e_tag).map_err(Error::internal)?,
+        );
+    }
+    if let Some(last_modified) = meta.last_modified {
+        headers.insert(
+            header::LAST_MODIFIED,
+            HeaderValue::from_str(&http_date(last_modified)).map_err(Error::internal)?,
+        );
+    }
+    Ok(())
+}
+
+/// Formats a time as an HTTP date, like `Sun, 06 Nov 1994 08:49:37 GMT`.
+fn http_date(time: SystemTime) -> String {
+    DateTime::<Utc>::from(time)
+        .format("%a, %d %b %Y %H:%M:%S GMT")
+        .to_string()
+}
+
+/// Returns a `416` response for an object in given size.
+fn range_not_satisfiable(len: u64) -> Result<Response, Error> {
+    let mut response = Error::range_not_satisfiable().into_response();
+    response.headers_mut().insert(
+        header::CONTENT_RANGE,
+        HeaderValue::from_str(&format!("bytes */{len}")).map_err(Error::internal)?,
+    );
+    Ok(response)
+}
+
+/// Returns `true` if the range overlaps an object in given size.
+fn satisfiable(range: ByteRange, len: u64) -> bool {
+    match range {
+        ByteRange::From(start) | ByteRange::Inclusive(start, _) => start < len,
+        ByteRange::Suffix(suffix) => suffix > 0 && len > 0,
+    }
+}
+
+/// Parses a `Range` header. Returns `None` if the header is malformed or requests multiple ranges, in which case the
+/// whole object should be sent.
+fn parse_range(value: &str) -> Option<ByteRange> {
+    let spec = value.strip_prefix("bytes=")?.trim();
+    if spec.contains(',') {
+        return None;
+    }
+
+    match spec.split_once('-')? {
+        ("", len) => Some(ByteRange::Suffix(len.parse().ok()?)),
+        (start, "") => Some(ByteRange::From(start.parse().ok()?)),
+        (start, end) => {
+            let (start, end) = (start.parse().ok()?, end.parse().ok()?);
+            (start <= end).then_some(ByteRange::Inclusive(start, end))
+        }
+    }
+}
+
+/// Returns MIME type of an object, inferred from its key.
+fn content_type(key: &ObjectKey) -> Option<&'static str> {
+    let (_, extension) = key.0.rsplit_once('.')?;
+    match extension {
+        "flac" => Some("audio/flac"),
+        "opus" => Some("audio/ogg; codecs=opus"),
+        "avif" => Some("image/avif"),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn meta(e_tag: Option<&str>, last_modified: Option<u64>) -> ObjectMeta {
+        ObjectMeta {
+            len: 1000,
+            e_tag: e_tag.map(String::from),
+            content_type: None,
+            last_modified: last_modified.map(|x| SystemTime::UNIX_EPOCH + Duration::from_secs(x)),
+        }
+    }
+
+    fn header_map(pairs: &[(header::HeaderName, &'static str)]) -> HeaderMap {
+        pairs
+            .iter()
+            .map(|(name, value)| (name.clone(), HeaderValue::from_static(value)))
+            .collect()
+    }
+
+    #[test]
+    fn if_none_match() {
+        let meta = meta(Some("\"abc\""), None);
+        for (value, expected) in [
+            ("\"abc\"", true),
+            ("W/\"abc\"", true),
+            ("\"xyz\", \"abc\"", true),
+            ("*", true),
+            ("\"xyz\"", false),
+        ] {
+            let headers = header_map(&[(header::IF_NONE_MATCH, value)]);
+            assert_eq!(not_modified(&headers, &meta), expected, "{value}");
+        }
+    }
+
+    #[test]
+    fn if_modified_since() {
+        // 784111777 is `Sun, 06 Nov 1994 08:49:37 GMT`.
+        let headers = header_map(&[(header::IF_MODIFIED_SINCE, "Sun, 06 Nov 1994 08:49:37 GMT")]);
+        assert!(not_modified(&headers, &meta(None, Some(784111777))));
+        assert!(not_modified(&headers, &meta(None, Some(784111000))));
+        assert!(!not_modified(&headers, &meta(None, Some(784111778))));
+        assert!(!not_modified(&headers, &meta(None, None)));
+
+        let malformed = header_map(&[(header::IF_MODIFIED_SINCE, "yesterday")]);
+        assert!(!not_modified(&malformed, &meta(None, Some(0))));
+    }
+
+    #[test]
+    fn if_none_match_takes_precedence() {
+        let headers = header_map(&[
+            (header::IF_NONE_MATCH, "\"xyz\""),
+            (header::IF_MODIFIED_SINCE, "Sun, 06 Nov 1994 08:49:37 GMT"),
+        ]);
+        assert!(!not_modified(&headers, &meta(Some("\"abc\""), Some(0))));
+    }
+
+    #[test]
+    fn http_dates() {
+        let time = SystemTime::UNIX_EPOCH + Duration::from_secs(784111777);
+        assert_eq!(http_date(time), "Sun, 06 Nov 1994 08:49:37 GMT");
+    }
+
+    #[test]
+    fn ranges() {
+        assert_eq!(
+            parse_range("bytes=0-499"),
+            Some(ByteRange::Inclusive(0, 499))
+        );
+        assert_eq!(parse_range("bytes=500-"), Some(ByteRange::From(500)));
+        assert_eq!(parse_range("bytes=-500"), Some(ByteRange::Suffix(500)));
+        assert_eq!(parse_range("bytes=500-499"), None);
+        assert_eq!(parse_range("bytes=0-1,5-9"), None);
+        assert_eq!(parse_range("items=0-1"), None);
+        assert_eq!(parse_range("bytes=a-b"), None);
+    }
+
+    #[test]
+    fn satisfiable_ranges() {
+        assert!(satisfiable(ByteRange::Inclusive(999, 2000), 1000));
+        assert!(!satisfiable(ByteRange::From(1000), 1000));
+        assert!(satisfiable(ByteRange::Suffix(2000), 1000));
+        assert!(!satisfiable(ByteRange::Suffix(0), 1000));
+        assert!(!satisfiable(ByteRange::Suffix(1), 0));
+    }
+
+    #[test]
+    fn unsatisfiable_range_response() {
+        let response = range_not_satisfiable(1000).unwrap();
+        assert_eq!(response.status(), StatusCode::RANGE_NOT_SATISFIABLE);
+        assert_eq!(response.headers()[header::CONTENT_RANGE], "bytes */1000");
+    }
+}
@@ -0,0 +1,42 @@
+use crate::{
+    AppState,
+    error::Error,
+    lyrics::{LyricsId, Version},
+    policy::Subject,
+    user::{extract::Authorization, session::Session},
+};
+use axum::{
+    Json, Router,
+    extract::{Path, State},
+    routing::{delete, get},
+};
+use std::sync::Arc;
+
+pub fn router() -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/{id}", delete(remove))
+        .route("/{id}/content.json", get(content))
+}
+
+async fn content(
+    State(state): State<Arc<AppState>>,
+    Authorization(session): Authorization<Option<Session>>,
+    Path(lyrics_id): Path<LyricsId>,
+) -> Result<Json<Version>, Error> {
+    let subject = session
+        .map(|x| Subject::from_user(&x.user))
+        .unwrap_or_else(Subject::anon);
+    state.lyrics().get(lyrics_id, &subject).await.map(Json)
+}
+
+async fn remove(
+    State(state): State<Arc<AppState>>,
+    Authorization(session): Authorization<Session>,
+    Path(lyrics_id): Path<LyricsId>,
+) -> Result<(), Error> {
+    let administrator = session.will_administrate().is_ok();
+    state
+        .lyrics()
+        .remove(lyrics_id, &session.user, administrator)
+        .await
+}
@@ -1,6 +1,12 @@
 mod admin;
 mod album;
+mod comment;
+pub mod delivery;
+mod lyrics;
+mod oauth;
 mod personalized;
+mod playlist;
+mod rate_limit;
 mod song;
 mod user;
 
@@ -20,16 +26,27 @@ pub const VERSION: &str = "9999";
 /// Root routes.
 pub fn router(state: Arc<AppState>) -> Router<Arc<AppState>> {
     Router::new()
-        .nest("/user", user::router())
+        .nest("/user", user::router(state.clone()))
         .nest("/admin", admin::router(state.clone()))
         .nest("/song", song::router())
         .nest("/album", album::router())
+        .nest("/playlist", playlist::router())
+        .nest("/comment", comment::router())
+        .nest("/lyrics", lyrics::router())
+        .nest("/oauth", oauth::router(state.clone()))
         .nest("/personalized", personalized::router())
         .route("/version.txt", get(|| async { VERSION }))
         .route("/site_info.json", get(site_info))
         .route("/license.html", get(license))
 }
 
+/// Request of a full-text search.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct SearchQuery {
+    /// Text to search for.
+    q: String,
+}
+
 /// Information of the site server.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 struct SiteInfo {
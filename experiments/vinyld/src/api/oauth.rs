@@ -0,0 +1,265 @@
+use super::rate_limit;
+use crate::{
+    AppState,
+    app_settings::SiteName,
+    error::Error,
+    oauth::{AuthorizeRequest, Client, ClientId, Exchange, RegisterClient},
+    user::{
+        User,
+        extract::Authorization,
+        login::UserProber,
+        session::{AccessToken, Permissions, RefreshToken, Session},
+    },
+};
+use axum::{
+    Form, Json, Router,
+    extract::{Path, Query, State},
+    http::header,
+    middleware::from_fn_with_state,
+    response::{Html, IntoResponse, Redirect, Response},
+    routing::{delete, get, post},
+};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+/// Template of the consent page.
+const CONSENT_PAGE: &str = include_str!("../../resources/OAuthConsent.html");
+
+pub fn router(state: Arc<AppState>) -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/clients/register", post(register_client))
+        .route("/clients.json", get(clients))
+        .route("/clients/{id}", delete(remove_client))
+        .route("/authorize", get(consent))
+        .route(
+            "/authorize",
+            post(decide).layer(from_fn_with_state(state.clone(), rate_limit::login)),
+        )
+        .route(
+            "/token",
+            post(token).layer(from_fn_with_state(state, rate_limit::refresh_token)),
+        )
+}
+
+async fn register_client(
+    State(state): State<Arc<AppState>>,
+    Authorization(session): Authorization<Session>,
+    Json(register): Json<RegisterClient>,
+) -> Result<Json<ClientId>, Error> {
+    state
+        .oauth()
+        .register_client(&session, register)
+        .await
+        .map(Json)
+}
+
+async fn clients(
+    State(state): State<Arc<AppState>>,
+    Authorization(session): Authorization<Session>,
+) -> Result<Json<Vec<Client>>, Error> {
+    state.oauth().list_clients(&session).await.map(Json)
+}
+
+async fn remove_client(
+    State(state): State<Arc<AppState>>,
+    Authorization(session): Authorization<Session>,
+    Path(client_id): Path<ClientId>,
+) -> Result<(), Error> {
+    state.oauth().remove_client(&session, &client_id).await
+}
+
+async fn consent(
+    State(state): State<Arc<AppState>>,
+    Query(request): Query<AuthorizeRequest>,
+) -> Result<Response, Error> {
+    render_consent(&state, &request, "").await
+}
+
+/// Decision of the user on the consent page.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Decision {
+    #[serde(flatten)]
+    request: AuthorizeRequest,
+    decision: String,
+    #[serde(default)]
+    username: String,
+    #[serde(default)]
+    password: String,
+
+    /// TOTP or recovery code, required if the user has enabled two-factor authentication.
+    #[serde(default)]
+    code: String,
+}
+
+async fn decide(
+    State(state): State<Arc<AppState>>,
+    Form(decision): Form<Decision>,
+) -> Result<Response, Error> {
+    let request = &decision.request;
+    state.oauth().validate(request).await?;
+
+    if decision.decision != "approve" {
+        let mut redirect = request.redirect_uri.clone();
+        redirect
+            .query_pairs_mut()
+            .append_pair("error", "access_denied");
+        if let Some(x) = &request.state {
+            redirect.query_pairs_mut().append_pair("state", x);
+        }
+        return Ok(Redirect::to(redirect.as_str()).into_response());
+    }
+
+    let redirect = match check_user(&state, &decision).await {
+        Ok((user, allowed)) => state.oauth().authorize(&user, allowed, request).await,
+        Err(err) => Err(err),
+    };
+    match redirect {
+        Ok(x) => Ok(Redirect::to(x.as_str()).into_response()),
+        Err(err) => render_consent(&state, request, &err.message).await,
+    }
+}
+
+/// Checks the credentials entered on the consent page, including the second factor if it's required, and returns
+/// the user with permissions that the login allows.
+async fn check_user(state: &AppState, decision: &Decision) -> Result<(User, Permissions), Error> {
+    let prober = UserProber::Username(decision.username.clone());
+    let user = state
+        .users()
+        .check_password(&prober, &decision.password)
+        .await?;
+    if state.users().two_factor_enabled(user.uid).await? {
+        if decision.code.is_empty() {
+            return Err(Error::bad_request(
+                "Enter the code from your authenticator app or a recovery code.",
+            ));
+        }
+        state
+            .users()
+            .check_second_factor(user.uid, &decision.code)
+            .await?;
+        return Ok((user, Permissions::all()));
+    }
+
+    let allowed = state.users().login_permissions(&user).await;
+    Ok((user, allowed))
+}
+
+/// Renders the consent page of a validated authorization request.
+///
+/// The page asks for credentials, so it forbids being framed by other sites.
+async fn render_consent(
+    state: &AppState,
+    request: &AuthorizeRequest,
+    notice: &str,
+) -> Result<Response, Error> {
+    let (client, permissions) = state.oauth().validate(request).await?;
+
+    let permissions = (permissions | Permissions::REFRESH)
+        .iter()
+        .map(|x| format!("<li>{}</li>", describe(x)))
+        .collect::<String>();
+    let fields = serde_json::to_value(request)
+        .map_err(Error::internal)?
+        .as_object()
+        .into_iter()
+        .flatten()
+        .filter_map(|(name, value)| Some((name, value.as_str()?)))
+        .map(|(name, value)| {
+            format!(
+                r#"<input type="hidden" name="{}" value="{}" />"#,
+                escape_html(name),
+                escape_html(value),
+            )
+        })
+        .collect::<String>();
+
+    let page = Html(
+        CONSENT_PAGE
+            .replace(
+                "{{site}}",
+                &escape_html(&state.app_settings().get::<SiteName>().await),
+            )
+            .replace("{{client}}", &escape_html(&client.name))
+            .replace("{{permissions}}", &permissions)
+            .replace("{{notice}}", &escape_html(notice))
+            .replace("{{fields}}", &fields),
+    );
+    Ok((
+        [
+            (header::X_FRAME_OPTIONS, "DENY"),
+            (header::CONTENT_SECURITY_POLICY, "frame-ancestors 'none'"),
+        ],
+        page,
+    )
+        .into_response())
+}
+
+/// Returns a description of a single permission, shown to the user on the consent page.
+fn describe(permission: Permissions) -> &'static str {
+    match permission {
+        Permissions::REFRESH => "Stay signed in",
+        Permissions::MANAGE_SESSIONS => "Manage your sessions and authorized apps",
+        Permissions::ADMINISTRATION => "Administrate the server",
+        Permissions::UPLOAD_SONG => "Upload songs",
+        Permissions::CREATE_ALBUM => "Create albums",
+        _ => "Unknown permission",
+    }
+}
+
+/// Escapes text to be placed into HTML.
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
+/// Request to the token endpoint.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct TokenRequest {
+    grant_type: String,
+
+    /// Authorization code to exchange, if `grant_type` is `authorization_code`.
+    #[serde(flatten)]
+    exchange: Option<Exchange>,
+
+    /// Refresh token to renew, if `grant_type` is `refresh_token`.
+    refresh_token: Option<RefreshToken>,
+}
+
+/// Response of the token endpoint.
+#[derive(Debug, Clone, Serialize)]
+struct TokenResponse {
+    access_token: AccessToken,
+    refresh_token: RefreshToken,
+    token_type: &'static str,
+    expires_in: u64,
+}
+
+async fn token(
+    State(state): State<Arc<AppState>>,
+    Form(request): Form<TokenRequest>,
+) -> Result<Json<TokenResponse>, Error> {
+    let (refresh_token, access_token) = match (
+        request.grant_type.as_str(),
+        request.exchange,
+        request.refresh_token,
+    ) {
+        ("authorization_code", Some(exchange), _) => {
+            let response = state.oauth().exchange(&exchange).await?;
+            (response.refresh_token, response.access_token)
+        }
+        ("refresh_token", _, Some(refresh_token)) => {
+            state.users().refresh_token(&refresh_token).await?
+        }
+        _ => return Err(Error::bad_request("unsupported or incomplete grant")),
+    };
+
+    Ok(Json(TokenResponse {
+        access_token,
+        refresh_token,
+        token_type: "Vinyl-Token",
+        expires_in: AccessToken::VALID_IN.as_secs(),
+    }))
+}
@@ -3,7 +3,11 @@ use crate::{
     personalized::Homepage,
     user::{extract::Authorization, session::Session},
 };
-use axum::{Json, Router, extract::State, routing::get};
+use axum::{
+    Json, Router,
+    extract::State,
+    routing::{get, post},
+};
 use std::sync::Arc;
 
 pub fn router() -> Router<Arc<AppState>> {
@@ -0,0 +1,182 @@
+use crate::{
+    AppState,
+    error::Error,
+    playlist::{Create, Entry, PlaylistId, Policies, Profile},
+    policy::Subject,
+    song::SongId,
+    user::{extract::Authorization, session::Session},
+    util::page::{Page, PageQuery},
+};
+use axum::{
+    Json, Router,
+    body::Bytes,
+    extract::{DefaultBodyLimit, Path, Query, State},
+    http::HeaderMap,
+    response::Response,
+    routing::{delete, get, post, put},
+};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+pub fn router() -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/create", post(create))
+        .route("/{id}", delete(remove))
+        .route(
+            "/{id}/cover/hq.avif",
+            get(cover_hq)
+                .put(set_cover)
+                .layer(DefaultBodyLimit::max(512 * 1024)),
+        )
+        .route("/{id}/profile.json", get(profile).put(edit))
+        .route("/{id}/policies.json", put(set_policies))
+        .route("/{id}/entries.json", get(entries).post(add_entry))
+        .route("/{id}/entries/{song}", delete(remove_entry))
+        .route("/{id}/order.json", put(reorder))
+}
+
+/// Request of adding a song into a playlist.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct AddEntry {
+    /// The song to be added.
+    song: SongId,
+}
+
+fn subject(session: Option<Session>) -> Subject {
+    session
+        .map(|x| Subject::from_user(&x.user))
+        .unwrap_or_else(Subject::anon)
+}
+
+async fn create(
+    State(state): State<Arc<AppState>>,
+    Authorization(session): Authorization<Session>,
+    Json(create): Json<Create>,
+) -> Result<Json<PlaylistId>, Error> {
+    state
+        .playlists()
+        .create(session.user.uid, create)
+        .await
+        .map(Json)
+}
+
+async fn remove(
+    State(state): State<Arc<AppState>>,
+    Authorization(session): Authorization<Session>,
+    Path(playlist_id): Path<PlaylistId>,
+) -> Result<(), Error> {
+    state
+        .playlists()
+        .remove(playlist_id, &subject(Some(session)))
+        .await
+}
+
+async fn cover_hq(
+    State(state): State<Arc<AppState>>,
+    Authorization(session): Authorization<Option<Session>>,
+    Path(playlist_id): Path<PlaylistId>,
+    headers: HeaderMap,
+) -> Result<Response, Error> {
+    let key = state
+        .playlists()
+        .cover_hq(playlist_id, &subject(session))
+        .await?;
+    super::delivery::deliver(&state, key, &headers).await
+}
+
+async fn set_cover(
+    State(state): State<Arc<AppState>>,
+    Authorization(session): Authorization<Session>,
+    Path(playlist_id): Path<PlaylistId>,
+    image: Bytes,
+) -> Result<(), Error> {
+    state
+        .playlists()
+        .set_cover(playlist_id, &subject(Some(session)), image)
+        .await
+}
+
+async fn profile(
+    State(state): State<Arc<AppState>>,
+    Authorization(session): Authorization<Option<Session>>,
+    Path(playlist_id): Path<PlaylistId>,
+) -> Result<Json<Profile>, Error> {
+    state
+        .playlists()
+        .profile(playlist_id, &subject(session))
+        .await
+        .map(Json)
+}
+
+async fn edit(
+    State(state): State<Arc<AppState>>,
+    Authorization(session): Authorization<Session>,
+    Path(playlist_id): Path<PlaylistId>,
+    Json(edit): Json<Create>,
+) -> Result<(), Error> {
+    state
+        .playlists()
+        .edit(playlist_id, &subject(Some(session)), edit)
+        .await
+}
+
+async fn set_policies(
+    State(state): State<Arc<AppState>>,
+    Authorization(session): Authorization<Session>,
+    Path(playlist_id): Path<PlaylistId>,
+    Json(policies): Json<Policies>,
+) -> Result<(), Error> {
+    state
+        .playlists()
+        .set_policies(playlist_id, &subject(Some(session)), policies)
+        .await
+}
+
+async fn entries(
+    State(state): State<Arc<AppState>>,
+    Authorization(session): Authorization<Option<Session>>,
+    Path(playlist_id): Path<PlaylistId>,
+    Query(page): Query<PageQuery>,
+) -> Result<Json<Page<Entry>>, Error> {
+    state
+        .playlists()
+        .entries(playlist_id, &subject(session), &page)
+        .await
+        .map(Json)
+}
+
+async fn add_entry(
+    State(state): State<Arc<AppState>>,
+    Authorization(session): Authorization<Session>,
+    Path(playlist_id): Path<PlaylistId>,
+    Json(add): Json<AddEntry>,
+) -> Result<(), Error> {
+    let uid = session.user.uid;
+    state
+        .playlists()
+        .add_entry(playlist_id, &subject(Some(session)), uid, add.song)
+        .await
+}
+
+async fn remove_entry(
+    State(state): State<Arc<AppState>>,
+    Authorization(session): Authorization<Session>,
+    Path((playlist_id, song_id)): Path<(PlaylistId, SongId)>,
+) -> Result<(), Error> {
+    state
+        .playlists()
+        .remove_entry(playlist_id, &subject(Some(session)), song_id)
+        .await
+}
+
+async fn reorder(
+    State(state): State<Arc<AppState>>,
+    Authorization(session): Authorization<Session>,
+    Path(playlist_id): Path<PlaylistId>,
+    Json(order): Json<Vec<SongId>>,
+) -> Result<(), Error> {
+    state
+        .playlists()
+        .reorder(playlist_id, &subject(Some(session)), order)
+        .await
+}
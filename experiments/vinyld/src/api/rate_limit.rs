@@ -0,0 +1,181 @@
+//! Middlewares limiting the rate of requests to sensitive routes.
+//!
+//! Requests are counted against the budget of the route once for the client address, and once for every username,
+//! token and credential found in the query, the JSON or form body, or the `Authorization` header. Budgets are
+//! configured by [`RateLimits`].
+
+use crate::{AppState, app_settings::RateLimits, error::Error, rate_limit::Budget, util::secret};
+use axum::{
+    body::{Body, Bytes},
+    extract::{ConnectInfo, Request, State},
+    http::header,
+    middleware::Next,
+    response::Response,
+};
+use serde_json::{Map, Value};
+use std::{net::SocketAddr, sync::Arc};
+
+/// Maximum size of bodies of rate limited requests, which are buffered to find the keys in them.
+const MAX_BODY_SIZE: usize = 64 * 1024;
+
+/// Limits requests of logging in.
+pub async fn login(
+    State(state): State<Arc<AppState>>,
+    request: Request,
+    next: Next,
+) -> Result<Response, Error> {
+    let budget = state.app_settings().get::<RateLimits>().await.login;
+    limit(&state, "login", &budget, request, next).await
+}
+
+/// Limits requests of registering.
+pub async fn register(
+    State(state): State<Arc<AppState>>,
+    request: Request,
+    next: Next,
+) -> Result<Response, Error> {
+    let budget = state.app_settings().get::<RateLimits>().await.register;
+    limit(&state, "register", &budget, request, next).await
+}
+
+/// Limits requests of refreshing tokens.
+pub async fn refresh_token(
+    State(state): State<Arc<AppState>>,
+    request: Request,
+    next: Next,
+) -> Result<Response, Error> {
+    let budget = state.app_settings().get::<RateLimits>().await.refresh_token;
+    limit(&state, "refresh_token", &budget, request, next).await
+}
+
+/// Limits requests of sending verification codes and resetting passwords.
+pub async fn send_code(
+    State(state): State<Arc<AppState>>,
+    request: Request,
+    next: Next,
+) -> Result<Response, Error> {
+    let budget = state.app_settings().get::<RateLimits>().await.send_code;
+    limit(&state, "send_code", &budget, request, next).await
+}
+
+async fn limit(
+    state: &AppState,
+    route: &str,
+    budget: &Budget,
+    request: Request,
+    next: Next,
+) -> Result<Response, Error> {
+    let (parts, body) = request.into_parts();
+    if parts
+        .headers
+        .get(header::CONTENT_LENGTH)
+        .and_then(|x| x.to_str().ok()?.parse::<usize>().ok())
+        .is_some_and(|x| x > MAX_BODY_SIZE)
+    {
+        return Err(Error::payload_too_large());
+    }
+    let body = axum::body::to_bytes(body, MAX_BODY_SIZE)
+        .await
+        .map_err(|_| Error::payload_too_large())?;
+    let request = Request::from_parts(parts, Body::from(body.clone()));
+
+    // A key carried twice, like in both the query and the body, is counted once.
+    let mut keys = keys(state, &request, &body)
+        .into_iter()
+        .map(|x| format!("{route}/{x}"))
+        .collect::<Vec<_>>();
+    keys.sort();
+    keys.dedup();
+    state.rate_limiter.hit(&keys, budget)?;
+
+    Ok(next.run(request).await)
+}
+
+/// Returns the keys that the request with given body is counted under.
+fn keys(state: &AppState, request: &Request, body: &Bytes) -> Vec<String> {
+    let mut keys = Vec::new();
+
+    if let Some(address) = client_address(state, request) {
+        keys.push(format!("ip/{address}"));
+    }
+
+    for (name, value) in url::form_urlencoded::parse(request.uri().query().unwrap_or("").as_bytes())
+    {
+        keys.extend(field_key(&name, &value));
+    }
+
+    let content_type = request
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|x| x.to_str().ok())
+        .and_then(|x| x.split(';').next())
+        .map(|x| x.trim().to_ascii_lowercase());
+    match content_type.as_deref() {
+        Some("application/json") => {
+            let fields = serde_json::from_slice::<Map<String, Value>>(body).unwrap_or_default();
+            for (name, value) in &fields {
+                match value {
+                    Value::String(value) => keys.extend(field_key(name, value)),
+                    // WebAuthn assertions are counted under the credential they are signed with.
+                    Value::Object(credential) if name == "credential" => {
+                        if let Some(Value::String(id)) = credential.get("id") {
+                            keys.push(format!("credential/{id}"));
+                        }
+                    }
+                    _ => (),
+                }
+            }
+        }
+        Some("application/x-www-form-urlencoded") => {
+            for (name, value) in url::form_urlencoded::parse(body) {
+                keys.extend(field_key(&name, &value));
+            }
+        }
+        _ => (),
+    }
+
+    if let Some(token) = request
+        .headers()
+        .get("Authorization")
+        .and_then(|x| x.to_str().ok())
+    {
+        keys.push(format!("token/{}", secret::hash(token)));
+    }
+
+    keys
+}
+
+/// Returns the address of the client.
+///
+/// Behind `n` trusted proxies, the address is the `n`-th entry from the right of `X-Forwarded-For`, since entries on
+/// its left are supplied by the client.
+fn client_address(state: &AppState, request: &Request) -> Option<String> {
+    let trusted_proxies = state.rate_limiter.trusted_proxies;
+    let forwarded_for = request
+        .headers()
+        .get_all("X-Forwarded-For")
+        .iter()
+        .filter_map(|x| x.to_str().ok())
+        .flat_map(|x| x.split(','))
+        .map(str::trim)
+        .collect::<Vec<_>>();
+    if trusted_proxies > 0
+        && let Some(address) = forwarded_for.iter().rev().nth(trusted_proxies - 1)
+    {
+        return Some(address.to_string());
+    }
+
+    request
+        .extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|x| x.0.ip().to_string())
+}
+
+/// Returns the key of a field of the query or the body, if it identifies an account or a token.
+fn field_key(name: &str, value: &str) -> Option<String> {
+    match name {
+        "username" | "email" | "phone" => Some(format!("user/{}", value.to_lowercase())),
+        "refresh_token" | "challenge" => Some(format!("token/{}", secret::hash(value))),
+        _ => None,
+    }
+}
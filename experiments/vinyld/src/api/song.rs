@@ -1,16 +1,22 @@
 use crate::{
     AppState,
+    comment::Comment,
     error::Error,
-    song::{AudioQuality, Profile, SongId, Upload},
+    lyrics,
+    policy::Subject,
+    song::{AudioQuality, Profile, SongId, Upload, transcode::JobStatus},
     user::{extract::Authorization, session::Session},
+    util::page::{Page, PageQuery},
 };
 use axum::{
     Json, Router,
-    extract::{DefaultBodyLimit, Multipart, Path, State},
-    response::Redirect,
+    extract::{DefaultBodyLimit, Multipart, Path, Query, State},
+    http::HeaderMap,
+    response::Response,
     routing::{get, post},
 };
-use std::{sync::Arc, time::Duration};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
 use tokio::io::AsyncWriteExt;
 
 pub fn router() -> Router<Arc<AppState>> {
@@ -21,6 +27,13 @@ pub fn router() -> Router<Arc<AppState>> {
         )
         .route("/{id}/audio/{quality}", get(audio))
         .route("/{id}/audio/profile.json", get(profile))
+        .route("/{id}/audio/job.json", get(job))
+        .route("/{id}/comments.json", get(comments))
+        .route("/{id}/lyrics/upload", post(upload_lyrics))
+        .route("/{id}/lyrics/list.json", get(list_lyrics))
+        .route("/{id}/lyrics/authoritative.json", get(authoritative_lyrics))
+        .route("/recent.json", get(recent))
+        .route("/search.json", get(search))
 }
 
 async fn upload(
@@ -73,7 +86,8 @@ async fn audio(
     State(app_state): State<Arc<AppState>>,
     Authorization(session): Authorization<Option<Session>>,
     Path((song_id, quality)): Path<(SongId, AudioQuality)>,
-) -> Result<Redirect, Error> {
+    headers: HeaderMap,
+) -> Result<Response, Error> {
     let object_key = app_state
         .songs()
         .audio(song_id)
@@ -82,18 +96,121 @@ async fn audio(
         .invoke()
         .await?;
 
-    Ok(Redirect::to(
-        app_state
-            .objects
-            .get_url(object_key, Duration::from_secs(10 * 60))
-            .await?
-            .as_str(),
-    ))
+    super::delivery::deliver(&app_state, object_key, &headers).await
 }
 
 async fn profile(
     State(app_state): State<Arc<AppState>>,
+    Authorization(session): Authorization<Option<Session>>,
     Path(song_id): Path<SongId>,
 ) -> Result<Json<Profile>, Error> {
-    app_state.songs().profile(song_id).await.map(Json)
+    let subject = session
+        .map(|x| Subject::from_user(&x.user))
+        .unwrap_or_else(Subject::anon);
+    app_state.songs().profile(song_id, &subject).await.map(Json)
+}
+
+async fn job(
+    State(app_state): State<Arc<AppState>>,
+    Authorization(session): Authorization<Session>,
+    Path(song_id): Path<SongId>,
+) -> Result<Json<JobStatus>, Error> {
+    app_state
+        .songs()
+        .transcode_status(song_id, session.user.uid)
+        .await
+        .map(Json)
+}
+
+async fn comments(
+    State(app_state): State<Arc<AppState>>,
+    Authorization(session): Authorization<Option<Session>>,
+    Path(song_id): Path<SongId>,
+    Query(page): Query<PageQuery>,
+) -> Result<Json<Page<Comment>>, Error> {
+    let viewer = session.map(|x| x.user);
+    app_state
+        .comments()
+        .list(song_id, viewer.as_ref(), &page)
+        .await
+        .map(Json)
+}
+
+/// Request of authoritative lyrics.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct LyricsQuery {
+    /// Requested language of the lyrics.
+    lang: Option<String>,
+}
+
+async fn upload_lyrics(
+    State(app_state): State<Arc<AppState>>,
+    Authorization(session): Authorization<Session>,
+    Path(song_id): Path<SongId>,
+    Json(upload): Json<lyrics::Upload>,
+) -> Result<Json<lyrics::LyricsId>, Error> {
+    let administrator = session.will_administrate().is_ok();
+    app_state
+        .lyrics()
+        .upload(song_id, &session.user, upload, administrator)
+        .await
+        .map(Json)
+}
+
+async fn list_lyrics(
+    State(app_state): State<Arc<AppState>>,
+    Authorization(session): Authorization<Option<Session>>,
+    Path(song_id): Path<SongId>,
+) -> Result<Json<Vec<lyrics::Profile>>, Error> {
+    let subject = session
+        .map(|x| Subject::from_user(&x.user))
+        .unwrap_or_else(Subject::anon);
+    app_state.lyrics().list(song_id, &subject).await.map(Json)
+}
+
+async fn authoritative_lyrics(
+    State(app_state): State<Arc<AppState>>,
+    Authorization(session): Authorization<Option<Session>>,
+    Path(song_id): Path<SongId>,
+    Query(query): Query<LyricsQuery>,
+) -> Result<Json<lyrics::Version>, Error> {
+    let subject = session
+        .map(|x| Subject::from_user(&x.user))
+        .unwrap_or_else(Subject::anon);
+    app_state
+        .lyrics()
+        .authoritative(song_id, query.lang.as_deref(), &subject)
+        .await
+        .map(Json)
+}
+
+async fn recent(
+    State(app_state): State<Arc<AppState>>,
+    Authorization(session): Authorization<Option<Session>>,
+    Query(page): Query<PageQuery>,
+) -> Result<Json<Page<Profile>>, Error> {
+    let subject = session
+        .map(|x| Subject::from_user(&x.user))
+        .unwrap_or_else(Subject::anon);
+    app_state
+        .songs()
+        .list_recent(&subject, &page)
+        .await
+        .map(Json)
+}
+
+async fn search(
+    State(app_state): State<Arc<AppState>>,
+    Authorization(session): Authorization<Option<Session>>,
+    Query(search): Query<super::SearchQuery>,
+    Query(page): Query<PageQuery>,
+) -> Result<Json<Page<Profile>>, Error> {
+    let subject = session
+        .map(|x| Subject::from_user(&x.user))
+        .unwrap_or_else(Subject::anon);
+    app_state
+        .songs()
+        .search(&search.q, &subject, &page)
+        .await
+        .map(Json)
 }
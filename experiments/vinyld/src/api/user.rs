@@ -1,29 +1,141 @@
+use super::rate_limit;
 use crate::{
-    AppState,
+    AppState, album,
     error::Error,
+    playlist,
+    policy::Subject,
     user::{
         Profile, Uid,
-        login::{LoginResponse, UserProber},
+        account::{ProfileEdit, ProfileImage},
+        auth::{
+            email::EmailStatus,
+            phone::PhoneStatus,
+            totp::{TotpEnrollment, TwoFactorStatus},
+            webauthn::{
+                AssertionCredential, CreationOptions, Credential, RegistrationCredential,
+                RequestOptions,
+            },
+        },
+        extract::Authorization,
+        login::{LoginResponse, LoginStep, UserProber},
         register::RegisterRequest,
-        session::{AccessToken, RefreshToken},
+        session::{
+            AccessToken, MintRequest, MintedToken, RefreshToken, Session, SessionInfo,
+            SessionNumeral,
+        },
     },
+    util::page::{Page, PageQuery},
 };
 use axum::{
     Json, Router,
-    extract::{Path, Query, State},
-    response::Redirect,
-    routing::{get, post},
+    body::Bytes,
+    extract::{DefaultBodyLimit, Path, Query, State},
+    http::HeaderMap,
+    middleware::from_fn_with_state,
+    response::Response,
+    routing::{delete, get, post, put},
 };
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
 
-pub fn router() -> Router<Arc<AppState>> {
+pub fn router(state: Arc<AppState>) -> Router<Arc<AppState>> {
+    let login = || from_fn_with_state(state.clone(), rate_limit::login);
+    let register_limit = || from_fn_with_state(state.clone(), rate_limit::register);
+    let refresh = || from_fn_with_state(state.clone(), rate_limit::refresh_token);
+    let send_code = || from_fn_with_state(state.clone(), rate_limit::send_code);
+
     Router::new()
-        .route("/login_via_passwd", post(login_via_passwd))
-        .route("/refresh_token", post(refresh_token))
-        .route("/register", post(register))
+        .route("/login_via_passwd", post(login_via_passwd).layer(login()))
+        .route(
+            "/login_via_phone_code",
+            post(login_via_phone_code).layer(login()),
+        )
+        .route("/login/second_factor", post(complete_login).layer(login()))
+        .route(
+            "/login/second_factor/webauthn/options",
+            post(webauthn_second_factor_options).layer(login()),
+        )
+        .route(
+            "/login/second_factor/webauthn",
+            post(complete_login_via_webauthn).layer(login()),
+        )
+        .route(
+            "/login_via_webauthn/options",
+            post(webauthn_login_options).layer(login()),
+        )
+        .route(
+            "/login_via_webauthn",
+            post(login_via_webauthn).layer(login()),
+        )
+        .route("/refresh_token", post(refresh_token).layer(refresh()))
+        .route("/register", post(register).layer(register_limit()))
+        .route("/logout", post(logout))
+        .route("/sessions.json", get(sessions))
+        .route("/sessions/{numeral}", delete(revoke_session))
+        .route("/sessions/revoke_others", post(revoke_other_sessions))
+        .route("/tokens/mint", post(mint_token))
+        .route("/email.json", get(email))
+        .route("/email", put(set_email))
+        .route("/email/verify", post(verify_email))
+        .route(
+            "/email/register_code",
+            post(send_register_code).layer(send_code()),
+        )
+        .route(
+            "/password/reset_code",
+            post(send_reset_code).layer(send_code()),
+        )
+        .route("/password/reset", post(reset_password).layer(send_code()))
+        .route("/phone.json", get(phone))
+        .route("/phone", put(set_phone))
+        .route("/phone/verify", post(verify_phone))
+        .route(
+            "/phone/register_code",
+            post(send_phone_register_code).layer(send_code()),
+        )
+        .route(
+            "/phone/login_code",
+            post(send_login_code).layer(send_code()),
+        )
+        .route("/two_factor.json", get(two_factor))
+        .route("/two_factor/totp/enroll", post(enroll_totp))
+        .route("/two_factor/totp/confirm", post(confirm_totp))
+        .route("/two_factor/totp/disable", post(disable_totp))
+        .route(
+            "/two_factor/recovery_codes/regenerate",
+            post(regenerate_recovery_codes),
+        )
+        .route(
+            "/webauthn/register/options",
+            post(webauthn_registration_options),
+        )
+        .route("/webauthn/register", post(register_webauthn_credential))
+        .route("/webauthn/credentials.json", get(webauthn_credentials))
+        .route(
+            "/webauthn/credentials/{id}",
+            delete(remove_webauthn_credential),
+        )
+        .route("/profile", put(update_profile))
+        .route("/username", put(claim_username))
+        .route(
+            "/avatar",
+            put(set_avatar)
+                .delete(remove_avatar)
+                .layer(DefaultBodyLimit::max(512 * 1024)),
+        )
+        .route(
+            "/banner",
+            put(set_banner)
+                .delete(remove_banner)
+                .layer(DefaultBodyLimit::max(1024 * 1024)),
+        )
+        .route("/password", put(change_password))
+        .route("/delete_account", post(delete_account))
         .route("/{uid}/profile/profile.json", get(profile))
         .route("/{uid}/avatar/hq.avif", get(avatar_hq))
+        .route("/{uid}/banner/hq.avif", get(banner_hq))
+        .route("/{uid}/albums.json", get(albums))
+        .route("/{uid}/playlists.json", get(playlists))
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -38,7 +150,7 @@ struct LoginViaPasswdRequest {
 async fn login_via_passwd(
     State(app_state): State<Arc<AppState>>,
     Query(args): Query<LoginViaPasswdRequest>,
-) -> Result<Json<LoginResponse>, Error> {
+) -> Result<Json<LoginStep>, Error> {
     app_state
         .users()
         .login_via_password(&args.prober, &args.password, args.source)
@@ -46,6 +158,102 @@ async fn login_via_passwd(
         .map(Json)
 }
 
+#[derive(Serialize, Deserialize)]
+struct LoginViaPhoneCodeRequest {
+    phone: String,
+    code: String,
+
+    source: String,
+}
+
+async fn login_via_phone_code(
+    State(state): State<Arc<AppState>>,
+    Json(request): Json<LoginViaPhoneCodeRequest>,
+) -> Result<Json<LoginStep>, Error> {
+    state
+        .users()
+        .login_via_phone_code(&request.phone, &request.code, request.source)
+        .await
+        .map(Json)
+}
+
+/// Request of completing a login with a second factor.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct CompleteLoginRequest {
+    challenge: String,
+    code: String,
+}
+
+async fn complete_login(
+    State(state): State<Arc<AppState>>,
+    Json(request): Json<CompleteLoginRequest>,
+) -> Result<Json<LoginResponse>, Error> {
+    state
+        .users()
+        .complete_login_via_code(&request.challenge, &request.code)
+        .await
+        .map(Json)
+}
+
+/// Request of starting to complete a login challenge with WebAuthn.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct LoginChallengeRequest {
+    challenge: String,
+}
+
+async fn webauthn_second_factor_options(
+    State(state): State<Arc<AppState>>,
+    Json(request): Json<LoginChallengeRequest>,
+) -> Result<Json<RequestOptions>, Error> {
+    state
+        .users()
+        .begin_webauthn_second_factor(&request.challenge)
+        .await
+        .map(Json)
+}
+
+/// Request of completing a login challenge with WebAuthn.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct CompleteLoginViaWebauthnRequest {
+    challenge: String,
+    credential: AssertionCredential,
+}
+
+async fn complete_login_via_webauthn(
+    State(state): State<Arc<AppState>>,
+    Json(request): Json<CompleteLoginViaWebauthnRequest>,
+) -> Result<Json<LoginResponse>, Error> {
+    state
+        .users()
+        .complete_login_via_webauthn(&request.challenge, &request.credential)
+        .await
+        .map(Json)
+}
+
+async fn webauthn_login_options(
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<RequestOptions>, Error> {
+    state.users().begin_webauthn_login().await.map(Json)
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct LoginViaWebauthnRequest {
+    credential: AssertionCredential,
+
+    source: String,
+}
+
+async fn login_via_webauthn(
+    State(state): State<Arc<AppState>>,
+    Json(request): Json<LoginViaWebauthnRequest>,
+) -> Result<Json<LoginResponse>, Error> {
+    state
+        .users()
+        .login_via_webauthn(&request.credential, request.source)
+        .await
+        .map(Json)
+}
+
 #[derive(Serialize, Deserialize)]
 struct RefreshTokenRequest {
     refresh_token: RefreshToken,
@@ -72,6 +280,270 @@ async fn refresh_token(
     }))
 }
 
+async fn logout(
+    State(state): State<Arc<AppState>>,
+    Authorization(session): Authorization<Session>,
+) -> Result<(), Error> {
+    state.users().logout(&session).await
+}
+
+async fn sessions(
+    State(state): State<Arc<AppState>>,
+    Authorization(session): Authorization<Session>,
+) -> Result<Json<Vec<SessionInfo>>, Error> {
+    state.users().list_sessions(&session).await.map(Json)
+}
+
+async fn revoke_session(
+    State(state): State<Arc<AppState>>,
+    Authorization(session): Authorization<Session>,
+    Path(numeral): Path<SessionNumeral>,
+) -> Result<(), Error> {
+    state.users().revoke_session(&session, numeral).await
+}
+
+async fn revoke_other_sessions(
+    State(state): State<Arc<AppState>>,
+    Authorization(session): Authorization<Session>,
+) -> Result<(), Error> {
+    state.users().revoke_other_sessions(&session).await
+}
+
+async fn mint_token(
+    State(state): State<Arc<AppState>>,
+    Authorization(session): Authorization<Session>,
+    Json(request): Json<MintRequest>,
+) -> Result<Json<MintedToken>, Error> {
+    state.users().mint_token(&session, request).await.map(Json)
+}
+
+/// Request carrying an email address.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct EmailRequest {
+    email: String,
+}
+
+/// Request carrying a verification code.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct CodeRequest {
+    code: String,
+}
+
+/// Request of resetting a password.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct ResetPasswordRequest {
+    email: String,
+    code: String,
+    password: String,
+}
+
+async fn email(
+    State(state): State<Arc<AppState>>,
+    Authorization(session): Authorization<Session>,
+) -> Result<Json<Option<EmailStatus>>, Error> {
+    state.users().email(session.user.uid).await.map(Json)
+}
+
+async fn set_email(
+    State(state): State<Arc<AppState>>,
+    Authorization(session): Authorization<Session>,
+    Json(request): Json<EmailRequest>,
+) -> Result<(), Error> {
+    session.will_manage_sessions()?;
+    state
+        .users()
+        .set_email(session.user.uid, &request.email)
+        .await
+}
+
+async fn verify_email(
+    State(state): State<Arc<AppState>>,
+    Authorization(session): Authorization<Session>,
+    Json(request): Json<CodeRequest>,
+) -> Result<(), Error> {
+    state
+        .users()
+        .verify_email(session.user.uid, &request.code)
+        .await
+}
+
+async fn send_register_code(
+    State(state): State<Arc<AppState>>,
+    Json(request): Json<EmailRequest>,
+) -> Result<(), Error> {
+    state.users().send_register_code(&request.email).await
+}
+
+async fn send_reset_code(
+    State(state): State<Arc<AppState>>,
+    Json(request): Json<EmailRequest>,
+) -> Result<(), Error> {
+    state.users().send_reset_code(&request.email).await
+}
+
+async fn reset_password(
+    State(state): State<Arc<AppState>>,
+    Json(request): Json<ResetPasswordRequest>,
+) -> Result<(), Error> {
+    state
+        .users()
+        .reset_password(&request.email, &request.code, &request.password)
+        .await
+}
+
+/// Request carrying a phone number.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct PhoneRequest {
+    phone: String,
+}
+
+async fn phone(
+    State(state): State<Arc<AppState>>,
+    Authorization(session): Authorization<Session>,
+) -> Result<Json<Option<PhoneStatus>>, Error> {
+    state.users().phone(session.user.uid).await.map(Json)
+}
+
+async fn set_phone(
+    State(state): State<Arc<AppState>>,
+    Authorization(session): Authorization<Session>,
+    Json(request): Json<PhoneRequest>,
+) -> Result<(), Error> {
+    session.will_manage_sessions()?;
+    state
+        .users()
+        .set_phone(session.user.uid, &request.phone)
+        .await
+}
+
+async fn verify_phone(
+    State(state): State<Arc<AppState>>,
+    Authorization(session): Authorization<Session>,
+    Json(request): Json<CodeRequest>,
+) -> Result<(), Error> {
+    state
+        .users()
+        .verify_phone(session.user.uid, &request.code)
+        .await
+}
+
+async fn send_phone_register_code(
+    State(state): State<Arc<AppState>>,
+    Json(request): Json<PhoneRequest>,
+) -> Result<(), Error> {
+    state.users().send_phone_register_code(&request.phone).await
+}
+
+async fn send_login_code(
+    State(state): State<Arc<AppState>>,
+    Json(request): Json<PhoneRequest>,
+) -> Result<(), Error> {
+    state.users().send_login_code(&request.phone).await
+}
+
+async fn two_factor(
+    State(state): State<Arc<AppState>>,
+    Authorization(session): Authorization<Session>,
+) -> Result<Json<TwoFactorStatus>, Error> {
+    state
+        .users()
+        .two_factor_status(session.user.uid)
+        .await
+        .map(Json)
+}
+
+async fn enroll_totp(
+    State(state): State<Arc<AppState>>,
+    Authorization(session): Authorization<Session>,
+) -> Result<Json<TotpEnrollment>, Error> {
+    state.users().enroll_totp(&session).await.map(Json)
+}
+
+async fn confirm_totp(
+    State(state): State<Arc<AppState>>,
+    Authorization(session): Authorization<Session>,
+    Json(request): Json<CodeRequest>,
+) -> Result<Json<Vec<String>>, Error> {
+    state
+        .users()
+        .confirm_totp(&session, &request.code)
+        .await
+        .map(Json)
+}
+
+async fn disable_totp(
+    State(state): State<Arc<AppState>>,
+    Authorization(session): Authorization<Session>,
+    Json(request): Json<CodeRequest>,
+) -> Result<(), Error> {
+    state.users().disable_totp(&session, &request.code).await
+}
+
+async fn regenerate_recovery_codes(
+    State(state): State<Arc<AppState>>,
+    Authorization(session): Authorization<Session>,
+    Json(request): Json<CodeRequest>,
+) -> Result<Json<Vec<String>>, Error> {
+    state
+        .users()
+        .regenerate_recovery_codes(&session, &request.code)
+        .await
+        .map(Json)
+}
+
+async fn webauthn_registration_options(
+    State(state): State<Arc<AppState>>,
+    Authorization(session): Authorization<Session>,
+) -> Result<Json<CreationOptions>, Error> {
+    state
+        .users()
+        .begin_webauthn_registration(&session)
+        .await
+        .map(Json)
+}
+
+/// Request of registering a WebAuthn credential.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct RegisterWebauthnRequest {
+    /// Name of the credential, shown to the user, like the name of the device holding it.
+    name: String,
+    credential: RegistrationCredential,
+}
+
+async fn register_webauthn_credential(
+    State(state): State<Arc<AppState>>,
+    Authorization(session): Authorization<Session>,
+    Json(request): Json<RegisterWebauthnRequest>,
+) -> Result<Json<Credential>, Error> {
+    state
+        .users()
+        .finish_webauthn_registration(&session, request.name, &request.credential)
+        .await
+        .map(Json)
+}
+
+async fn webauthn_credentials(
+    State(state): State<Arc<AppState>>,
+    Authorization(session): Authorization<Session>,
+) -> Result<Json<Vec<Credential>>, Error> {
+    state
+        .users()
+        .list_webauthn_credentials(&session)
+        .await
+        .map(Json)
+}
+
+async fn remove_webauthn_credential(
+    State(state): State<Arc<AppState>>,
+    Authorization(session): Authorization<Session>,
+    Path(id): Path<String>,
+) -> Result<(), Error> {
+    state
+        .users()
+        .remove_webauthn_credential(&session, &id)
+        .await
+}
+
 async fn register(
     State(app_state): State<Arc<AppState>>,
     Query(request): Query<RegisterRequest>,
@@ -81,18 +553,171 @@ async fn register(
 
 async fn profile(
     State(app_state): State<Arc<AppState>>,
+    Authorization(session): Authorization<Option<Session>>,
     Path(uid): Path<Uid>,
 ) -> Result<Json<Profile>, Error> {
-    app_state
+    let viewer = session.map(|x| x.user.uid);
+    app_state.users().profile(uid, viewer).await.map(Json)
+}
+
+async fn avatar_hq(
+    State(state): State<Arc<AppState>>,
+    Path(uid): Path<Uid>,
+    headers: HeaderMap,
+) -> Result<Response, Error> {
+    super::delivery::deliver(&state, state.users().avatar_hq(uid).await?, &headers).await
+}
+
+async fn banner_hq(
+    State(state): State<Arc<AppState>>,
+    Path(uid): Path<Uid>,
+    headers: HeaderMap,
+) -> Result<Response, Error> {
+    super::delivery::deliver(&state, state.users().banner_hq(uid).await?, &headers).await
+}
+
+async fn update_profile(
+    State(state): State<Arc<AppState>>,
+    Authorization(session): Authorization<Session>,
+    Json(edit): Json<ProfileEdit>,
+) -> Result<(), Error> {
+    state.users().update_profile(&session, edit).await
+}
+
+/// Request carrying a username.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct UsernameRequest {
+    username: String,
+}
+
+async fn claim_username(
+    State(state): State<Arc<AppState>>,
+    Authorization(session): Authorization<Session>,
+    Json(request): Json<UsernameRequest>,
+) -> Result<(), Error> {
+    state
         .users()
-        .find_by_uid(uid)
+        .claim_username(&session, &request.username)
         .await
-        .map(|x| Json(Profile::from(x)))
 }
 
-async fn avatar_hq(
+async fn set_avatar(
+    State(state): State<Arc<AppState>>,
+    Authorization(session): Authorization<Session>,
+    image: Bytes,
+) -> Result<(), Error> {
+    state
+        .users()
+        .set_profile_image(&session, ProfileImage::Avatar, image)
+        .await
+}
+
+async fn remove_avatar(
+    State(state): State<Arc<AppState>>,
+    Authorization(session): Authorization<Session>,
+) -> Result<(), Error> {
+    state
+        .users()
+        .remove_profile_image(&session, ProfileImage::Avatar)
+        .await
+}
+
+async fn set_banner(
+    State(state): State<Arc<AppState>>,
+    Authorization(session): Authorization<Session>,
+    image: Bytes,
+) -> Result<(), Error> {
+    state
+        .users()
+        .set_profile_image(&session, ProfileImage::Banner, image)
+        .await
+}
+
+async fn remove_banner(
     State(state): State<Arc<AppState>>,
+    Authorization(session): Authorization<Session>,
+) -> Result<(), Error> {
+    state
+        .users()
+        .remove_profile_image(&session, ProfileImage::Banner)
+        .await
+}
+
+/// Request of changing the password.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct ChangePasswordRequest {
+    /// The current password, which is required unless the user has none.
+    current_password: Option<String>,
+    new_password: String,
+}
+
+async fn change_password(
+    State(state): State<Arc<AppState>>,
+    Authorization(session): Authorization<Session>,
+    Json(request): Json<ChangePasswordRequest>,
+) -> Result<(), Error> {
+    state
+        .users()
+        .change_password(
+            &session,
+            request.current_password.as_deref(),
+            &request.new_password,
+        )
+        .await
+}
+
+/// Request of deleting the account, confirming the identity of the user.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct DeleteAccountRequest {
+    password: Option<String>,
+
+    /// A TOTP code or a recovery code, which is required if TOTP is enabled.
+    code: Option<String>,
+}
+
+async fn delete_account(
+    State(state): State<Arc<AppState>>,
+    Authorization(session): Authorization<Session>,
+    Json(request): Json<DeleteAccountRequest>,
+) -> Result<(), Error> {
+    state
+        .users()
+        .delete_account(
+            &session,
+            request.password.as_deref(),
+            request.code.as_deref(),
+        )
+        .await
+}
+
+async fn albums(
+    State(state): State<Arc<AppState>>,
+    Authorization(session): Authorization<Option<Session>>,
     Path(uid): Path<Uid>,
-) -> Result<Redirect, Error> {
-    Ok(Redirect::to(&state.users().avatar_hq(uid).await?.0))
+    Query(page): Query<PageQuery>,
+) -> Result<Json<Page<album::Profile>>, Error> {
+    let subject = session
+        .map(|x| Subject::from_user(&x.user))
+        .unwrap_or_else(Subject::anon);
+    state
+        .albums()
+        .list_by_uploader(uid, &subject, &page)
+        .await
+        .map(Json)
+}
+
+async fn playlists(
+    State(state): State<Arc<AppState>>,
+    Authorization(session): Authorization<Option<Session>>,
+    Path(uid): Path<Uid>,
+    Query(page): Query<PageQuery>,
+) -> Result<Json<Page<playlist::Profile>>, Error> {
+    let subject = session
+        .map(|x| Subject::from_user(&x.user))
+        .unwrap_or_else(Subject::anon);
+    state
+        .playlists()
+        .list_by_owner(uid, &subject, &page)
+        .await
+        .map(Json)
 }
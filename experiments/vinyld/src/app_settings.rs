@@ -1,6 +1,6 @@
 //! Application settings.
 
-use crate::{AppState, database::entity::app_settings, error::Error};
+use crate::{AppState, audit::Actor, database::entity::app_settings, error::Error};
 use sea_orm::{ActiveValue::Set, EntityTrait, sea_query::OnConflict};
 use serde::{Serialize, de::DeserializeOwned};
 
@@ -75,22 +75,36 @@ impl AppSettings<'_> {
     }
 
     /// Sets the value of the specified app setting entry.
-    pub async fn set<T: Entry>(&self, value: &T::Ty) -> Result<(), Error> {
+    pub async fn set<T: Entry>(&self, actor: &Actor, value: &T::Ty) -> Result<(), Error> {
         let value = serde_json::to_value(value).map_err(|err| Error::internal(err))?;
+        let before = app_settings::Entity::find_by_id(T::key())
+            .one(&*self.0.database.conn)
+            .await?
+            .map(|x| x.value)
+            .unwrap_or_default();
 
         app_settings::Entity::insert(app_settings::ActiveModel {
             key: Set(T::key().into()),
-            value: Set(value),
+            value: Set(value.clone()),
         })
         .on_conflict(
             OnConflict::column(app_settings::Column::Key)
-                .update_column(app_settings::Column::Key)
+                .update_column(app_settings::Column::Value)
                 .to_owned(),
         )
         .exec(&*self.0.database.conn)
         .await?;
 
-        Ok(())
+        self.0
+            .audit_log()
+            .record(
+                actor,
+                "app_settings.set",
+                format!("app_settings/{}", T::key()),
+                before,
+                value,
+            )
+            .await
     }
 }
 
@@ -99,6 +113,10 @@ entry!(RegisterRequirements: crate::user::register::RegisterRequires);
 entry!(MandatoryAlbumCensorship: bool);
 entry!(MandatorySongCensorship: bool);
 entry!(MandatoryCommentCensorship: bool);
+entry!(MandatoryEmailVerification: bool);
+entry!(MandatoryWheelTwoFactor: bool);
+entry!(MaxSongDuration: u64 = 30 * 60);
+entry!(RateLimits: crate::rate_limit::RateLimits);
 entry!(LicenseHTML: String = include_str!("../resources/DefaultEula.html").into());
 
 impl AppState {
@@ -0,0 +1,150 @@
+//! Audit records of administrative and destructive actions.
+//!
+//! The audit log is append-only. Records are never edited or removed by the server, and they don't reference their
+//! actors or targets by foreign keys, so that they outlive both.
+
+use crate::{
+    AppState,
+    database::entity::audit_log,
+    error::Error,
+    user::{Uid, session::Session},
+    util::page::{Page, PageQuery},
+};
+use chrono::{DateTime, Utc};
+use sea_orm::{
+    ActiveValue::NotSet, ColumnTrait, EntityTrait, QueryFilter, QueryOrder, QuerySelect, Set,
+};
+use serde::{Deserialize, Serialize};
+
+/// Manager of the audit log.
+pub struct AuditLog<'a>(&'a AppState);
+impl AuditLog<'_> {
+    /// Records that the actor performed the action on the target.
+    ///
+    /// `target` names the affected object, like `user/42`. `before` and `after` are the parts of the target that the
+    /// action changed, and are `null` if the part didn't exist on the respective side.
+    pub async fn record(
+        &self,
+        actor: &Actor,
+        action: &str,
+        target: String,
+        before: serde_json::Value,
+        after: serde_json::Value,
+    ) -> Result<(), Error> {
+        audit_log::Entity::insert(audit_log::ActiveModel {
+            audit_id: NotSet,
+            actor: Set(actor.uid.0),
+            action: Set(action.into()),
+            target: Set(target),
+            detail: Set(Some(serde_json::json! {{"before": before, "after": after}})),
+            created_at: Set(Utc::now().naive_utc()),
+            source: Set(actor.source.clone()),
+        })
+        .exec(&*self.0.database.conn)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Queries the audit log, from the newest.
+    pub async fn query(&self, filter: &Filter, query: &PageQuery) -> Result<Page<Record>, Error> {
+        let limit = query.limit();
+        let mut select = audit_log::Entity::find()
+            .order_by_desc(audit_log::Column::AuditId)
+            .limit(limit + 1);
+        if let Some(position) = query.position()? {
+            select = select.filter(audit_log::Column::AuditId.lt(position));
+        }
+        if let Some(actor) = filter.actor {
+            select = select.filter(audit_log::Column::Actor.eq(actor.0));
+        }
+        if let Some(target) = &filter.target {
+            select = select.filter(audit_log::Column::Target.eq(target));
+        }
+        if let Some(since) = filter.since {
+            select = select.filter(audit_log::Column::CreatedAt.gte(since.naive_utc()));
+        }
+        if let Some(until) = filter.until {
+            select = select.filter(audit_log::Column::CreatedAt.lt(until.naive_utc()));
+        }
+
+        let mut models = select.all(&*self.0.database.conn).await?;
+        let next_position = if models.len() as u64 > limit {
+            models.truncate(limit as _);
+            models.last().map(|x| x.audit_id)
+        } else {
+            None
+        };
+
+        Ok(Page::new(
+            models.into_iter().map(Record::from).collect(),
+            next_position,
+        ))
+    }
+}
+
+/// Who performed an action.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Actor {
+    pub uid: Uid,
+
+    /// Source of the session that the action was performed in.
+    pub source: String,
+}
+impl From<&Session> for Actor {
+    fn from(session: &Session) -> Self {
+        Self {
+            uid: session.user.uid,
+            source: session.source.clone(),
+        }
+    }
+}
+
+/// Filter of querying the audit log. Unset fields match every record.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Filter {
+    pub actor: Option<Uid>,
+    pub target: Option<String>,
+
+    /// Inclusive beginning of the time range.
+    pub since: Option<DateTime<Utc>>,
+
+    /// Exclusive end of the time range.
+    pub until: Option<DateTime<Utc>>,
+}
+
+/// A record in the audit log.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Record {
+    pub id: i64,
+    pub actor: Actor,
+    pub action: String,
+    pub target: String,
+    pub before: serde_json::Value,
+    pub after: serde_json::Value,
+    pub created_at: DateTime<Utc>,
+}
+impl From<audit_log::Model> for Record {
+    fn from(model: audit_log::Model) -> Self {
+        let mut detail = model.detail.unwrap_or_default();
+        Self {
+            id: model.audit_id,
+            actor: Actor {
+                uid: Uid(model.actor),
+                source: model.source,
+            },
+            action: model.action,
+            target: model.target,
+            before: detail["before"].take(),
+            after: detail["after"].take(),
+            created_at: model.created_at.and_utc(),
+        }
+    }
+}
+
+impl AppState {
+    /// Returns a manager of the audit log.
+    pub fn audit_log(&self) -> AuditLog<'_> {
+        AuditLog(self)
+    }
+}
@@ -0,0 +1,446 @@
+use crate::{
+    AppState,
+    app_settings::MandatoryCommentCensorship,
+    audit::Actor,
+    database::entity::song_comment,
+    error::{Error, ErrorCode},
+    moderation::ModerationStatus,
+    policy::Subject,
+    song::SongId,
+    user::{Uid, User},
+    util::page::{Page, PageQuery},
+};
+use axum::body::Bytes;
+use chrono::{DateTime, Utc};
+use sea_orm::{
+    ActiveModelTrait, ActiveValue::NotSet, ColumnTrait, Condition, EntityTrait, IntoActiveModel,
+    QueryFilter, QueryOrder, QuerySelect, Select, Set,
+};
+use serde::{Deserialize, Serialize};
+use std::io::Cursor;
+use vinioss::ObjectKey;
+
+/// Manager of song comments.
+pub struct Comments<'a>(&'a AppState);
+impl Comments<'_> {
+    /// Maximum resolution of attached images.
+    const ATTACHMENT_RESOLUTION: (u32, u32) = (1280, 1280);
+
+    /// Maximum number of attachments in a comment.
+    pub const MAX_ATTACHMENTS: usize = 4;
+
+    /// Maximum length of comment text, in characters.
+    const MAX_TEXT_LEN: usize = 2000;
+
+    /// Posts a comment on a song, or a reply to another comment.
+    ///
+    /// If comment censorship is mandatory, the comment is held for review unless the author is an administrator.
+    pub async fn post(
+        &self,
+        author: &User,
+        post: Post,
+        attachments: Vec<Bytes>,
+    ) -> Result<CommentId, Error> {
+        check_text(&post.text)?;
+        if attachments.len() > Self::MAX_ATTACHMENTS {
+            return Err(Error::bad_request(format!(
+                "a comment can have at most {} attachments",
+                Self::MAX_ATTACHMENTS
+            )));
+        }
+
+        self.0
+            .songs()
+            .listenable(post.song, &Subject::from_user(author))
+            .await
+            .map_err(|err| match err.code {
+                ErrorCode::NOT_FOUND => Error::non_existent_song(),
+                _ => err,
+            })?;
+        if let Some(parent) = post.parent {
+            let parent = self.find(parent).await?;
+            if parent.song != post.song.0 || !parent.visible_to(Some(author))? {
+                return Err(Error::not_found());
+            }
+        }
+
+        let images = attachments
+            .into_iter()
+            .map(|x| crate::util::image::recompress(x, Self::ATTACHMENT_RESOLUTION))
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut keys = Vec::with_capacity(images.len());
+        for image in images {
+            let object_key = ObjectKey(vinutie::random::filename("comment_attachment", "avif"));
+            let uploaded = self
+                .0
+                .objects
+                .put_stream(object_key.clone(), &mut Cursor::new(image))
+                .await;
+            if let Err(err) = uploaded {
+                self.remove_attachments(&keys).await;
+                return Err(err.into());
+            }
+            keys.push(object_key.0);
+        }
+
+        let mandatory = self
+            .0
+            .app_settings()
+            .get::<MandatoryCommentCensorship>()
+            .await;
+        let status = ModerationStatus::initial_for(author, mandatory);
+        let inserted = song_comment::Entity::insert(song_comment::ActiveModel {
+            song_comment_id: NotSet,
+            song: Set(post.song.0),
+            parent: Set(post.parent.map(|x| x.0)),
+            created_by: Set(author.uid.0),
+            text: Set(post.text),
+            attachments: Set((!keys.is_empty()).then_some(keys.clone())),
+            created_at: Set(Utc::now().naive_utc()),
+            status: Set(status.as_str().into()),
+            edited_at: Set(None),
+            deleted: Set(false),
+        })
+        .exec(&*self.0.database.conn)
+        .await;
+
+        match inserted {
+            Ok(x) => Ok(CommentId(x.last_insert_id)),
+            Err(err) => {
+                self.remove_attachments(&keys).await;
+                Err(err.into())
+            }
+        }
+    }
+
+    /// Removes uploaded attachments of a comment that failed to be posted.
+    async fn remove_attachments(&self, keys: &[String]) {
+        for key in keys {
+            if let Err(err) = self.0.objects.remove(ObjectKey(key.clone())).await {
+                tracing::warn!("failed to remove comment attachment `{key}`: {err}");
+            }
+        }
+    }
+
+    /// Edits text of a comment. Only the author can do this.
+    ///
+    /// If comment censorship is mandatory, the edited comment is held for review again.
+    pub async fn edit(
+        &self,
+        comment_id: CommentId,
+        author: &User,
+        text: String,
+    ) -> Result<(), Error> {
+        check_text(&text)?;
+
+        let model = self.find(comment_id).await?;
+        if model.deleted {
+            return Err(Error::not_found());
+        }
+        if model.created_by != author.uid.0 {
+            return Err(Error::restricted_user());
+        }
+
+        let mandatory = self
+            .0
+            .app_settings()
+            .get::<MandatoryCommentCensorship>()
+            .await;
+        let status = ModerationStatus::initial_for(author, mandatory);
+        let mut active_model = model.into_active_model();
+        active_model.text = Set(text);
+        active_model.edited_at = Set(Some(Utc::now().naive_utc()));
+        active_model.status = Set(status.as_str().into());
+        active_model.update(&*self.0.database.conn).await?;
+
+        Ok(())
+    }
+
+    /// Deletes a comment. Only the author or an administrator can do this.
+    ///
+    /// The comment is kept as a placeholder without text and attachments, so its replies stay in the thread.
+    pub async fn remove(
+        &self,
+        comment_id: CommentId,
+        user: &User,
+        administrator: bool,
+    ) -> Result<(), Error> {
+        let model = self.find(comment_id).await?;
+        if model.deleted {
+            return Err(Error::not_found());
+        }
+        if model.created_by != user.uid.0 && !administrator {
+            return Err(Error::restricted_user());
+        }
+
+        for key in model.attachments.iter().flatten() {
+            self.0.objects.remove(ObjectKey(key.clone())).await?;
+        }
+
+        let mut active_model = model.into_active_model();
+        active_model.text = Set(String::new());
+        active_model.attachments = Set(None);
+        active_model.deleted = Set(true);
+        active_model.update(&*self.0.database.conn).await?;
+
+        Ok(())
+    }
+
+    /// Lists top-level comments on a song, in the order of posting.
+    ///
+    /// Comments that are not approved are only listed to their authors.
+    pub async fn list(
+        &self,
+        song_id: SongId,
+        viewer: Option<&User>,
+        query: &PageQuery,
+    ) -> Result<Page<Comment>, Error> {
+        self.thread(song_id, None, viewer, query).await
+    }
+
+    /// Lists replies to a comment, in the order of posting.
+    ///
+    /// Replies that are not approved are only listed to their authors.
+    pub async fn replies(
+        &self,
+        comment_id: CommentId,
+        viewer: Option<&User>,
+        query: &PageQuery,
+    ) -> Result<Page<Comment>, Error> {
+        let model = self.find(comment_id).await?;
+        if !model.visible_to(viewer)? {
+            return Err(Error::not_found());
+        }
+
+        self.thread(SongId(model.song), Some(comment_id), viewer, query)
+            .await
+    }
+
+    /// Lists comments on a song under the parent, or top-level comments if `parent` is `None`.
+    async fn thread(
+        &self,
+        song_id: SongId,
+        parent: Option<CommentId>,
+        viewer: Option<&User>,
+        query: &PageQuery,
+    ) -> Result<Page<Comment>, Error> {
+        let subject = viewer.map(Subject::from_user).unwrap_or_else(Subject::anon);
+        self.0.songs().listenable(song_id, &subject).await?;
+
+        let mut visible = Condition::any()
+            .add(song_comment::Column::Status.eq(ModerationStatus::Approved.as_str()));
+        if let Some(viewer) = viewer {
+            visible = visible.add(song_comment::Column::CreatedBy.eq(viewer.uid.0));
+        }
+        let select = song_comment::Entity::find()
+            .filter(song_comment::Column::Song.eq(song_id.0))
+            .filter(match parent {
+                Some(parent) => song_comment::Column::Parent.eq(parent.0),
+                None => song_comment::Column::Parent.is_null(),
+            })
+            .filter(visible);
+
+        self.page(select, query).await
+    }
+
+    /// Returns an attachment of the comment.
+    pub async fn attachment(
+        &self,
+        comment_id: CommentId,
+        index: usize,
+        viewer: Option<&User>,
+    ) -> Result<ObjectKey, Error> {
+        let model = self.find(comment_id).await?;
+        if !model.visible_to(viewer)? {
+            return Err(Error::not_found());
+        }
+        let subject = viewer.map(Subject::from_user).unwrap_or_else(Subject::anon);
+        self.0
+            .songs()
+            .listenable(SongId(model.song), &subject)
+            .await?;
+
+        model
+            .attachments
+            .and_then(|mut x| (index < x.len()).then(|| x.swap_remove(index)))
+            .map(ObjectKey)
+            .ok_or_else(Error::not_found)
+    }
+
+    /// Lists comments waiting for review, from the oldest.
+    pub async fn list_pending(&self, query: &PageQuery) -> Result<Page<Comment>, Error> {
+        let select = song_comment::Entity::find()
+            .filter(song_comment::Column::Status.eq(ModerationStatus::Pending.as_str()))
+            .filter(song_comment::Column::Deleted.eq(false));
+
+        self.page(select, query).await
+    }
+
+    /// Sets the moderation status of a comment.
+    pub async fn review(
+        &self,
+        actor: &Actor,
+        comment_id: CommentId,
+        status: ModerationStatus,
+    ) -> Result<(), Error> {
+        let model = self.find(comment_id).await?;
+        let before = model.status.clone();
+        let mut active_model = model.into_active_model();
+        active_model.status = Set(status.as_str().into());
+        active_model.update(&*self.0.database.conn).await?;
+
+        self.0
+            .audit_log()
+            .record(
+                actor,
+                "comment.review",
+                format!("comment/{}", comment_id.0),
+                serde_json::json!(before),
+                serde_json::json!(status),
+            )
+            .await
+    }
+
+    /// Returns a page of comments from the select, in the ascending order of IDs.
+    async fn page(
+        &self,
+        select: Select<song_comment::Entity>,
+        query: &PageQuery,
+    ) -> Result<Page<Comment>, Error> {
+        let limit = query.limit();
+        let mut select = select
+            .order_by_asc(song_comment::Column::SongCommentId)
+            .limit(limit + 1);
+        if let Some(position) = query.position()? {
+            select = select.filter(song_comment::Column::SongCommentId.gt(position));
+        }
+
+        let mut models = select.all(&*self.0.database.conn).await?;
+        let next_position = if models.len() as u64 > limit {
+            models.truncate(limit as _);
+            models.last().map(|x| x.song_comment_id)
+        } else {
+            None
+        };
+
+        Ok(Page::new(
+            models
+                .into_iter()
+                .map(Comment::try_from)
+                .collect::<Result<_, _>>()?,
+            next_position,
+        ))
+    }
+
+    /// Returns the model of the comment.
+    async fn find(&self, comment_id: CommentId) -> Result<song_comment::Model, Error> {
+        song_comment::Entity::find_by_id(comment_id.0)
+            .one(&*self.0.database.conn)
+            .await?
+            .ok_or_else(Error::not_found)
+    }
+}
+
+impl song_comment::Model {
+    /// Returns `true` if the comment is approved or the viewer is its author.
+    fn visible_to(&self, viewer: Option<&User>) -> Result<bool, Error> {
+        Ok(
+            self.status.parse::<ModerationStatus>()? == ModerationStatus::Approved
+                || viewer.is_some_and(|x| x.uid.0 == self.created_by),
+        )
+    }
+}
+
+/// Succeeds if the text can be used as comment text.
+fn check_text(text: &str) -> Result<(), Error> {
+    if text.trim().is_empty() {
+        Err(Error::bad_request("comment text must not be empty"))
+    } else if text.chars().count() > Comments::MAX_TEXT_LEN {
+        Err(Error::bad_request(format!(
+            "comment text must not be longer than {} characters",
+            Comments::MAX_TEXT_LEN
+        )))
+    } else {
+        Ok(())
+    }
+}
+
+/// Request of posting a comment.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Post {
+    /// The song to comment on.
+    pub song: SongId,
+
+    /// The comment to reply to, if this is a reply.
+    pub parent: Option<CommentId>,
+
+    /// Text of the comment.
+    pub text: String,
+}
+
+/// Representation of a Comment ID.
+///
+/// A Comment ID identifies a comment uniquely and cannot be changed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(transparent)]
+#[repr(transparent)]
+pub struct CommentId(pub i64);
+
+/// A comment on a song.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Comment {
+    /// ID of the comment.
+    pub comment_id: CommentId,
+
+    /// The song that the comment is on.
+    pub song: SongId,
+
+    /// The comment that this comment replies to.
+    pub parent: Option<CommentId>,
+
+    /// Author of the comment.
+    pub created_by: Uid,
+
+    /// Text of the comment. This is empty if the comment was deleted.
+    pub text: String,
+
+    /// Number of attached images.
+    pub attachments: usize,
+
+    /// Moderation status of the comment.
+    pub status: ModerationStatus,
+
+    /// Whether the comment was deleted.
+    pub deleted: bool,
+
+    /// Time when the comment was posted.
+    pub created_at: DateTime<Utc>,
+
+    /// Time when the comment was last edited.
+    pub edited_at: Option<DateTime<Utc>>,
+}
+impl TryFrom<song_comment::Model> for Comment {
+    type Error = Error;
+
+    fn try_from(model: song_comment::Model) -> Result<Self, Error> {
+        Ok(Self {
+            comment_id: CommentId(model.song_comment_id),
+            song: SongId(model.song),
+            parent: model.parent.map(CommentId),
+            created_by: Uid(model.created_by),
+            text: model.text,
+            attachments: model.attachments.map_or(0, |x| x.len()),
+            status: model.status.parse()?,
+            deleted: model.deleted,
+            created_at: model.created_at.and_utc(),
+            edited_at: model.edited_at.map(|x| x.and_utc()),
+        })
+    }
+}
+
+impl AppState {
+    /// Returns a manager of song comments.
+    pub fn comments(&self) -> Comments<'_> {
+        Comments(self)
+    }
+}
@@ -0,0 +1,20 @@
+use crate::database::entity::rotated_refresh_token;
+use sea_orm::{ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter};
+use std::{sync::Arc, time::Duration};
+use tokio::time::{MissedTickBehavior, interval};
+
+pub fn spawn(conn: Arc<DatabaseConnection>) {
+    tokio::spawn(async move {
+        let mut interval = interval(Duration::from_secs(24 * 60 * 60));
+        interval.set_missed_tick_behavior(MissedTickBehavior::Skip);
+        loop {
+            interval.tick().await;
+
+            tracing::info!("Running database cron task `expire_rotated_tokens`...");
+            _ = rotated_refresh_token::Entity::delete_many()
+                .filter(rotated_refresh_token::Column::ExpiresAt.lt(chrono::Utc::now().naive_utc()))
+                .exec(&*conn)
+                .await;
+        }
+    });
+}
@@ -1,3 +1,4 @@
+mod expire_rotated_tokens;
 mod expire_sessions;
 
 use sea_orm::DatabaseConnection;
@@ -5,4 +6,5 @@ use std::sync::Arc;
 
 pub fn spawn(conn: Arc<DatabaseConnection>) {
     expire_sessions::spawn(conn.clone());
+    expire_rotated_tokens::spawn(conn.clone());
 }
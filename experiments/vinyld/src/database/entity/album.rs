@@ -15,6 +15,9 @@ pub struct Model {
     pub write_policy: Json,
     pub cover: Option<String>,
     pub created_at: DateTime,
+    pub status: String,
+    #[sea_orm(column_type = "Text", nullable)]
+    pub rejection_reason: Option<String>,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
@@ -16,6 +16,8 @@ pub struct Model {
     pub language: String,
     pub is_authoritative: bool,
     pub created_at: DateTime,
+    pub content: Option<Json>,
+    pub format: String,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
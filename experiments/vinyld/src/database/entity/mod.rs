@@ -4,9 +4,26 @@ pub mod prelude;
 
 pub mod album;
 pub mod app_settings;
+pub mod audit_log;
+pub mod email_code;
+pub mod login_challenge;
 pub mod lyrics;
+pub mod oauth_client;
+pub mod oauth_code;
+pub mod phone_code;
+pub mod playlist;
+pub mod playlist_entry;
+pub mod recovery_code;
+pub mod rotated_refresh_token;
 pub mod session;
 pub mod song;
 pub mod song_comment;
+pub mod song_rendition;
+pub mod transcode_job;
 pub mod user;
+pub mod user_auth_email;
 pub mod user_auth_password;
+pub mod user_auth_phone;
+pub mod user_auth_totp;
+pub mod user_auth_webauthn;
+pub mod webauthn_challenge;
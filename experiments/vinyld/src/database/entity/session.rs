@@ -9,16 +9,26 @@ pub struct Model {
     pub numeral: i64,
     pub uid: i64,
     #[sea_orm(unique)]
-    pub refresh_token: String,
+    pub refresh_token_hash: String,
     #[sea_orm(unique)]
-    pub access_token: String,
+    pub access_token_hash: String,
     pub refresh_expiry: DateTime,
     pub access_expiry: DateTime,
     pub permissions: i32,
     pub source: String,
+    pub created_at: Option<DateTime>,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
-pub enum Relation {}
+pub enum Relation {
+    #[sea_orm(has_many = "super::rotated_refresh_token::Entity")]
+    RotatedRefreshToken,
+}
+
+impl Related<super::rotated_refresh_token::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::RotatedRefreshToken.def()
+    }
+}
 
 impl ActiveModelBehavior for ActiveModel {}
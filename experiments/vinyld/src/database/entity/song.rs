@@ -14,6 +14,15 @@ pub struct Model {
     pub origin_audio: String,
     pub listen_policy: Option<Json>,
     pub created_at: DateTime,
+    pub duration_ms: Option<i64>,
+    pub sample_rate: Option<i32>,
+    pub channels: Option<i32>,
+    pub source_codec: Option<String>,
+    pub source_bitrate: Option<i32>,
+    pub language: Option<String>,
+    pub status: String,
+    #[sea_orm(column_type = "Text", nullable)]
+    pub rejection_reason: Option<String>,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
@@ -28,8 +37,14 @@ pub enum Relation {
     Album,
     #[sea_orm(has_many = "super::lyrics::Entity")]
     Lyrics,
+    #[sea_orm(has_many = "super::playlist_entry::Entity")]
+    PlaylistEntry,
     #[sea_orm(has_many = "super::song_comment::Entity")]
     SongComment,
+    #[sea_orm(has_many = "super::song_rendition::Entity")]
+    SongRendition,
+    #[sea_orm(has_many = "super::transcode_job::Entity")]
+    TranscodeJob,
 }
 
 impl Related<super::album::Entity> for Entity {
@@ -44,10 +59,28 @@ impl Related<super::lyrics::Entity> for Entity {
     }
 }
 
+impl Related<super::playlist_entry::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::PlaylistEntry.def()
+    }
+}
+
 impl Related<super::song_comment::Entity> for Entity {
     fn to() -> RelationDef {
         Relation::SongComment.def()
     }
 }
 
+impl Related<super::song_rendition::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::SongRendition.def()
+    }
+}
+
+impl Related<super::transcode_job::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::TranscodeJob.def()
+    }
+}
+
 impl ActiveModelBehavior for ActiveModel {}
@@ -14,6 +14,9 @@ pub struct Model {
     pub text: String,
     pub attachments: Option<Vec<String>>,
     pub created_at: DateTime,
+    pub status: String,
+    pub edited_at: Option<DateTime>,
+    pub deleted: bool,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
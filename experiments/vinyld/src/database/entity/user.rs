@@ -23,16 +23,53 @@ pub struct Model {
     pub groups: Vec<String>,
     pub created_at: DateTime,
     pub last_logined_at: DateTime,
+    pub username_generated: bool,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
 pub enum Relation {
+    #[sea_orm(has_many = "super::email_code::Entity")]
+    EmailCode,
+    #[sea_orm(has_many = "super::login_challenge::Entity")]
+    LoginChallenge,
     #[sea_orm(has_many = "super::lyrics::Entity")]
     Lyrics,
+    #[sea_orm(has_many = "super::oauth_client::Entity")]
+    OauthClient,
+    #[sea_orm(has_many = "super::oauth_code::Entity")]
+    OauthCode,
+    #[sea_orm(has_many = "super::phone_code::Entity")]
+    PhoneCode,
+    #[sea_orm(has_many = "super::playlist::Entity")]
+    Playlist,
+    #[sea_orm(has_many = "super::recovery_code::Entity")]
+    RecoveryCode,
     #[sea_orm(has_many = "super::song_comment::Entity")]
     SongComment,
+    #[sea_orm(has_one = "super::user_auth_email::Entity")]
+    UserAuthEmail,
     #[sea_orm(has_one = "super::user_auth_password::Entity")]
     UserAuthPassword,
+    #[sea_orm(has_one = "super::user_auth_phone::Entity")]
+    UserAuthPhone,
+    #[sea_orm(has_one = "super::user_auth_totp::Entity")]
+    UserAuthTotp,
+    #[sea_orm(has_many = "super::user_auth_webauthn::Entity")]
+    UserAuthWebauthn,
+    #[sea_orm(has_many = "super::webauthn_challenge::Entity")]
+    WebauthnChallenge,
+}
+
+impl Related<super::email_code::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::EmailCode.def()
+    }
+}
+
+impl Related<super::login_challenge::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::LoginChallenge.def()
+    }
 }
 
 impl Related<super::lyrics::Entity> for Entity {
@@ -41,16 +78,76 @@ impl Related<super::lyrics::Entity> for Entity {
     }
 }
 
+impl Related<super::oauth_client::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::OauthClient.def()
+    }
+}
+
+impl Related<super::oauth_code::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::OauthCode.def()
+    }
+}
+
+impl Related<super::phone_code::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::PhoneCode.def()
+    }
+}
+
+impl Related<super::playlist::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Playlist.def()
+    }
+}
+
+impl Related<super::recovery_code::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::RecoveryCode.def()
+    }
+}
+
 impl Related<super::song_comment::Entity> for Entity {
     fn to() -> RelationDef {
         Relation::SongComment.def()
     }
 }
 
+impl Related<super::user_auth_email::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::UserAuthEmail.def()
+    }
+}
+
 impl Related<super::user_auth_password::Entity> for Entity {
     fn to() -> RelationDef {
         Relation::UserAuthPassword.def()
     }
 }
 
+impl Related<super::user_auth_phone::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::UserAuthPhone.def()
+    }
+}
+
+impl Related<super::user_auth_totp::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::UserAuthTotp.def()
+    }
+}
+
+impl Related<super::user_auth_webauthn::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::UserAuthWebauthn.def()
+    }
+}
+
+impl Related<super::webauthn_challenge::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::WebauthnChallenge.def()
+    }
+}
+
 impl ActiveModelBehavior for ActiveModel {}
@@ -0,0 +1,9 @@
+mod transcode;
+
+use crate::AppState;
+use std::sync::Arc;
+
+/// Spawns the job workers.
+pub fn spawn(state: Arc<AppState>, transcode_workers: usize) {
+    transcode::spawn(state, transcode_workers);
+}
@@ -0,0 +1,28 @@
+use crate::AppState;
+use std::{sync::Arc, time::Duration};
+use tokio::time::{MissedTickBehavior, interval};
+
+pub fn spawn(state: Arc<AppState>, workers: usize) {
+    for _ in 0..workers {
+        tokio::spawn(worker(state.clone()));
+    }
+}
+
+async fn worker(state: Arc<AppState>) {
+    let mut interval = interval(Duration::from_secs(5));
+    interval.set_missed_tick_behavior(MissedTickBehavior::Skip);
+    loop {
+        interval.tick().await;
+
+        loop {
+            match state.songs().run_transcode_job().await {
+                Ok(true) => continue,
+                Ok(false) => break,
+                Err(err) => {
+                    tracing::warn!("failed to run transcode job: {err}");
+                    break;
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,63 @@
+use super::m20250302_000002_create_song_table::Song;
+use async_trait::async_trait;
+use sea_orm_migration::prelude::*;
+
+pub struct Migration;
+impl MigrationName for Migration {
+    fn name(&self) -> &str {
+        "m20250518_000001_create_song_rendition_table"
+    }
+}
+#[async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(SongRendition::Table)
+                    .col(ColumnDef::new(SongRendition::Song).big_integer().not_null())
+                    .col(ColumnDef::new(SongRendition::Quality).string().not_null())
+                    .col(ColumnDef::new(SongRendition::Audio).string().not_null())
+                    .col(
+                        ColumnDef::new(SongRendition::CreatedAt)
+                            .date_time()
+                            .not_null(),
+                    )
+                    .primary_key(
+                        Index::create()
+                            .col(SongRendition::Song)
+                            .col(SongRendition::Quality),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_foreign_key(
+                ForeignKeyCreateStatement::new()
+                    .from(SongRendition::Table, SongRendition::Song)
+                    .to(Song::Table, Song::SongId)
+                    .on_delete(ForeignKeyAction::Cascade)
+                    .on_update(ForeignKeyAction::Restrict)
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(SongRendition::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(Iden)]
+pub enum SongRendition {
+    Table,
+    Song,
+    Quality,
+    Audio,
+    CreatedAt,
+}
@@ -0,0 +1,92 @@
+use super::m20250302_000002_create_song_table::Song;
+use async_trait::async_trait;
+use sea_orm_migration::prelude::*;
+
+pub struct Migration;
+impl MigrationName for Migration {
+    fn name(&self) -> &str {
+        "m20250525_000001_create_transcode_job_table"
+    }
+}
+#[async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(TranscodeJob::Table)
+                    .col(
+                        ColumnDef::new(TranscodeJob::JobId)
+                            .big_integer()
+                            .primary_key()
+                            .auto_increment(),
+                    )
+                    .col(ColumnDef::new(TranscodeJob::Song).big_integer().not_null())
+                    .col(ColumnDef::new(TranscodeJob::Source).string().not_null())
+                    .col(ColumnDef::new(TranscodeJob::Target).string().not_null())
+                    .col(ColumnDef::new(TranscodeJob::Quality).string().not_null())
+                    .col(ColumnDef::new(TranscodeJob::State).string().not_null())
+                    .col(ColumnDef::new(TranscodeJob::Attempts).integer().not_null())
+                    .col(ColumnDef::new(TranscodeJob::Error).text())
+                    .col(
+                        ColumnDef::new(TranscodeJob::CreatedAt)
+                            .date_time()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(TranscodeJob::UpdatedAt)
+                            .date_time()
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(TranscodeJob::Claim).string())
+                    .col(ColumnDef::new(TranscodeJob::LeaseExpiresAt).date_time())
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_foreign_key(
+                ForeignKeyCreateStatement::new()
+                    .from(TranscodeJob::Table, TranscodeJob::Song)
+                    .to(Song::Table, Song::SongId)
+                    .on_delete(ForeignKeyAction::Cascade)
+                    .on_update(ForeignKeyAction::Restrict)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .table(TranscodeJob::Table)
+                    .col(TranscodeJob::State)
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(TranscodeJob::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(Iden)]
+pub enum TranscodeJob {
+    Table,
+    JobId,
+    Song,
+    Source,
+    Target,
+    Quality,
+    State,
+    Attempts,
+    Error,
+    CreatedAt,
+    UpdatedAt,
+    Claim,
+    LeaseExpiresAt,
+}
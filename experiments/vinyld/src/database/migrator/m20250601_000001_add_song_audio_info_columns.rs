@@ -0,0 +1,72 @@
+use super::m20250302_000002_create_song_table::Song;
+use async_trait::async_trait;
+use sea_orm_migration::prelude::*;
+
+pub struct Migration;
+impl MigrationName for Migration {
+    fn name(&self) -> &str {
+        "m20250601_000001_add_song_audio_info_columns"
+    }
+}
+#[async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Columns are added one by one, since SQLite doesn't support multiple alterations in one statement.
+        for column in [
+            ColumnDef::new(SongAudioInfo::DurationMs)
+                .big_integer()
+                .to_owned(),
+            ColumnDef::new(SongAudioInfo::SampleRate)
+                .integer()
+                .to_owned(),
+            ColumnDef::new(SongAudioInfo::Channels).integer().to_owned(),
+            ColumnDef::new(SongAudioInfo::SourceCodec)
+                .string()
+                .to_owned(),
+            ColumnDef::new(SongAudioInfo::SourceBitrate)
+                .integer()
+                .to_owned(),
+        ] {
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(Song::Table)
+                        .add_column(column)
+                        .to_owned(),
+                )
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        for column in [
+            SongAudioInfo::DurationMs,
+            SongAudioInfo::SampleRate,
+            SongAudioInfo::Channels,
+            SongAudioInfo::SourceCodec,
+            SongAudioInfo::SourceBitrate,
+        ] {
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(Song::Table)
+                        .drop_column(column)
+                        .to_owned(),
+                )
+                .await?;
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Iden)]
+pub enum SongAudioInfo {
+    DurationMs,
+    SampleRate,
+    Channels,
+    SourceCodec,
+    SourceBitrate,
+}
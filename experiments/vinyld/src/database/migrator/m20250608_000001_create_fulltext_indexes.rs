@@ -0,0 +1,59 @@
+use async_trait::async_trait;
+use sea_orm_migration::{prelude::*, sea_orm::DatabaseBackend};
+
+pub struct Migration;
+impl MigrationName for Migration {
+    fn name(&self) -> &str {
+        "m20250608_000001_create_fulltext_indexes"
+    }
+}
+#[async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Expressions of the indexes must be kept the same as those in `crate::database::search`.
+        let statements: &[&str] = match manager.get_database_backend() {
+            DatabaseBackend::Postgres => &[
+                r#"CREATE INDEX "song_fulltext" ON "song" USING GIN (to_tsvector('simple', "title"))"#,
+                r#"CREATE INDEX "album_fulltext" ON "album" USING GIN (to_tsvector('simple', "title" || ' ' || coalesce("description", '')))"#,
+            ],
+            DatabaseBackend::MySql => &[
+                "ALTER TABLE `song` ADD FULLTEXT INDEX `song_fulltext` (`title`)",
+                "ALTER TABLE `album` ADD FULLTEXT INDEX `album_fulltext` (`title`, `description`)",
+            ],
+            // SQLite has no full-text indexes on ordinary tables, so searching falls back to pattern matching.
+            DatabaseBackend::Sqlite => &[],
+        };
+
+        for statement in statements {
+            manager
+                .get_connection()
+                .execute_unprepared(statement)
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let statements: &[&str] = match manager.get_database_backend() {
+            DatabaseBackend::Postgres => &[
+                r#"DROP INDEX "song_fulltext""#,
+                r#"DROP INDEX "album_fulltext""#,
+            ],
+            DatabaseBackend::MySql => &[
+                "ALTER TABLE `song` DROP INDEX `song_fulltext`",
+                "ALTER TABLE `album` DROP INDEX `album_fulltext`",
+            ],
+            DatabaseBackend::Sqlite => &[],
+        };
+
+        for statement in statements {
+            manager
+                .get_connection()
+                .execute_unprepared(statement)
+                .await?;
+        }
+
+        Ok(())
+    }
+}
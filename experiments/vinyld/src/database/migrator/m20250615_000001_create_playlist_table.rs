@@ -0,0 +1,151 @@
+use super::{m20250216_000001_create_user_table::User, m20250302_000002_create_song_table::Song};
+use async_trait::async_trait;
+use sea_orm_migration::prelude::*;
+
+pub struct Migration;
+impl MigrationName for Migration {
+    fn name(&self) -> &str {
+        "m20250615_000001_create_playlist_table"
+    }
+}
+#[async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(Playlist::Table)
+                    .col(
+                        ColumnDef::new(Playlist::Id)
+                            .big_integer()
+                            .primary_key()
+                            .auto_increment(),
+                    )
+                    .col(ColumnDef::new(Playlist::Title).text().not_null())
+                    .col(ColumnDef::new(Playlist::Description).text())
+                    .col(ColumnDef::new(Playlist::Owner).big_integer().not_null())
+                    .col(ColumnDef::new(Playlist::ReadPolicy).json().not_null())
+                    .col(ColumnDef::new(Playlist::WritePolicy).json().not_null())
+                    .col(ColumnDef::new(Playlist::Cover).string())
+                    .col(ColumnDef::new(Playlist::CreatedAt).date_time().not_null())
+                    .col(ColumnDef::new(Playlist::UpdatedAt).date_time().not_null())
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_foreign_key(
+                ForeignKeyCreateStatement::new()
+                    .from(Playlist::Table, Playlist::Owner)
+                    .to(User::Table, User::Uid)
+                    .on_delete(ForeignKeyAction::Cascade)
+                    .on_update(ForeignKeyAction::Restrict)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_table(
+                Table::create()
+                    .table(PlaylistEntry::Table)
+                    .col(
+                        ColumnDef::new(PlaylistEntry::Playlist)
+                            .big_integer()
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(PlaylistEntry::Song).big_integer().not_null())
+                    .col(
+                        ColumnDef::new(PlaylistEntry::Position)
+                            .big_integer()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(PlaylistEntry::AddedBy)
+                            .big_integer()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(PlaylistEntry::AddedAt)
+                            .date_time()
+                            .not_null(),
+                    )
+                    .primary_key(
+                        Index::create()
+                            .col(PlaylistEntry::Playlist)
+                            .col(PlaylistEntry::Song),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_foreign_key(
+                ForeignKeyCreateStatement::new()
+                    .from(PlaylistEntry::Table, PlaylistEntry::Playlist)
+                    .to(Playlist::Table, Playlist::Id)
+                    .on_delete(ForeignKeyAction::Cascade)
+                    .on_update(ForeignKeyAction::Restrict)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_foreign_key(
+                ForeignKeyCreateStatement::new()
+                    .from(PlaylistEntry::Table, PlaylistEntry::Song)
+                    .to(Song::Table, Song::SongId)
+                    .on_delete(ForeignKeyAction::Cascade)
+                    .on_update(ForeignKeyAction::Restrict)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("playlist_entry_position")
+                    .table(PlaylistEntry::Table)
+                    .col(PlaylistEntry::Playlist)
+                    .col(PlaylistEntry::Position)
+                    .unique()
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(PlaylistEntry::Table).to_owned())
+            .await?;
+        manager
+            .drop_table(Table::drop().table(Playlist::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(Iden)]
+pub enum Playlist {
+    Table,
+    #[iden = "playlist_id"]
+    Id,
+    Title,
+    Description,
+    Owner,
+    ReadPolicy,
+    WritePolicy,
+    Cover,
+    CreatedAt,
+    UpdatedAt,
+}
+
+#[derive(Iden)]
+pub enum PlaylistEntry {
+    Table,
+    Playlist,
+    Song,
+    Position,
+    AddedBy,
+    AddedAt,
+}
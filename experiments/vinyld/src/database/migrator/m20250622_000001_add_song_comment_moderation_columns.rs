@@ -0,0 +1,89 @@
+use super::m20250406_000001_create_song_comment_table::SongComment;
+use async_trait::async_trait;
+use sea_orm_migration::prelude::*;
+
+pub struct Migration;
+impl MigrationName for Migration {
+    fn name(&self) -> &str {
+        "m20250622_000001_add_song_comment_moderation_columns"
+    }
+}
+#[async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Columns are added one by one, since SQLite doesn't support multiple alterations in one statement.
+        for column in [
+            // Comments posted before moderation existed are treated as approved.
+            ColumnDef::new(SongCommentModeration::Status)
+                .string()
+                .not_null()
+                .default("approved")
+                .to_owned(),
+            ColumnDef::new(SongCommentModeration::EditedAt)
+                .date_time()
+                .to_owned(),
+            ColumnDef::new(SongCommentModeration::Deleted)
+                .boolean()
+                .not_null()
+                .default(false)
+                .to_owned(),
+        ] {
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(SongComment::Table)
+                        .add_column(column)
+                        .to_owned(),
+                )
+                .await?;
+        }
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("song_comment_thread")
+                    .table(SongComment::Table)
+                    .col(SongComment::Song)
+                    .col(SongComment::Parent)
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_index(
+                Index::drop()
+                    .name("song_comment_thread")
+                    .table(SongComment::Table)
+                    .to_owned(),
+            )
+            .await?;
+
+        for column in [
+            SongCommentModeration::Status,
+            SongCommentModeration::EditedAt,
+            SongCommentModeration::Deleted,
+        ] {
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(SongComment::Table)
+                        .drop_column(column)
+                        .to_owned(),
+                )
+                .await?;
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Iden)]
+pub enum SongCommentModeration {
+    Status,
+    EditedAt,
+    Deleted,
+}
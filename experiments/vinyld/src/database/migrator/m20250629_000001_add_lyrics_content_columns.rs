@@ -0,0 +1,75 @@
+use super::{
+    m20250302_000002_create_song_table::Song, m20250501_000001_create_lyrics_table::Lyrics,
+};
+use async_trait::async_trait;
+use sea_orm_migration::prelude::*;
+
+pub struct Migration;
+impl MigrationName for Migration {
+    fn name(&self) -> &str {
+        "m20250629_000001_add_lyrics_content_columns"
+    }
+}
+#[async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Columns are added one by one, since SQLite doesn't support multiple alterations in one statement.
+        for column in [
+            ColumnDef::new(LyricsContent::Content).json().to_owned(),
+            ColumnDef::new(LyricsContent::Format)
+                .string()
+                .not_null()
+                .default("plain")
+                .to_owned(),
+        ] {
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(Lyrics::Table)
+                        .add_column(column)
+                        .to_owned(),
+                )
+                .await?;
+        }
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Song::Table)
+                    .add_column(ColumnDef::new(LyricsContent::Language).string())
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Song::Table)
+                    .drop_column(LyricsContent::Language)
+                    .to_owned(),
+            )
+            .await?;
+
+        for column in [LyricsContent::Content, LyricsContent::Format] {
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(Lyrics::Table)
+                        .drop_column(column)
+                        .to_owned(),
+                )
+                .await?;
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Iden)]
+pub enum LyricsContent {
+    Content,
+    Format,
+    Language,
+}
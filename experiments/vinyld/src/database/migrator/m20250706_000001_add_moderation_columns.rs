@@ -0,0 +1,87 @@
+use super::{m20250302_000001_create_album_table::Album, m20250302_000002_create_song_table::Song};
+use async_trait::async_trait;
+use sea_orm_migration::prelude::*;
+
+pub struct Migration;
+impl MigrationName for Migration {
+    fn name(&self) -> &str {
+        "m20250706_000001_add_moderation_columns"
+    }
+}
+#[async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        for table in [Album::Table.into_iden(), Song::Table.into_iden()] {
+            // Columns are added one by one, since SQLite doesn't support multiple alterations in one statement.
+            for column in [
+                // Content created before moderation existed is treated as approved.
+                ColumnDef::new(Moderation::Status)
+                    .string()
+                    .not_null()
+                    .default("approved")
+                    .to_owned(),
+                ColumnDef::new(Moderation::RejectionReason)
+                    .text()
+                    .to_owned(),
+            ] {
+                manager
+                    .alter_table(
+                        Table::alter()
+                            .table(table.clone())
+                            .add_column(column)
+                            .to_owned(),
+                    )
+                    .await?;
+            }
+        }
+
+        for (name, table) in [
+            ("album_status", Album::Table.into_iden()),
+            ("song_status", Song::Table.into_iden()),
+        ] {
+            manager
+                .create_index(
+                    Index::create()
+                        .name(name)
+                        .table(table)
+                        .col(Moderation::Status)
+                        .to_owned(),
+                )
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        for (name, table) in [
+            ("album_status", Album::Table.into_iden()),
+            ("song_status", Song::Table.into_iden()),
+        ] {
+            manager
+                .drop_index(Index::drop().name(name).table(table).to_owned())
+                .await?;
+        }
+
+        for table in [Album::Table.into_iden(), Song::Table.into_iden()] {
+            for column in [Moderation::Status, Moderation::RejectionReason] {
+                manager
+                    .alter_table(
+                        Table::alter()
+                            .table(table.clone())
+                            .drop_column(column)
+                            .to_owned(),
+                    )
+                    .await?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Iden)]
+pub enum Moderation {
+    Status,
+    RejectionReason,
+}
@@ -0,0 +1,70 @@
+use async_trait::async_trait;
+use sea_orm_migration::prelude::*;
+
+pub struct Migration;
+impl MigrationName for Migration {
+    fn name(&self) -> &str {
+        "m20250713_000001_create_audit_log_table"
+    }
+}
+#[async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Records outlive their actors and targets, so no foreign keys are created.
+        manager
+            .create_table(
+                Table::create()
+                    .table(AuditLog::Table)
+                    .col(
+                        ColumnDef::new(AuditLog::AuditId)
+                            .big_integer()
+                            .primary_key()
+                            .auto_increment(),
+                    )
+                    .col(ColumnDef::new(AuditLog::Actor).big_integer().not_null())
+                    .col(ColumnDef::new(AuditLog::Action).string().not_null())
+                    .col(ColumnDef::new(AuditLog::Target).string().not_null())
+                    .col(ColumnDef::new(AuditLog::Detail).json())
+                    .col(ColumnDef::new(AuditLog::CreatedAt).date_time().not_null())
+                    .col(ColumnDef::new(AuditLog::Source).string().not_null())
+                    .to_owned(),
+            )
+            .await?;
+
+        for (name, column) in [
+            ("audit_log_actor", AuditLog::Actor),
+            ("audit_log_target", AuditLog::Target),
+            ("audit_log_created_at", AuditLog::CreatedAt),
+        ] {
+            manager
+                .create_index(
+                    Index::create()
+                        .name(name)
+                        .table(AuditLog::Table)
+                        .col(column)
+                        .to_owned(),
+                )
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(AuditLog::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(Iden)]
+pub enum AuditLog {
+    Table,
+    AuditId,
+    Actor,
+    Action,
+    Target,
+    Detail,
+    CreatedAt,
+    Source,
+}
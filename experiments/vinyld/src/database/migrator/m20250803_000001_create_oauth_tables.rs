@@ -0,0 +1,118 @@
+use super::m20250216_000001_create_user_table::User;
+use async_trait::async_trait;
+use sea_orm_migration::prelude::*;
+
+pub struct Migration;
+impl MigrationName for Migration {
+    fn name(&self) -> &str {
+        "m20250803_000001_create_oauth_tables"
+    }
+}
+#[async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(OauthClient::Table)
+                    .col(
+                        ColumnDef::new(OauthClient::ClientId)
+                            .string()
+                            .not_null()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(OauthClient::Name).string().not_null())
+                    .col(ColumnDef::new(OauthClient::Owner).big_integer().not_null())
+                    .col(ColumnDef::new(OauthClient::RedirectUris).json().not_null())
+                    .col(
+                        ColumnDef::new(OauthClient::CreatedAt)
+                            .date_time()
+                            .not_null(),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .from(OauthClient::Table, OauthClient::Owner)
+                            .to(User::Table, User::Uid)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Restrict),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_table(
+                Table::create()
+                    .table(OauthCode::Table)
+                    .col(
+                        ColumnDef::new(OauthCode::CodeHash)
+                            .string()
+                            .not_null()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(OauthCode::ClientId).string().not_null())
+                    .col(ColumnDef::new(OauthCode::Uid).big_integer().not_null())
+                    .col(ColumnDef::new(OauthCode::RedirectUri).string().not_null())
+                    .col(ColumnDef::new(OauthCode::Permissions).integer().not_null())
+                    .col(ColumnDef::new(OauthCode::CodeChallenge).string().not_null())
+                    .col(ColumnDef::new(OauthCode::ExpiresAt).date_time().not_null())
+                    .foreign_key(
+                        ForeignKey::create()
+                            .from(OauthCode::Table, OauthCode::ClientId)
+                            .to(OauthClient::Table, OauthClient::ClientId)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Restrict),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .from(OauthCode::Table, OauthCode::Uid)
+                            .to(User::Table, User::Uid)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Restrict),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("oauth_client_owner")
+                    .table(OauthClient::Table)
+                    .col(OauthClient::Owner)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(OauthCode::Table).to_owned())
+            .await?;
+        manager
+            .drop_table(Table::drop().table(OauthClient::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(Iden)]
+pub enum OauthClient {
+    Table,
+    ClientId,
+    Name,
+    Owner,
+    RedirectUris,
+    CreatedAt,
+}
+
+#[derive(Iden)]
+pub enum OauthCode {
+    Table,
+    CodeHash,
+    ClientId,
+    Uid,
+    RedirectUri,
+    Permissions,
+    CodeChallenge,
+    ExpiresAt,
+}
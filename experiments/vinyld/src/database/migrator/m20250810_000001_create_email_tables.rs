@@ -0,0 +1,109 @@
+use super::m20250216_000001_create_user_table::User;
+use async_trait::async_trait;
+use sea_orm_migration::prelude::*;
+
+pub struct Migration;
+impl MigrationName for Migration {
+    fn name(&self) -> &str {
+        "m20250810_000001_create_email_tables"
+    }
+}
+#[async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Addresses aren't unique here, since an unverified address must not keep its owner from using it. Verified
+        // addresses are kept unique by the server.
+        manager
+            .create_table(
+                Table::create()
+                    .table(UserAuthEmail::Table)
+                    .col(
+                        ColumnDef::new(UserAuthEmail::Uid)
+                            .big_integer()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(UserAuthEmail::Email).string().not_null())
+                    .col(ColumnDef::new(UserAuthEmail::VerifiedAt).date_time())
+                    .foreign_key(
+                        ForeignKey::create()
+                            .from(UserAuthEmail::Table, UserAuthEmail::Uid)
+                            .to(User::Table, User::Uid)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Restrict),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_table(
+                Table::create()
+                    .table(EmailCode::Table)
+                    .col(
+                        ColumnDef::new(EmailCode::CodeHash)
+                            .string()
+                            .not_null()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(EmailCode::Email).string().not_null())
+                    .col(ColumnDef::new(EmailCode::Uid).big_integer())
+                    .col(ColumnDef::new(EmailCode::Purpose).string().not_null())
+                    .col(ColumnDef::new(EmailCode::ExpiresAt).date_time().not_null())
+                    .foreign_key(
+                        ForeignKey::create()
+                            .from(EmailCode::Table, EmailCode::Uid)
+                            .to(User::Table, User::Uid)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Restrict),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("user_auth_email_email")
+                    .table(UserAuthEmail::Table)
+                    .col(UserAuthEmail::Email)
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_index(
+                Index::create()
+                    .name("email_code_email")
+                    .table(EmailCode::Table)
+                    .col(EmailCode::Email)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(EmailCode::Table).to_owned())
+            .await?;
+        manager
+            .drop_table(Table::drop().table(UserAuthEmail::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(Iden)]
+pub enum UserAuthEmail {
+    Table,
+    Uid,
+    Email,
+    VerifiedAt,
+}
+
+#[derive(Iden)]
+pub enum EmailCode {
+    Table,
+    CodeHash,
+    Email,
+    Uid,
+    Purpose,
+    ExpiresAt,
+}
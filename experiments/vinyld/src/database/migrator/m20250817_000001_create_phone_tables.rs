@@ -0,0 +1,123 @@
+use super::m20250216_000001_create_user_table::User;
+use async_trait::async_trait;
+use sea_orm_migration::prelude::*;
+
+pub struct Migration;
+impl MigrationName for Migration {
+    fn name(&self) -> &str {
+        "m20250817_000001_create_phone_tables"
+    }
+}
+#[async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Like email addresses, phone numbers are only kept unique by the server once verified.
+        manager
+            .create_table(
+                Table::create()
+                    .table(UserAuthPhone::Table)
+                    .col(
+                        ColumnDef::new(UserAuthPhone::Uid)
+                            .big_integer()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(UserAuthPhone::Phone).string().not_null())
+                    .col(ColumnDef::new(UserAuthPhone::VerifiedAt).date_time())
+                    .foreign_key(
+                        ForeignKey::create()
+                            .from(UserAuthPhone::Table, UserAuthPhone::Uid)
+                            .to(User::Table, User::Uid)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Restrict),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        // Codes sent by SMS are short, so they are looked up by phone number and limited in attempts, instead of
+        // being looked up by their hashes.
+        manager
+            .create_table(
+                Table::create()
+                    .table(PhoneCode::Table)
+                    .col(
+                        ColumnDef::new(PhoneCode::Id)
+                            .big_integer()
+                            .primary_key()
+                            .auto_increment(),
+                    )
+                    .col(ColumnDef::new(PhoneCode::Phone).string().not_null())
+                    .col(ColumnDef::new(PhoneCode::Uid).big_integer())
+                    .col(ColumnDef::new(PhoneCode::Purpose).string().not_null())
+                    .col(ColumnDef::new(PhoneCode::CodeHash).string().not_null())
+                    .col(
+                        ColumnDef::new(PhoneCode::Attempts)
+                            .integer()
+                            .not_null()
+                            .default(0),
+                    )
+                    .col(ColumnDef::new(PhoneCode::CreatedAt).date_time().not_null())
+                    .col(ColumnDef::new(PhoneCode::ExpiresAt).date_time().not_null())
+                    .foreign_key(
+                        ForeignKey::create()
+                            .from(PhoneCode::Table, PhoneCode::Uid)
+                            .to(User::Table, User::Uid)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Restrict),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("user_auth_phone_phone")
+                    .table(UserAuthPhone::Table)
+                    .col(UserAuthPhone::Phone)
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_index(
+                Index::create()
+                    .name("phone_code_phone")
+                    .table(PhoneCode::Table)
+                    .col(PhoneCode::Phone)
+                    .col(PhoneCode::CreatedAt)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(PhoneCode::Table).to_owned())
+            .await?;
+        manager
+            .drop_table(Table::drop().table(UserAuthPhone::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(Iden)]
+pub enum UserAuthPhone {
+    Table,
+    Uid,
+    Phone,
+    VerifiedAt,
+}
+
+#[derive(Iden)]
+pub enum PhoneCode {
+    Table,
+    #[iden = "phone_code_id"]
+    Id,
+    Phone,
+    Uid,
+    Purpose,
+    CodeHash,
+    Attempts,
+    CreatedAt,
+    ExpiresAt,
+}
@@ -0,0 +1,147 @@
+use super::m20250216_000001_create_user_table::User;
+use async_trait::async_trait;
+use sea_orm_migration::prelude::*;
+
+pub struct Migration;
+impl MigrationName for Migration {
+    fn name(&self) -> &str {
+        "m20250824_000001_create_two_factor_tables"
+    }
+}
+#[async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(UserAuthTotp::Table)
+                    .col(
+                        ColumnDef::new(UserAuthTotp::Uid)
+                            .big_integer()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(UserAuthTotp::Secret).string().not_null())
+                    .col(ColumnDef::new(UserAuthTotp::ConfirmedAt).date_time())
+                    .col(
+                        ColumnDef::new(UserAuthTotp::LastStep)
+                            .big_integer()
+                            .not_null()
+                            .default(0),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .from(UserAuthTotp::Table, UserAuthTotp::Uid)
+                            .to(User::Table, User::Uid)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Restrict),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_table(
+                Table::create()
+                    .table(RecoveryCode::Table)
+                    .col(
+                        ColumnDef::new(RecoveryCode::CodeHash)
+                            .string()
+                            .not_null()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(RecoveryCode::Uid).big_integer().not_null())
+                    .foreign_key(
+                        ForeignKey::create()
+                            .from(RecoveryCode::Table, RecoveryCode::Uid)
+                            .to(User::Table, User::Uid)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Restrict),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        // A login challenge is issued when the password is correct but a second factor is still required.
+        manager
+            .create_table(
+                Table::create()
+                    .table(LoginChallenge::Table)
+                    .col(
+                        ColumnDef::new(LoginChallenge::ChallengeHash)
+                            .string()
+                            .not_null()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(LoginChallenge::Uid).big_integer().not_null())
+                    .col(ColumnDef::new(LoginChallenge::Source).string().not_null())
+                    .col(
+                        ColumnDef::new(LoginChallenge::Attempts)
+                            .integer()
+                            .not_null()
+                            .default(0),
+                    )
+                    .col(
+                        ColumnDef::new(LoginChallenge::ExpiresAt)
+                            .date_time()
+                            .not_null(),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .from(LoginChallenge::Table, LoginChallenge::Uid)
+                            .to(User::Table, User::Uid)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Restrict),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("recovery_code_uid")
+                    .table(RecoveryCode::Table)
+                    .col(RecoveryCode::Uid)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(LoginChallenge::Table).to_owned())
+            .await?;
+        manager
+            .drop_table(Table::drop().table(RecoveryCode::Table).to_owned())
+            .await?;
+        manager
+            .drop_table(Table::drop().table(UserAuthTotp::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(Iden)]
+pub enum UserAuthTotp {
+    Table,
+    Uid,
+    Secret,
+    ConfirmedAt,
+    LastStep,
+}
+
+#[derive(Iden)]
+pub enum RecoveryCode {
+    Table,
+    CodeHash,
+    Uid,
+}
+
+#[derive(Iden)]
+pub enum LoginChallenge {
+    Table,
+    ChallengeHash,
+    Uid,
+    Source,
+    Attempts,
+    ExpiresAt,
+}
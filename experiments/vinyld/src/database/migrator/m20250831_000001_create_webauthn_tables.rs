@@ -0,0 +1,146 @@
+use super::{
+    m20250216_000001_create_user_table::User,
+    m20250824_000001_create_two_factor_tables::LoginChallenge,
+};
+use async_trait::async_trait;
+use sea_orm_migration::prelude::*;
+
+pub struct Migration;
+impl MigrationName for Migration {
+    fn name(&self) -> &str {
+        "m20250831_000001_create_webauthn_tables"
+    }
+}
+#[async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(UserAuthWebauthn::Table)
+                    .col(
+                        ColumnDef::new(UserAuthWebauthn::CredentialId)
+                            .string()
+                            .not_null()
+                            .primary_key(),
+                    )
+                    .col(
+                        ColumnDef::new(UserAuthWebauthn::Uid)
+                            .big_integer()
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(UserAuthWebauthn::Name).string().not_null())
+                    .col(
+                        ColumnDef::new(UserAuthWebauthn::PublicKey)
+                            .string()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(UserAuthWebauthn::SignCount)
+                            .big_integer()
+                            .not_null()
+                            .default(0),
+                    )
+                    .col(
+                        ColumnDef::new(UserAuthWebauthn::CreatedAt)
+                            .date_time()
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(UserAuthWebauthn::LastUsedAt).date_time())
+                    .foreign_key(
+                        ForeignKey::create()
+                            .from(UserAuthWebauthn::Table, UserAuthWebauthn::Uid)
+                            .to(User::Table, User::Uid)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Restrict),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        // A challenge of a second factor belongs to the login challenge that it completes.
+        manager
+            .create_table(
+                Table::create()
+                    .table(WebauthnChallenge::Table)
+                    .col(
+                        ColumnDef::new(WebauthnChallenge::ChallengeHash)
+                            .string()
+                            .not_null()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(WebauthnChallenge::Uid).big_integer())
+                    .col(
+                        ColumnDef::new(WebauthnChallenge::Purpose)
+                            .string()
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(WebauthnChallenge::LoginChallengeHash).string())
+                    .col(
+                        ColumnDef::new(WebauthnChallenge::ExpiresAt)
+                            .date_time()
+                            .not_null(),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .from(WebauthnChallenge::Table, WebauthnChallenge::Uid)
+                            .to(User::Table, User::Uid)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Restrict),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .from(
+                                WebauthnChallenge::Table,
+                                WebauthnChallenge::LoginChallengeHash,
+                            )
+                            .to(LoginChallenge::Table, LoginChallenge::ChallengeHash)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Restrict),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("user_auth_webauthn_uid")
+                    .table(UserAuthWebauthn::Table)
+                    .col(UserAuthWebauthn::Uid)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(WebauthnChallenge::Table).to_owned())
+            .await?;
+        manager
+            .drop_table(Table::drop().table(UserAuthWebauthn::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(Iden)]
+pub enum UserAuthWebauthn {
+    Table,
+    CredentialId,
+    Uid,
+    Name,
+    PublicKey,
+    SignCount,
+    CreatedAt,
+    LastUsedAt,
+}
+
+#[derive(Iden)]
+pub enum WebauthnChallenge {
+    Table,
+    ChallengeHash,
+    Uid,
+    Purpose,
+    LoginChallengeHash,
+    ExpiresAt,
+}
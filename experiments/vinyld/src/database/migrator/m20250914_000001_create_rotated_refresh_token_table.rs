@@ -0,0 +1,75 @@
+use super::m20250316_000001_create_session_table::Session;
+use async_trait::async_trait;
+use sea_orm_migration::prelude::*;
+
+pub struct Migration;
+impl MigrationName for Migration {
+    fn name(&self) -> &str {
+        "m20250914_000001_create_rotated_refresh_token_table"
+    }
+}
+#[async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(RotatedRefreshToken::Table)
+                    .col(
+                        ColumnDef::new(RotatedRefreshToken::TokenHash)
+                            .string()
+                            .not_null()
+                            .primary_key(),
+                    )
+                    .col(
+                        ColumnDef::new(RotatedRefreshToken::Session)
+                            .big_integer()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(RotatedRefreshToken::RotatedAt)
+                            .date_time()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(RotatedRefreshToken::ExpiresAt)
+                            .date_time()
+                            .not_null(),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .from(RotatedRefreshToken::Table, RotatedRefreshToken::Session)
+                            .to(Session::Table, Session::Numeral)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Restrict),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("rotated_refresh_token_session")
+                    .table(RotatedRefreshToken::Table)
+                    .col(RotatedRefreshToken::Session)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(RotatedRefreshToken::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(Iden)]
+pub enum RotatedRefreshToken {
+    Table,
+    TokenHash,
+    Session,
+    RotatedAt,
+    ExpiresAt,
+}
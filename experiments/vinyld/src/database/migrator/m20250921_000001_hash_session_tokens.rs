@@ -0,0 +1,79 @@
+use super::{
+    m20250316_000001_create_session_table::Session,
+    m20250914_000001_create_rotated_refresh_token_table::RotatedRefreshToken,
+};
+use async_trait::async_trait;
+use sea_orm_migration::prelude::*;
+
+pub struct Migration;
+impl MigrationName for Migration {
+    fn name(&self) -> &str {
+        "m20250921_000001_hash_session_tokens"
+    }
+}
+#[async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Plaintext tokens can't be hashed here without the key, and must not stay in the database, so every session
+        // is revoked and users have to log in again.
+        revoke_sessions(manager).await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Session::Table)
+                    .rename_column(Session::RefreshToken, SessionTokenHash::RefreshTokenHash)
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Session::Table)
+                    .rename_column(Session::AccessToken, SessionTokenHash::AccessTokenHash)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        revoke_sessions(manager).await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Session::Table)
+                    .rename_column(SessionTokenHash::RefreshTokenHash, Session::RefreshToken)
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Session::Table)
+                    .rename_column(SessionTokenHash::AccessTokenHash, Session::AccessToken)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+/// Deletes every session with its rotated refresh tokens.
+async fn revoke_sessions(manager: &SchemaManager<'_>) -> Result<(), DbErr> {
+    manager
+        .exec_stmt(
+            Query::delete()
+                .from_table(RotatedRefreshToken::Table)
+                .to_owned(),
+        )
+        .await?;
+    manager
+        .exec_stmt(Query::delete().from_table(Session::Table).to_owned())
+        .await
+}
+
+#[derive(Iden)]
+pub enum SessionTokenHash {
+    RefreshTokenHash,
+    AccessTokenHash,
+}
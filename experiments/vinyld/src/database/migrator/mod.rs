@@ -6,6 +6,24 @@ mod m20250316_000001_create_session_table;
 mod m20250318_000001_create_app_settings_table;
 mod m20250406_000001_create_song_comment_table;
 mod m20250501_000001_create_lyrics_table;
+mod m20250518_000001_create_song_rendition_table;
+mod m20250525_000001_create_transcode_job_table;
+mod m20250601_000001_add_song_audio_info_columns;
+mod m20250608_000001_create_fulltext_indexes;
+mod m20250615_000001_create_playlist_table;
+mod m20250622_000001_add_song_comment_moderation_columns;
+mod m20250629_000001_add_lyrics_content_columns;
+mod m20250706_000001_add_moderation_columns;
+mod m20250713_000001_create_audit_log_table;
+mod m20250727_000001_add_session_created_at_column;
+mod m20250803_000001_create_oauth_tables;
+mod m20250810_000001_create_email_tables;
+mod m20250817_000001_create_phone_tables;
+mod m20250824_000001_create_two_factor_tables;
+mod m20250831_000001_create_webauthn_tables;
+mod m20250907_000001_add_user_username_generated_column;
+mod m20250914_000001_create_rotated_refresh_token_table;
+mod m20250921_000001_hash_session_tokens;
 
 use async_trait::async_trait;
 use sea_orm_migration::prelude::*;
@@ -23,6 +41,24 @@ impl MigratorTrait for Migrator {
             Box::new(m20250318_000001_create_app_settings_table::Migration),
             Box::new(m20250406_000001_create_song_comment_table::Migration),
             Box::new(m20250501_000001_create_lyrics_table::Migration),
+            Box::new(m20250518_000001_create_song_rendition_table::Migration),
+            Box::new(m20250525_000001_create_transcode_job_table::Migration),
+            Box::new(m20250601_000001_add_song_audio_info_columns::Migration),
+            Box::new(m20250608_000001_create_fulltext_indexes::Migration),
+            Box::new(m20250615_000001_create_playlist_table::Migration),
+            Box::new(m20250622_000001_add_song_comment_moderation_columns::Migration),
+            Box::new(m20250629_000001_add_lyrics_content_columns::Migration),
+            Box::new(m20250706_000001_add_moderation_columns::Migration),
+            Box::new(m20250713_000001_create_audit_log_table::Migration),
+            Box::new(m20250727_000001_add_session_created_at_column::Migration),
+            Box::new(m20250803_000001_create_oauth_tables::Migration),
+            Box::new(m20250810_000001_create_email_tables::Migration),
+            Box::new(m20250817_000001_create_phone_tables::Migration),
+            Box::new(m20250824_000001_create_two_factor_tables::Migration),
+            Box::new(m20250831_000001_create_webauthn_tables::Migration),
+            Box::new(m20250907_000001_add_user_username_generated_column::Migration),
+            Box::new(m20250914_000001_create_rotated_refresh_token_table::Migration),
+            Box::new(m20250921_000001_hash_session_tokens::Migration),
         ]
     }
 }
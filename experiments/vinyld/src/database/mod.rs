@@ -1,6 +1,8 @@
 mod cron;
 pub mod entity;
+pub mod jobs;
 mod migrator;
+pub mod search;
 
 use anyhow::anyhow;
 use migrator::Migrator;
@@ -0,0 +1,96 @@
+//! Full-text search over the vendors supported by the server.
+//!
+//! PostgreSQL and MySQL use their native full-text indexes, created by migration
+//! `m20250608_000001_create_fulltext_indexes`. Other backends fall back to pattern matching.
+
+use sea_orm::{
+    DatabaseBackend,
+    sea_query::{Alias, Condition, Expr, LikeExpr, SimpleExpr},
+};
+
+/// Searchable document of a table, made of one or more text columns.
+#[derive(Debug, Clone, Copy)]
+pub struct Document {
+    /// `tsvector` expression of the document on PostgreSQL, which must be the same as its index.
+    postgres: &'static str,
+
+    /// Column list of the document on MySQL, which must be the same as its index.
+    mysql: &'static str,
+
+    /// Columns of the document.
+    columns: &'static [&'static str],
+}
+impl Document {
+    /// Returns a condition which is satisfied if the document matches the query.
+    pub fn matches(&self, backend: DatabaseBackend, query: &str) -> SimpleExpr {
+        match backend {
+            DatabaseBackend::Postgres => Expr::cust_with_values(
+                format!("{} @@ websearch_to_tsquery('simple', $1)", self.postgres),
+                [query],
+            ),
+            DatabaseBackend::MySql => Expr::cust_with_values(
+                format!(
+                    "MATCH ({}) AGAINST (? IN NATURAL LANGUAGE MODE)",
+                    self.mysql
+                ),
+                [query],
+            ),
+            DatabaseBackend::Sqlite => contains(self.columns, query),
+        }
+    }
+
+    /// Returns relevance of the document to the query, where a higher value means more relevant.
+    pub fn rank(&self, backend: DatabaseBackend, query: &str) -> SimpleExpr {
+        match backend {
+            DatabaseBackend::Postgres => Expr::cust_with_values(
+                format!(
+                    "ts_rank({}, websearch_to_tsquery('simple', $1))",
+                    self.postgres
+                ),
+                [query],
+            ),
+            DatabaseBackend::MySql => self.matches(backend, query),
+            DatabaseBackend::Sqlite => Expr::val(0).into(),
+        }
+    }
+}
+
+/// Searchable document of songs.
+pub const SONG: Document = Document {
+    postgres: r#"to_tsvector('simple', "title")"#,
+    mysql: "`title`",
+    columns: &["title"],
+};
+
+/// Searchable document of albums.
+pub const ALBUM: Document = Document {
+    postgres: r#"to_tsvector('simple', "title" || ' ' || coalesce("description", ''))"#,
+    mysql: "`title`, `description`",
+    columns: &["title", "description"],
+};
+
+/// Returns a condition which is satisfied if any of the columns contains the query as a substring.
+///
+/// This doesn't use indexes, so it should only be used for tables which are small or rarely searched.
+pub fn contains(columns: &[&str], query: &str) -> SimpleExpr {
+    let pattern = format!("%{}%", escape_like(query));
+    columns
+        .iter()
+        .fold(Condition::any(), |condition, column| {
+            condition.add(
+                Expr::col(Alias::new(*column)).like(LikeExpr::new(pattern.clone()).escape('\\')),
+            )
+        })
+        .into()
+}
+
+/// Escapes wildcards in a `LIKE` pattern.
+fn escape_like(value: &str) -> String {
+    value
+        .chars()
+        .flat_map(|c| match c {
+            '%' | '_' | '\\' => vec!['\\', c],
+            c => vec![c],
+        })
+        .collect()
+}
@@ -39,6 +39,11 @@ impl Error {
         }
     }
 
+    simple_error_constructor!(
+        invalid_grant,
+        INVALID_GRANT,
+        "The authorization grant is invalid, expired or has been used.",
+    );
     simple_error_constructor!(unauthorized, UNAUTHORIZED, "You must login to continue.");
     simple_error_constructor!(
         restricted_session,
@@ -55,7 +60,27 @@ impl Error {
         USERNAME_CONFLICT,
         "The username to be saved conflicts with that of someone else.",
     );
+    simple_error_constructor!(
+        audio_not_ready,
+        AUDIO_NOT_READY,
+        "The audio is still being processed, please try again later.",
+    );
+    simple_error_constructor!(
+        playlist_entry_conflict,
+        PLAYLIST_ENTRY_CONFLICT,
+        "The song is already in the playlist.",
+    );
+    simple_error_constructor!(
+        email_conflict,
+        EMAIL_CONFLICT,
+        "The email address is already used by someone else.",
+    );
     simple_error_constructor!(payload_too_large, PAYLOAD_TOO_LARGE, "Payload too large.");
+    simple_error_constructor!(
+        range_not_satisfiable,
+        RANGE_NOT_SATISFIABLE,
+        "The requested range is not satisfiable.",
+    );
     simple_error_constructor!(
         invalid_username,
         INVALID_USERNAME,
@@ -87,6 +112,65 @@ impl Error {
         NON_EXISTENT_ALBUM,
         "The album required is not found.",
     );
+    simple_error_constructor!(
+        unrecognized_audio,
+        UNRECOGNIZED_AUDIO,
+        "The uploaded file is not a recognized audio file.",
+    );
+    simple_error_constructor!(
+        audio_processing_failed,
+        AUDIO_PROCESSING_FAILED,
+        "The uploaded audio could not be processed, please upload it again.",
+    );
+    simple_error_constructor!(
+        non_existent_song,
+        NON_EXISTENT_SONG,
+        "The song required is not found.",
+    );
+    simple_error_constructor!(
+        invalid_email,
+        INVALID_EMAIL,
+        "The email address is malformed.",
+    );
+    simple_error_constructor!(
+        invalid_verification_code,
+        INVALID_VERIFICATION_CODE,
+        "The verification code is incorrect or has expired.",
+    );
+    simple_error_constructor!(
+        invalid_phone,
+        INVALID_PHONE,
+        "The phone number is malformed.",
+    );
+    simple_error_constructor!(
+        phone_conflict,
+        PHONE_CONFLICT,
+        "The phone number is already used by someone else.",
+    );
+
+    pub fn audio_too_long(max_duration_secs: u64) -> Self {
+        Self {
+            code: ErrorCode::AUDIO_TOO_LONG,
+            message: "The uploaded audio is longer than allowed on this server.".into(),
+            payload: Some(serde_json::json! {{"max_duration_secs": max_duration_secs}}),
+        }
+    }
+
+    pub fn audio_quality_mismatch(reason: &str) -> Self {
+        Self {
+            code: ErrorCode::AUDIO_QUALITY_MISMATCH,
+            message: "The uploaded audio does not match the claimed quality.".into(),
+            payload: Some(serde_json::json! {{"reason": reason}}),
+        }
+    }
+
+    pub fn invalid_lyrics<E: Display>(reason: E) -> Self {
+        Self {
+            code: ErrorCode::INVALID_LYRICS,
+            message: "The uploaded lyrics are malformed.".into(),
+            payload: Some(serde_json::json! {{"reason": format!("{reason}")}}),
+        }
+    }
 
     pub fn banned_user(payload: serde_json::Value) -> Self {
         Self {
@@ -104,6 +188,38 @@ impl Error {
         }
     }
 
+    pub fn too_many_requests(retry_after_secs: u64) -> Self {
+        Self {
+            code: ErrorCode::TOO_MANY_REQUESTS,
+            message: "Too many requests. Try again later.".into(),
+            payload: Some(serde_json::json! {{"retry_after_secs": retry_after_secs}}),
+        }
+    }
+
+    pub fn invalid_profile(field: &str) -> Self {
+        Self {
+            code: ErrorCode::INVALID_PROFILE,
+            message: "A field of the profile is malformed.".into(),
+            payload: Some(serde_json::json! {{"field": field}}),
+        }
+    }
+
+    pub fn shared_album_conflict(albums: &[i64]) -> Self {
+        Self {
+            code: ErrorCode::SHARED_ALBUM_CONFLICT,
+            message: "Albums of the account contain songs uploaded by others, which must be removed first.".into(),
+            payload: Some(serde_json::json! {{"albums": albums}}),
+        }
+    }
+
+    pub fn login_locked(retry_after_secs: u64) -> Self {
+        Self {
+            code: ErrorCode::LOGIN_LOCKED,
+            message: "The account is locked out for a while after too many failed logins.".into(),
+            payload: Some(serde_json::json! {{"retry_after_secs": retry_after_secs}}),
+        }
+    }
+
     pub fn internal<E: Display>(since: E) -> Self {
         Self {
             code: ErrorCode::INTERNAL,
@@ -122,6 +238,19 @@ impl Error {
         if self.code.to_http() == StatusCode::UNAUTHORIZED {
             headers.append("WWW-Authenticate", HeaderValue::from_static("Vinyl-Token"));
         }
+        if let Some(retry_after) = self
+            .payload
+            .as_ref()
+            .filter(|_| {
+                matches!(
+                    self.code,
+                    ErrorCode::TOO_MANY_REQUESTS | ErrorCode::LOGIN_LOCKED
+                )
+            })
+            .and_then(|x| x["retry_after_secs"].as_u64())
+        {
+            headers.append("Retry-After", HeaderValue::from(retry_after));
+        }
 
         headers
     }
@@ -157,6 +286,7 @@ impl From<vinioss::Error> for Error {
     fn from(value: vinioss::Error) -> Self {
         match value {
             vinioss::Error::ObjectNotFound => Self::not_found(),
+            vinioss::Error::InvalidRange => Self::range_not_satisfiable(),
             vinioss::Error::UnknownVendor(err) => Self::internal(err),
             vinioss::Error::InvalidConfiguration(err) => Self::internal(err),
             vinioss::Error::Unrecognized(err) => Self::internal(err),
@@ -170,6 +300,7 @@ impl From<vinioss::Error> for Error {
 pub struct ErrorCode(pub u32);
 impl ErrorCode {
     pub const BAD_REQUEST: Self = Self(400);
+    pub const INVALID_GRANT: Self = Self(40001);
     pub const UNAUTHORIZED: Self = Self(401);
     pub const RESTRICTED_SESSION: Self = Self(40301);
     pub const RESTRICTED_USER: Self = Self(40302);
@@ -177,13 +308,31 @@ impl ErrorCode {
     pub const DENIED_BY_POLICY: Self = Self(40304);
     pub const NOT_FOUND: Self = Self(404);
     pub const USERNAME_CONFLICT: Self = Self(40901);
+    pub const AUDIO_NOT_READY: Self = Self(40902);
+    pub const PLAYLIST_ENTRY_CONFLICT: Self = Self(40903);
+    pub const EMAIL_CONFLICT: Self = Self(40904);
+    pub const PHONE_CONFLICT: Self = Self(40905);
+    pub const SHARED_ALBUM_CONFLICT: Self = Self(40906);
     pub const PAYLOAD_TOO_LARGE: Self = Self(413);
+    pub const RANGE_NOT_SATISFIABLE: Self = Self(416);
+    pub const TOO_MANY_REQUESTS: Self = Self(429);
+    pub const LOGIN_LOCKED: Self = Self(42901);
     pub const INVALID_USERNAME: Self = Self(42201);
     pub const INVALID_PASSWORD: Self = Self(42202);
     pub const INVALID_NICKNAME: Self = Self(42203);
     pub const LOGIN_INCORRECT: Self = Self(42204);
     pub const REGISTRATION_FORM_NOT_FILLED: Self = Self(42205);
     pub const NON_EXISTENT_ALBUM: Self = Self(42206);
+    pub const UNRECOGNIZED_AUDIO: Self = Self(42207);
+    pub const AUDIO_TOO_LONG: Self = Self(42208);
+    pub const AUDIO_QUALITY_MISMATCH: Self = Self(42209);
+    pub const NON_EXISTENT_SONG: Self = Self(42210);
+    pub const INVALID_LYRICS: Self = Self(42211);
+    pub const INVALID_EMAIL: Self = Self(42212);
+    pub const INVALID_VERIFICATION_CODE: Self = Self(42213);
+    pub const INVALID_PHONE: Self = Self(42214);
+    pub const INVALID_PROFILE: Self = Self(42215);
+    pub const AUDIO_PROCESSING_FAILED: Self = Self(42216);
 
     pub const INTERNAL: Self = Self(500);
 
@@ -6,8 +6,18 @@ pub const DATABASE_URL: &str = "DATABASE_URL";
 pub const DATABASE_CRON_ENABLED: &str = "DATABASE_CRON_ENABLED";
 pub const DATABASE_DANGEROUS_FRESH_MIGRATIONS: &str = "DATABASE_DANGEROUS_FRESH_MIGRATIONS";
 pub const NETWORK_LISTEN_URL: &str = "NETWORK_LISTEN_URL";
+pub const NETWORK_TRUSTED_PROXIES: &str = "NETWORK_TRUSTED_PROXIES";
 pub const S3_BUCKET: &str = "S3_BUCKET";
 pub const OBJECT_STORAGE: &str = "OBJECT_STORAGE";
+pub const TRANSCODE_WORKERS: &str = "TRANSCODE_WORKERS";
+pub const AUDIO_BACKEND: &str = "AUDIO_BACKEND";
+pub const OBJECT_DELIVERY: &str = "OBJECT_DELIVERY";
+pub const MAIL_TRANSPORT: &str = "MAIL_TRANSPORT";
+pub const MAIL_FROM: &str = "MAIL_FROM";
+pub const SMS_PROVIDER: &str = "SMS_PROVIDER";
+pub const WEBAUTHN_ORIGIN: &str = "WEBAUTHN_ORIGIN";
+pub const WEBAUTHN_RP_ID: &str = "WEBAUTHN_RP_ID";
+pub const TOKEN_HASH_KEY: &str = "TOKEN_HASH_KEY";
 
 /// Fetches an environment variable and parses it into a type.
 pub fn fetch_env<T>(key: &str) -> anyhow::Result<T>
@@ -2,15 +2,21 @@
 
 pub mod dotenv;
 pub mod temp;
+pub mod token_key;
 
 pub use temp::Temp;
+pub use token_key::TokenKey;
 
 #[derive(Debug)]
 pub struct LocalData {
     pub temp: Temp,
+    pub token_key: TokenKey,
 }
 impl LocalData {
     pub fn new() -> anyhow::Result<Self> {
-        Ok(Self { temp: Temp::new()? })
+        Ok(Self {
+            temp: Temp::new()?,
+            token_key: TokenKey::new()?,
+        })
     }
 }
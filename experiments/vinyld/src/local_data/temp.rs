@@ -27,6 +27,19 @@ impl Temp {
 
     /// Opens a new temporary file.
     pub async fn open(&self) -> std::io::Result<TempFile> {
+        TempFile::new().await
+    }
+}
+
+/// A temporary file.
+#[derive(Debug, Clone)]
+pub struct TempFile(Arc<TempFileInner>);
+impl TempFile {
+    /// Creates a new temporary file.
+    ///
+    /// This is useful for components which don't have access to a [`Temp`]. The temporary file storage must have been
+    /// initialized.
+    pub async fn new() -> std::io::Result<Self> {
         let mut options = tokio::fs::File::options();
         options.create_new(true).write(true);
 
@@ -34,18 +47,18 @@ impl Temp {
             let filename = vinutie::random::filename("temp", "tmp");
             let path = Path::new(PATH).join(filename);
             break match options.open(&path).await {
-                Ok(_) => Ok(TempFile(Arc::new(TempFileInner { path }))),
+                Ok(_) => Ok(Self(Arc::new(TempFileInner { path }))),
                 Err(err) if err.kind() == ErrorKind::AlreadyExists => continue,
                 Err(x) => Err(x),
             };
         }
     }
-}
 
-/// A temporary file.
-#[derive(Debug, Clone)]
-pub struct TempFile(Arc<TempFileInner>);
-impl TempFile {
+    /// Returns path to the temporary file.
+    pub fn path(&self) -> &Path {
+        &self.0.path
+    }
+
     /// Returns a reader for the temporary file.
     pub async fn reader(&self) -> std::io::Result<TempFileReader> {
         let file = tokio::fs::File::open(&self.0.path).await?;
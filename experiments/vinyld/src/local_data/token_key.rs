@@ -0,0 +1,77 @@
+//! Key of hashing tokens at rest.
+
+use super::dotenv::TOKEN_HASH_KEY;
+use anyhow::anyhow;
+use base64::{Engine, prelude::BASE64_URL_SAFE_NO_PAD};
+use hmac::{Hmac, Mac};
+use rand::RngCore;
+use sha2::Sha256;
+use std::{fmt::Debug, io::Write};
+
+const PATH: &str = "token_key";
+
+/// Minimum length of the key in bytes.
+const MIN_LEN: usize = 32;
+
+/// Key of hashing access and refresh tokens for storage, so that a leaked database holds no usable tokens.
+///
+/// The key is taken from `TOKEN_HASH_KEY` in URL-safe Base64 if it's set, which every server sharing the database
+/// must agree on. Otherwise it's read from the `token_key` file in the working directory, which is generated on first
+/// start. Changing the key invalidates every session.
+pub struct TokenKey(Vec<u8>);
+impl TokenKey {
+    /// Loads the key, generating one if there is none.
+    pub fn new() -> anyhow::Result<Self> {
+        let encoded = match std::env::var(TOKEN_HASH_KEY) {
+            Ok(x) => x,
+            Err(_) => match std::fs::read_to_string(PATH) {
+                Ok(x) => x,
+                Err(err) if err.kind() == std::io::ErrorKind::NotFound => Self::generate()?,
+                Err(err) => return Err(anyhow!("failed to read the token key: {err}")),
+            },
+        };
+
+        let key = BASE64_URL_SAFE_NO_PAD
+            .decode(encoded.trim())
+            .map_err(|err| anyhow!("malformed token key: {err}"))?;
+        if key.len() < MIN_LEN {
+            return Err(anyhow!(
+                "the token key must be at least {MIN_LEN} bytes long"
+            ));
+        }
+
+        Ok(Self(key))
+    }
+
+    /// Hashes a token for storage.
+    pub fn hash(&self, token: &str) -> String {
+        let mut mac =
+            Hmac::<Sha256>::new_from_slice(&self.0).expect("HMAC accepts keys of any length");
+        mac.update(token.as_bytes());
+        BASE64_URL_SAFE_NO_PAD.encode(mac.finalize().into_bytes())
+    }
+
+    /// Generates a key and saves it, returning it encoded.
+    fn generate() -> anyhow::Result<String> {
+        tracing::info!("Generating the token key...");
+        let mut data = [0u8; MIN_LEN];
+        rand::rng().fill_bytes(&mut data);
+        let encoded = BASE64_URL_SAFE_NO_PAD.encode(data);
+
+        let mut options = std::fs::File::options();
+        options.create_new(true).write(true);
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+        options
+            .open(PATH)
+            .and_then(|mut x| x.write_all(encoded.as_bytes()))
+            .map_err(|err| anyhow!("failed to save the token key: {err}"))?;
+
+        Ok(encoded)
+    }
+}
+impl Debug for TokenKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("TokenKey(..)")
+    }
+}
@@ -0,0 +1,391 @@
+//! Parsing of lyrics formats into the normalized form.
+
+use crate::error::Error;
+use quick_xml::{Reader, events::Event};
+use serde::{Deserialize, Serialize};
+
+/// Format of uploaded lyrics.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Format {
+    /// Plain text, one line per line.
+    Plain,
+
+    /// LRC, with `[mm:ss.xx]` timestamps.
+    Lrc,
+
+    /// Timed Text Markup Language.
+    Ttml,
+}
+impl Format {
+    /// Returns the form stored in the database.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Plain => "plain",
+            Self::Lrc => "lrc",
+            Self::Ttml => "ttml",
+        }
+    }
+}
+
+/// Normalized lyrics.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Content {
+    /// Lines of the lyrics, in the order of appearance.
+    pub lines: Vec<Line>,
+}
+impl Content {
+    /// Parses the lyrics in given format.
+    pub fn parse(format: Format, text: &str) -> Result<Self, Error> {
+        let content = match format {
+            Format::Plain => parse_plain(text),
+            Format::Lrc => parse_lrc(text)?,
+            Format::Ttml => parse_ttml(text)?,
+        };
+        if content.lines.iter().all(|x| x.text.is_empty()) {
+            return Err(Error::invalid_lyrics("the lyrics are empty"));
+        }
+        Ok(content)
+    }
+
+    /// Returns `true` if every line is timed.
+    pub fn synced(&self) -> bool {
+        self.lines.iter().all(|x| x.start_ms.is_some())
+    }
+}
+
+/// A line of lyrics.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Line {
+    /// Time when the line starts, in milliseconds.
+    pub start_ms: Option<u64>,
+
+    /// Time when the line ends, in milliseconds. For LRC, this is when the next line starts.
+    pub end_ms: Option<u64>,
+
+    /// Text of the line.
+    pub text: String,
+}
+
+fn parse_plain(text: &str) -> Content {
+    let lines: Vec<&str> = text.lines().map(str::trim_end).collect();
+    let len = lines.len() - lines.iter().rev().take_while(|x| x.is_empty()).count();
+
+    Content {
+        lines: lines[..len]
+            .iter()
+            .map(|x| Line {
+                start_ms: None,
+                end_ms: None,
+                text: x.to_string(),
+            })
+            .collect(),
+    }
+}
+
+fn parse_lrc(text: &str) -> Result<Content, Error> {
+    let mut offset = 0i64;
+    let mut timed = Vec::new();
+
+    for (number, line) in text.lines().enumerate() {
+        let mut rest = line.trim();
+        if rest.is_empty() {
+            continue;
+        }
+
+        let mut times = Vec::new();
+        while let Some(tag) = rest.strip_prefix('[') {
+            let Some((tag, after)) = tag.split_once(']') else {
+                break;
+            };
+            rest = after;
+            if let Some(time) = parse_lrc_time(tag) {
+                times.push(time);
+            } else if let Some(value) = tag.strip_prefix("offset:") {
+                offset = value.trim().parse().map_err(|_| {
+                    Error::invalid_lyrics(format!("malformed offset at line {}", number + 1))
+                })?;
+            }
+            // Other ID tags, like `[ar:...]`, carry no lyrics.
+        }
+
+        if times.is_empty() {
+            if rest.trim().is_empty() {
+                continue;
+            }
+            return Err(Error::invalid_lyrics(format!(
+                "line {} has no timestamp",
+                number + 1
+            )));
+        }
+
+        let text = strip_word_times(rest.trim());
+        timed.extend(times.into_iter().map(|x| (x, text.clone())));
+    }
+
+    if timed.is_empty() {
+        return Err(Error::invalid_lyrics("no timed lines are found"));
+    }
+
+    // Positive offsets make lyrics appear sooner.
+    timed.sort_by_key(|x| x.0);
+    let starts: Vec<u64> = timed
+        .iter()
+        .map(|x| {
+            i64::try_from(x.0)
+                .unwrap_or(i64::MAX)
+                .saturating_sub(offset)
+                .max(0) as u64
+        })
+        .collect();
+    Ok(Content {
+        lines: timed
+            .into_iter()
+            .enumerate()
+            .map(|(i, (_, text))| Line {
+                start_ms: Some(starts[i]),
+                end_ms: starts.get(i + 1).copied(),
+                text,
+            })
+            .collect(),
+    })
+}
+
+/// Parses an LRC timestamp like `mm:ss`, `mm:ss.xx` or `mm:ss.xxx`, into milliseconds. Timestamps that overflow are
+/// rejected.
+fn parse_lrc_time(tag: &str) -> Option<u64> {
+    let (minutes, seconds) = tag.split_once(':')?;
+    let minutes: u64 = minutes.trim().parse().ok()?;
+    let (seconds, fraction) = seconds.split_once(['.', ':']).unwrap_or((seconds, ""));
+    let seconds: u64 = seconds.parse().ok()?;
+    if seconds >= 60 || fraction.len() > 3 || !fraction.bytes().all(|x| x.is_ascii_digit()) {
+        return None;
+    }
+    let millis = match fraction.len() {
+        0 => 0,
+        n => fraction.parse::<u64>().ok()? * 10u64.pow(3 - n as u32),
+    };
+
+    minutes
+        .checked_mul(60_000)?
+        .checked_add(seconds * 1000 + millis)
+}
+
+/// Removes word timestamps of enhanced LRC, like `<mm:ss.xx>`.
+fn strip_word_times(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('<') {
+        match rest[start..].find('>') {
+            Some(end) if parse_lrc_time(&rest[start + 1..start + end]).is_some() => {
+                result.push_str(&rest[..start]);
+                rest = &rest[start + end + 1..];
+            }
+            _ => {
+                result.push_str(&rest[..=start]);
+                rest = &rest[start + 1..];
+            }
+        }
+    }
+    result.push_str(rest);
+    result.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+fn parse_ttml(text: &str) -> Result<Content, Error> {
+    let malformed = |err: quick_xml::Error| Error::invalid_lyrics(format!("malformed TTML: {err}"));
+    let mut reader = Reader::from_str(text);
+    let mut lines = Vec::new();
+    let mut paragraph: Option<Line> = None;
+    let mut seen_tt = false;
+
+    loop {
+        match reader.read_event().map_err(malformed)? {
+            Event::Start(start) if start.local_name().as_ref() == b"tt" => seen_tt = true,
+            Event::Start(start) if start.local_name().as_ref() == b"p" => {
+                let time = |name: &[u8]| -> Result<Option<u64>, Error> {
+                    match start
+                        .try_get_attribute(name)
+                        .map_err(|x| malformed(x.into()))?
+                    {
+                        Some(x) => {
+                            let value = x.unescape_value().map_err(malformed)?;
+                            parse_ttml_time(&value).map(Some).ok_or_else(|| {
+                                Error::invalid_lyrics(format!("malformed TTML time `{value}`"))
+                            })
+                        }
+                        None => Ok(None),
+                    }
+                };
+                paragraph = Some(Line {
+                    start_ms: time(b"begin")?,
+                    end_ms: time(b"end")?,
+                    text: String::new(),
+                });
+            }
+            Event::Empty(empty) if empty.local_name().as_ref() == b"br" => {
+                if let Some(line) = &mut paragraph {
+                    line.text.push(' ');
+                }
+            }
+            Event::Text(text) => {
+                if let Some(line) = &mut paragraph {
+                    line.text.push_str(&text.unescape().map_err(malformed)?);
+                }
+            }
+            Event::CData(data) => {
+                if let Some(line) = &mut paragraph {
+                    line.text.push_str(&String::from_utf8_lossy(&data));
+                }
+            }
+            Event::End(end) if end.local_name().as_ref() == b"p" => {
+                if let Some(mut line) = paragraph.take() {
+                    line.text = line.text.split_whitespace().collect::<Vec<_>>().join(" ");
+                    lines.push(line);
+                }
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+
+    if !seen_tt {
+        return Err(Error::invalid_lyrics("the document is not TTML"));
+    }
+    Ok(Content { lines })
+}
+
+/// Parses a TTML time expression into milliseconds.
+///
+/// Clock times like `hh:mm:ss.fff` or `mm:ss.fff` and offset times like `12.5s` or `500ms` are supported.
+fn parse_ttml_time(value: &str) -> Option<u64> {
+    let value = value.trim();
+    let seconds = |x: &str| -> Option<f64> {
+        let x: f64 = x.parse().ok()?;
+        (x.is_finite() && x >= 0.0).then_some(x)
+    };
+
+    let total = if let Some(x) = value.strip_suffix("ms") {
+        seconds(x)? / 1000.0
+    } else if let Some(x) = value.strip_suffix('s') {
+        seconds(x)?
+    } else if let Some(x) = value.strip_suffix('m') {
+        seconds(x)? * 60.0
+    } else if let Some(x) = value.strip_suffix('h') {
+        seconds(x)? * 3600.0
+    } else {
+        value
+            .split(':')
+            .try_fold((0.0, 0), |(acc, n), x| {
+                Some((acc * 60.0 + seconds(x)?, n + 1))
+            })
+            .filter(|x| x.1 <= 3)?
+            .0
+    };
+
+    Some((total * 1000.0).round() as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns start times and texts of the lines.
+    fn lines(content: &Content) -> Vec<(Option<u64>, &str)> {
+        content
+            .lines
+            .iter()
+            .map(|x| (x.start_ms, x.text.as_str()))
+            .collect()
+    }
+
+    #[test]
+    fn lrc_lines_are_sorted_and_chained() {
+        let content = Content::parse(
+            Format::Lrc,
+            "[ar:Someone]\n[00:01.50]First\n[00:03.250]Second\n\n[01:00]Third",
+        )
+        .unwrap();
+        assert_eq!(
+            lines(&content),
+            [
+                (Some(1500), "First"),
+                (Some(3250), "Second"),
+                (Some(60000), "Third")
+            ],
+        );
+        assert_eq!(content.lines[0].end_ms, Some(3250));
+        assert_eq!(content.lines[2].end_ms, None);
+        assert!(content.synced());
+    }
+
+    #[test]
+    fn lrc_lines_with_several_timestamps_are_repeated() {
+        let content =
+            Content::parse(Format::Lrc, "[00:10.00][00:02.00]Chorus\n[00:05.00]Verse").unwrap();
+        assert_eq!(
+            lines(&content),
+            [
+                (Some(2000), "Chorus"),
+                (Some(5000), "Verse"),
+                (Some(10000), "Chorus")
+            ],
+        );
+    }
+
+    #[test]
+    fn lrc_offsets_shift_lines() {
+        let content =
+            Content::parse(Format::Lrc, "[offset:500]\n[00:00.20]A\n[00:01.00]B").unwrap();
+        assert_eq!(lines(&content), [(Some(0), "A"), (Some(500), "B")]);
+
+        let content = Content::parse(Format::Lrc, "[offset:-250]\n[00:01.00]A").unwrap();
+        assert_eq!(lines(&content), [(Some(1250), "A")]);
+
+        let content =
+            Content::parse(Format::Lrc, "[offset:-9223372036854775808]\n[00:01.00]A").unwrap();
+        assert_eq!(lines(&content), [(Some(i64::MAX as u64), "A")]);
+    }
+
+    #[test]
+    fn lrc_word_times_are_stripped() {
+        let content =
+            Content::parse(Format::Lrc, "[00:01.00]<00:01.00>Hello <00:01.50>world <a>").unwrap();
+        assert_eq!(lines(&content), [(Some(1000), "Hello world <a>")]);
+    }
+
+    #[test]
+    fn lrc_overflowing_timestamps_are_rejected() {
+        assert_eq!(parse_lrc_time("99999999999999999:00"), None);
+        assert!(Content::parse(Format::Lrc, "[99999999999999999:00]Overflow").is_err());
+    }
+
+    #[test]
+    fn ttml_times_are_parsed() {
+        assert_eq!(parse_ttml_time("00:01:02.5"), Some(62500));
+        assert_eq!(parse_ttml_time("01:02.250"), Some(62250));
+        assert_eq!(parse_ttml_time("1:00:00"), Some(3_600_000));
+        assert_eq!(parse_ttml_time("12.5s"), Some(12500));
+        assert_eq!(parse_ttml_time("500ms"), Some(500));
+        assert_eq!(parse_ttml_time("1.5m"), Some(90000));
+        assert_eq!(parse_ttml_time("2h"), Some(7_200_000));
+        assert_eq!(parse_ttml_time("1:2:3:4"), None);
+        assert_eq!(parse_ttml_time("-1s"), None);
+    }
+
+    #[test]
+    fn ttml_paragraphs_become_lines() {
+        let content = Content::parse(
+            Format::Ttml,
+            r#"<tt xmlns="http://www.w3.org/ns/ttml"><body><div>
+                <p begin="00:00:01.000" end="2.5s">First<br/>line</p>
+                <p begin="3s" end="00:00:04.000"><span>Second</span> &amp; last</p>
+            </div></body></tt>"#,
+        )
+        .unwrap();
+        assert_eq!(
+            lines(&content),
+            [(Some(1000), "First line"), (Some(3000), "Second & last")],
+        );
+        assert_eq!(content.lines[0].end_ms, Some(2500));
+    }
+}
@@ -0,0 +1,269 @@
+mod format;
+
+use crate::{
+    AppState,
+    database::entity::lyrics,
+    error::Error,
+    policy::Subject,
+    song::SongId,
+    user::{Uid, User},
+};
+use chrono::{DateTime, Utc};
+use sea_orm::{
+    ActiveModelTrait, ActiveValue::NotSet, ColumnTrait, EntityTrait, IntoActiveModel, QueryFilter,
+    QueryOrder, Set, TransactionTrait,
+};
+use serde::{Deserialize, Serialize};
+
+pub use format::{Content, Format};
+
+/// Manager of song lyrics.
+///
+/// A song can have several versions of lyrics in each language. Versions uploaded by the song's uploader or an
+/// administrator are authoritative, and the newest authoritative version of a language replaces the older ones.
+pub struct Lyrics<'a>(&'a AppState);
+impl Lyrics<'_> {
+    /// Maximum size of uploaded lyrics, in bytes.
+    const MAX_SOURCE_LEN: usize = 256 * 1024;
+
+    /// Uploads lyrics of a song.
+    pub async fn upload(
+        &self,
+        song_id: SongId,
+        uploader: &User,
+        upload: Upload,
+        administrator: bool,
+    ) -> Result<LyricsId, Error> {
+        if upload.content.len() > Self::MAX_SOURCE_LEN {
+            return Err(Error::payload_too_large());
+        }
+        let language = crate::util::language::normalize(&upload.language)?;
+        let content = Content::parse(upload.format, &upload.content)?;
+
+        let song = self
+            .0
+            .songs()
+            .listenable(song_id, &Subject::from_user(uploader))
+            .await?;
+        let is_authoritative = song.uploader == uploader.uid.0 || administrator;
+
+        let txn = self.0.database.conn.begin().await?;
+        if is_authoritative {
+            lyrics::Entity::update_many()
+                .col_expr(lyrics::Column::IsAuthoritative, false.into())
+                .filter(lyrics::Column::Song.eq(song_id.0))
+                .filter(lyrics::Column::Language.eq(&language))
+                .exec(&txn)
+                .await?;
+        }
+        let lyrics_id = lyrics::Entity::insert(lyrics::ActiveModel {
+            lyrics_id: NotSet,
+            song: Set(song_id.0),
+            title: Set(upload.title),
+            description: Set(upload.description),
+            uploader: Set(uploader.uid.0),
+            language: Set(language),
+            is_authoritative: Set(is_authoritative),
+            created_at: Set(Utc::now().naive_utc()),
+            content: Set(Some(
+                serde_json::to_value(content).map_err(Error::internal)?,
+            )),
+            format: Set(upload.format.as_str().into()),
+        })
+        .exec(&txn)
+        .await?
+        .last_insert_id;
+        txn.commit().await?;
+
+        Ok(LyricsId(lyrics_id))
+    }
+
+    /// Lists all versions of lyrics of the song, from the newest.
+    pub async fn list(&self, song_id: SongId, subject: &Subject) -> Result<Vec<Profile>, Error> {
+        self.0.songs().listenable(song_id, subject).await?;
+
+        lyrics::Entity::find()
+            .filter(lyrics::Column::Song.eq(song_id.0))
+            .order_by_desc(lyrics::Column::LyricsId)
+            .all(&*self.0.database.conn)
+            .await?
+            .into_iter()
+            .map(|x| Version::try_from(x).map(|x| x.profile))
+            .collect()
+    }
+
+    /// Returns a version of lyrics.
+    pub async fn get(&self, lyrics_id: LyricsId, subject: &Subject) -> Result<Version, Error> {
+        let model = lyrics::Entity::find_by_id(lyrics_id.0)
+            .one(&*self.0.database.conn)
+            .await?
+            .ok_or_else(Error::not_found)?;
+        self.0
+            .songs()
+            .listenable(SongId(model.song), subject)
+            .await?;
+
+        Version::try_from(model)
+    }
+
+    /// Returns the authoritative lyrics of the song in the requested language.
+    ///
+    /// If there are no authoritative lyrics in the requested language, or no language is requested, the
+    /// authoritative lyrics in the song's original language are returned.
+    pub async fn authoritative(
+        &self,
+        song_id: SongId,
+        language: Option<&str>,
+        subject: &Subject,
+    ) -> Result<Version, Error> {
+        let song = self.0.songs().listenable(song_id, subject).await?;
+
+        let requested = language.map(crate::util::language::normalize).transpose()?;
+        for language in requested.into_iter().chain(song.language) {
+            let model = lyrics::Entity::find()
+                .filter(lyrics::Column::Song.eq(song_id.0))
+                .filter(lyrics::Column::Language.eq(language))
+                .filter(lyrics::Column::IsAuthoritative.eq(true))
+                .order_by_desc(lyrics::Column::LyricsId)
+                .one(&*self.0.database.conn)
+                .await?;
+            if let Some(model) = model {
+                return Version::try_from(model);
+            }
+        }
+
+        Err(Error::not_found())
+    }
+
+    /// Removes a version of lyrics. Only the uploader or an administrator can do this.
+    pub async fn remove(
+        &self,
+        lyrics_id: LyricsId,
+        user: &User,
+        administrator: bool,
+    ) -> Result<(), Error> {
+        let model = lyrics::Entity::find_by_id(lyrics_id.0)
+            .one(&*self.0.database.conn)
+            .await?
+            .ok_or_else(Error::not_found)?;
+        if model.uploader != user.uid.0 && !administrator {
+            return Err(Error::restricted_user());
+        }
+
+        model
+            .into_active_model()
+            .delete(&*self.0.database.conn)
+            .await?;
+
+        Ok(())
+    }
+}
+
+/// Request of uploading lyrics.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Upload {
+    /// Title of the song, in the language of the lyrics.
+    pub title: String,
+
+    /// Description of this version of lyrics, like credits of the translation.
+    #[serde(default)]
+    pub description: String,
+
+    /// Language of the lyrics, as a BCP 47 language tag.
+    pub language: String,
+
+    /// Format of `content`.
+    pub format: Format,
+
+    /// The lyrics, in the given format.
+    pub content: String,
+}
+
+/// Representation of a Lyrics ID.
+///
+/// A Lyrics ID identifies a version of lyrics uniquely and cannot be changed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(transparent)]
+#[repr(transparent)]
+pub struct LyricsId(pub i64);
+
+/// Profile of a version of lyrics.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Profile {
+    /// ID of the lyrics.
+    pub lyrics_id: LyricsId,
+
+    /// The song that the lyrics belong to.
+    pub song: SongId,
+
+    /// Title of the song, in the language of the lyrics.
+    pub title: String,
+
+    /// Description of this version of lyrics.
+    pub description: String,
+
+    /// Uploader of the lyrics.
+    pub uploader: Uid,
+
+    /// Language of the lyrics, as a lowercase BCP 47 language tag.
+    pub language: String,
+
+    /// Whether this is the authoritative version in its language.
+    pub is_authoritative: bool,
+
+    /// Format that the lyrics were uploaded in.
+    pub format: Format,
+
+    /// Whether every line of the lyrics is timed.
+    pub synced: bool,
+
+    /// Time when the lyrics were uploaded.
+    pub created_at: DateTime<Utc>,
+}
+
+/// A version of lyrics, with its content.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Version {
+    #[serde(flatten)]
+    pub profile: Profile,
+
+    /// Normalized content of the lyrics.
+    pub content: Content,
+}
+impl TryFrom<lyrics::Model> for Version {
+    type Error = Error;
+
+    fn try_from(model: lyrics::Model) -> Result<Self, Error> {
+        let content: Content = model
+            .content
+            .map(serde_json::from_value)
+            .transpose()
+            .map_err(Error::internal)?
+            .unwrap_or_default();
+        let format = serde_json::from_value(serde_json::Value::String(model.format))
+            .map_err(Error::internal)?;
+
+        Ok(Self {
+            profile: Profile {
+                lyrics_id: LyricsId(model.lyrics_id),
+                song: SongId(model.song),
+                title: model.title,
+                description: model.description,
+                uploader: Uid(model.uploader),
+                language: model.language,
+                is_authoritative: model.is_authoritative,
+                format,
+                synced: !content.lines.is_empty() && content.synced(),
+                created_at: model.created_at.and_utc(),
+            },
+            content,
+        })
+    }
+}
+
+impl AppState {
+    /// Returns a manager of song lyrics.
+    pub fn lyrics(&self) -> Lyrics<'_> {
+        Lyrics(self)
+    }
+}
@@ -0,0 +1,35 @@
+//! Mail transports for testing and local development.
+
+use super::Transport;
+use async_trait::async_trait;
+use lettre::Message;
+use std::path::PathBuf;
+
+/// Transport that prints messages to the standard output.
+#[derive(Debug)]
+pub struct Stdout;
+#[async_trait]
+impl Transport for Stdout {
+    async fn send(&self, message: Message) -> anyhow::Result<()> {
+        println!("{}", String::from_utf8_lossy(&message.formatted()));
+        Ok(())
+    }
+}
+
+/// Transport that writes messages into a directory as `.eml` files.
+#[derive(Debug)]
+pub struct Directory(PathBuf);
+impl Directory {
+    pub fn new(path: &str) -> anyhow::Result<Self> {
+        std::fs::create_dir_all(path)?;
+        Ok(Self(path.into()))
+    }
+}
+#[async_trait]
+impl Transport for Directory {
+    async fn send(&self, message: Message) -> anyhow::Result<()> {
+        let path = self.0.join(vinutie::random::filename("mail", "eml"));
+        tokio::fs::write(path, message.formatted()).await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,23 @@
+//! SMTP mail transport.
+
+use super::Transport;
+use async_trait::async_trait;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+
+/// Transport that sends messages through an SMTP relay.
+#[derive(Debug)]
+pub struct Smtp(AsyncSmtpTransport<Tokio1Executor>);
+impl Smtp {
+    pub fn new(url: &str) -> anyhow::Result<Self> {
+        Ok(Self(
+            AsyncSmtpTransport::<Tokio1Executor>::from_url(url)?.build(),
+        ))
+    }
+}
+#[async_trait]
+impl Transport for Smtp {
+    async fn send(&self, message: Message) -> anyhow::Result<()> {
+        self.0.send(message).await?;
+        Ok(())
+    }
+}
@@ -1,12 +1,21 @@
 mod album;
 mod api;
 mod app_settings;
+mod audit;
+mod comment;
 mod database;
 mod error;
 mod local_data;
+mod lyrics;
+mod mail;
+mod moderation;
+mod oauth;
 mod personalized;
+mod playlist;
 mod policy;
+mod rate_limit;
 mod setup_wizard;
+mod sms;
 mod song;
 mod user;
 mod util;
@@ -17,9 +26,11 @@ use clap::Parser;
 use database::Database;
 use local_data::{LocalData, dotenv::*};
 use std::{
+    net::SocketAddr,
     path::{Path, PathBuf},
     sync::Arc,
 };
+use url::Url;
 
 /// Application state.
 #[derive(Debug)]
@@ -27,6 +38,12 @@ struct AppState {
     local_data: LocalData,
     database: Database,
     objects: Box<dyn vinioss::Objects>,
+    audio_backend: song::AudioBackend,
+    object_delivery: api::delivery::ObjectDelivery,
+    mailer: mail::Mailer,
+    texter: sms::Texter,
+    relying_party: Option<user::auth::webauthn::RelyingParty>,
+    rate_limiter: rate_limit::RateLimiter,
 }
 impl AppState {
     /// Creates a new application state.
@@ -68,11 +85,58 @@ impl AppState {
 
         let objects = vinioss::connect(&fetch_env::<String>(OBJECT_STORAGE)?).await?;
 
-        Ok(Arc::new(Self {
+        let audio_backend = match std::env::var(AUDIO_BACKEND).as_deref() {
+            Ok("ffmpeg") | Err(_) => song::AudioBackend::Ffmpeg,
+            Ok("native") if cfg!(feature = "opus") => song::AudioBackend::Native,
+            Ok("native") => {
+                return Err(anyhow!(
+                    "the native audio backend requires vinyld to be built with the `opus` feature"
+                ));
+            }
+            Ok(backend) => return Err(anyhow!("unknown audio backend `{backend}`")),
+        };
+
+        let object_delivery = match std::env::var(OBJECT_DELIVERY).as_deref() {
+            Ok("redirect") | Err(_) => api::delivery::ObjectDelivery::Redirect,
+            Ok("proxy") => api::delivery::ObjectDelivery::Proxy,
+            Ok(delivery) => return Err(anyhow!("unknown object delivery mode `{delivery}`")),
+        };
+
+        let mailer = mail::Mailer::new(
+            std::env::var(MAIL_FROM)
+                .as_deref()
+                .unwrap_or("vinyld@localhost"),
+            std::env::var(MAIL_TRANSPORT).as_deref().unwrap_or("stdout"),
+        )?;
+
+        let texter = sms::Texter::new(std::env::var(SMS_PROVIDER).as_deref().unwrap_or("stdout"))?;
+
+        // WebAuthn is only available if the origin of the web app is known, since credentials are bound to it.
+        let relying_party = match fetch_env::<Url>(WEBAUTHN_ORIGIN) {
+            Ok(origin) => Some(user::auth::webauthn::RelyingParty::new(
+                &origin,
+                std::env::var(WEBAUTHN_RP_ID).ok(),
+            )?),
+            Err(_) => None,
+        };
+
+        let rate_limiter =
+            rate_limit::RateLimiter::new(fetch_env::<usize>(NETWORK_TRUSTED_PROXIES).unwrap_or(0));
+
+        let state = Arc::new(Self {
             local_data,
             database,
             objects,
-        }))
+            audio_backend,
+            object_delivery,
+            mailer,
+            texter,
+            relying_party,
+            rate_limiter,
+        });
+        database::jobs::spawn(state.clone(), fetch_env(TRANSCODE_WORKERS).unwrap_or(2));
+
+        Ok(state)
     }
 }
 
@@ -119,7 +183,9 @@ async fn start_axum_app(state: Arc<AppState>) -> anyhow::Result<()> {
     let listener = util::listener::listener().await?;
 
     tokio::spawn(async move {
-        if let Err(err) = axum::serve(listener, axum_app).await {
+        // Client addresses are needed for rate limiting.
+        let service = axum_app.into_make_service_with_connect_info::<SocketAddr>();
+        if let Err(err) = axum::serve(listener, service).await {
             tracing::error!("failed to initialize application: {err}");
             std::process::exit(1);
         }
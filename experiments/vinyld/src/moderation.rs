@@ -0,0 +1,238 @@
+//! Moderation of user-generated content.
+
+use crate::{
+    AppState,
+    album::AlbumId,
+    audit::Actor,
+    database::entity::{album, song},
+    error::Error,
+    policy::{Condition, Subject},
+    song::SongId,
+    user::{Uid, User, group::WHEEL},
+    util::page::{Page, PageQuery},
+};
+use chrono::{DateTime, Utc};
+use sea_orm::{ColumnTrait, EntityTrait, QueryFilter, QueryOrder, QuerySelect};
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+/// Review state of a piece of user-generated content.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ModerationStatus {
+    /// The content is waiting for review, and is only visible to its author.
+    Pending,
+
+    /// The content is visible to everyone.
+    Approved,
+
+    /// The content was rejected by an administrator, and is only visible to its author.
+    Rejected,
+}
+impl ModerationStatus {
+    /// Returns the status of newly submitted content, depending on whether censorship is mandatory.
+    pub fn initial(mandatory_censorship: bool) -> Self {
+        if mandatory_censorship {
+            Self::Pending
+        } else {
+            Self::Approved
+        }
+    }
+
+    /// Returns the status of content newly submitted by the user, depending on whether censorship is mandatory.
+    /// Content of administrators is never held for review.
+    pub fn initial_for(user: &User, mandatory_censorship: bool) -> Self {
+        Self::initial(mandatory_censorship && !user.groups.iter().any(|x| x == WHEEL))
+    }
+
+    /// Returns `true` if content in this status, owned by `owner`, is visible to the subject.
+    ///
+    /// Content that is not approved is only visible to its owner and administrators.
+    pub fn visible_to(self, owner: Uid, subject: &Subject) -> bool {
+        self == Self::Approved
+            || Condition::Any(vec![
+                Condition::MatchUid(owner),
+                Condition::MatchGroup(WHEEL.into()),
+            ])
+            .matches(subject)
+    }
+
+    /// Returns the SQL condition matching rows visible to the subject, whose status and owner are in given columns.
+    ///
+    /// This is the query counterpart of [`ModerationStatus::visible_to`].
+    pub fn visible_condition(
+        status: impl ColumnTrait,
+        owner: impl ColumnTrait,
+        subject: &Subject,
+    ) -> sea_orm::Condition {
+        if Condition::MatchGroup(WHEEL.into()).matches(subject) {
+            return sea_orm::Condition::all();
+        }
+
+        let mut condition = sea_orm::Condition::any().add(status.eq(Self::Approved.as_str()));
+        if let Some(uid) = subject.uid() {
+            condition = condition.add(owner.eq(uid.0));
+        }
+        condition
+    }
+
+    /// Returns the form stored in the database.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Pending => "pending",
+            Self::Approved => "approved",
+            Self::Rejected => "rejected",
+        }
+    }
+}
+impl FromStr for ModerationStatus {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "pending" => Ok(Self::Pending),
+            "approved" => Ok(Self::Approved),
+            "rejected" => Ok(Self::Rejected),
+            _ => Err(Error::internal(format!("unknown moderation status `{s}`"))),
+        }
+    }
+}
+
+/// Manager of the review queue of albums and songs.
+pub struct Moderation<'a>(&'a AppState);
+impl Moderation<'_> {
+    /// Lists items waiting for review, from the oldest.
+    pub async fn queue(&self, kind: ContentKind, query: &PageQuery) -> Result<Page<Item>, Error> {
+        let limit = query.limit();
+        let position = query.position()?;
+        let pending = ModerationStatus::Pending.as_str();
+
+        let mut items = match kind {
+            ContentKind::Album => {
+                let mut select = album::Entity::find()
+                    .filter(album::Column::Status.eq(pending))
+                    .order_by_asc(album::Column::AlbumId)
+                    .limit(limit + 1);
+                if let Some(position) = position {
+                    select = select.filter(album::Column::AlbumId.gt(position));
+                }
+                select
+                    .all(&*self.0.database.conn)
+                    .await?
+                    .into_iter()
+                    .map(|x| Item {
+                        id: x.album_id,
+                        title: x.title,
+                        owner: Uid(x.uploader),
+                        created_at: x.created_at.and_utc(),
+                    })
+                    .collect::<Vec<_>>()
+            }
+            ContentKind::Song => {
+                let mut select = song::Entity::find()
+                    .filter(song::Column::Status.eq(pending))
+                    .order_by_asc(song::Column::SongId)
+                    .limit(limit + 1);
+                if let Some(position) = position {
+                    select = select.filter(song::Column::SongId.gt(position));
+                }
+                select
+                    .all(&*self.0.database.conn)
+                    .await?
+                    .into_iter()
+                    .map(|x| Item {
+                        id: x.song_id,
+                        title: x.title,
+                        owner: Uid(x.uploader),
+                        created_at: x.created_at.and_utc(),
+                    })
+                    .collect()
+            }
+        };
+
+        let next_position = if items.len() as u64 > limit {
+            items.truncate(limit as _);
+            items.last().map(|x| x.id)
+        } else {
+            None
+        };
+        Ok(Page::new(items, next_position))
+    }
+
+    /// Approves an item, making it visible to everyone.
+    pub async fn approve(&self, actor: &Actor, kind: ContentKind, id: i64) -> Result<(), Error> {
+        self.set_status(actor, kind, id, ModerationStatus::Approved, None)
+            .await
+    }
+
+    /// Rejects an item with a reason shown to its owner.
+    pub async fn reject(
+        &self,
+        actor: &Actor,
+        kind: ContentKind,
+        id: i64,
+        reason: String,
+    ) -> Result<(), Error> {
+        if reason.trim().is_empty() {
+            return Err(Error::bad_request("a reason is required to reject content"));
+        }
+
+        self.set_status(actor, kind, id, ModerationStatus::Rejected, Some(reason))
+            .await
+    }
+
+    async fn set_status(
+        &self,
+        actor: &Actor,
+        kind: ContentKind,
+        id: i64,
+        status: ModerationStatus,
+        reason: Option<String>,
+    ) -> Result<(), Error> {
+        match kind {
+            ContentKind::Album => {
+                self.0
+                    .albums()
+                    .set_status(actor, AlbumId(id), status, reason)
+                    .await
+            }
+            ContentKind::Song => {
+                self.0
+                    .songs()
+                    .set_status(actor, SongId(id), status, reason)
+                    .await
+            }
+        }
+    }
+}
+
+/// Kind of content that goes through the review queue.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ContentKind {
+    Album,
+    Song,
+}
+
+/// An item in the review queue.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Item {
+    /// ID of the album or the song.
+    pub id: i64,
+
+    /// Title of the item.
+    pub title: String,
+
+    /// Uploader of the item.
+    pub owner: Uid,
+
+    /// Time when the item was created.
+    pub created_at: DateTime<Utc>,
+}
+
+impl AppState {
+    /// Returns a manager of the review queue.
+    pub fn moderation(&self) -> Moderation<'_> {
+        Moderation(self)
+    }
+}
@@ -0,0 +1,305 @@
+//! OAuth 2.0 authorization server, implementing the authorization code grant with PKCE.
+//!
+//! Client apps are public clients, so they hold no secrets, and every authorization request must carry a PKCE
+//! challenge using the `S256` method. Tokens issued by the token endpoint are ordinary sessions created by
+//! [`Users::put_session`](crate::user::Users::put_session).
+
+use crate::{
+    AppState,
+    database::entity::{oauth_client, oauth_code},
+    error::Error,
+    user::{
+        Uid, User,
+        login::LoginResponse,
+        session::{Permissions, Session},
+    },
+    util::secret,
+};
+use base64::{Engine, prelude::BASE64_URL_SAFE_NO_PAD};
+use chrono::{DateTime, Utc};
+use rand::RngCore;
+use sea_orm::{ColumnTrait, EntityTrait, QueryFilter, QueryOrder, Set};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::time::Duration;
+use url::Url;
+
+/// Manager of OAuth client apps and authorizations.
+pub struct OAuth<'a>(&'a AppState);
+impl OAuth<'_> {
+    /// Maximum number of redirect URIs of a client app.
+    const MAX_REDIRECT_URIS: usize = 8;
+
+    /// Time before an authorization code expires.
+    const CODE_VALID_IN: Duration = Duration::from_secs(10 * 60);
+
+    /// Registers a client app owned by the user.
+    pub async fn register_client(
+        &self,
+        session: &Session,
+        register: RegisterClient,
+    ) -> Result<ClientId, Error> {
+        session.will_manage_sessions()?;
+        if register.name.trim().is_empty() {
+            return Err(Error::bad_request("client name must not be empty"));
+        }
+        if register.redirect_uris.is_empty()
+            || register.redirect_uris.len() > Self::MAX_REDIRECT_URIS
+        {
+            return Err(Error::bad_request("invalid number of redirect URIs"));
+        }
+        if register
+            .redirect_uris
+            .iter()
+            .any(|x| x.fragment().is_some())
+        {
+            return Err(Error::bad_request("redirect URIs must not have fragments"));
+        }
+
+        let mut data = [0u8; 24];
+        rand::rng().fill_bytes(&mut data);
+        let client_id = ClientId(BASE64_URL_SAFE_NO_PAD.encode(data));
+        oauth_client::Entity::insert(oauth_client::ActiveModel {
+            client_id: Set(client_id.0.clone()),
+            name: Set(register.name),
+            owner: Set(session.user.uid.0),
+            redirect_uris: Set(serde_json::to_value(&register.redirect_uris).unwrap()),
+            created_at: Set(Utc::now().naive_utc()),
+        })
+        .exec(&*self.0.database.conn)
+        .await?;
+
+        Ok(client_id)
+    }
+
+    /// Lists client apps owned by the user, from the newest.
+    pub async fn list_clients(&self, session: &Session) -> Result<Vec<Client>, Error> {
+        session.will_manage_sessions()?;
+
+        oauth_client::Entity::find()
+            .filter(oauth_client::Column::Owner.eq(session.user.uid.0))
+            .order_by_desc(oauth_client::Column::CreatedAt)
+            .all(&*self.0.database.conn)
+            .await?
+            .into_iter()
+            .map(Client::try_from)
+            .collect()
+    }
+
+    /// Removes a client app owned by the user. Sessions issued to the app are left intact.
+    pub async fn remove_client(
+        &self,
+        session: &Session,
+        client_id: &ClientId,
+    ) -> Result<(), Error> {
+        session.will_manage_sessions()?;
+
+        let result = oauth_client::Entity::delete_many()
+            .filter(oauth_client::Column::ClientId.eq(&client_id.0))
+            .filter(oauth_client::Column::Owner.eq(session.user.uid.0))
+            .exec(&*self.0.database.conn)
+            .await?;
+        if result.rows_affected == 0 {
+            return Err(Error::not_found());
+        }
+
+        Ok(())
+    }
+
+    /// Validates an authorization request, returning the client app and the requested permissions.
+    ///
+    /// Errors of this function must be shown to the user instead of being sent to the redirect URI, since the
+    /// redirect URI may not be trusted yet.
+    pub async fn validate(
+        &self,
+        request: &AuthorizeRequest,
+    ) -> Result<(Client, Permissions), Error> {
+        let client = oauth_client::Entity::find_by_id(&request.client_id.0)
+            .one(&*self.0.database.conn)
+            .await?
+            .ok_or_else(|| Error::bad_request("unknown client"))
+            .and_then(Client::try_from)?;
+        if !client.redirect_uris.contains(&request.redirect_uri) {
+            return Err(Error::bad_request("unregistered redirect URI"));
+        }
+        if request.response_type != "code" {
+            return Err(Error::bad_request("unsupported response type"));
+        }
+        if request.code_challenge_method != "S256" {
+            return Err(Error::bad_request("unsupported code challenge method"));
+        }
+        if BASE64_URL_SAFE_NO_PAD
+            .decode(&request.code_challenge)
+            .map_or(true, |x| x.len() != 32)
+        {
+            return Err(Error::bad_request("malformed code challenge"));
+        }
+
+        Ok((client, parse_scope(request.scope.as_deref().unwrap_or(""))?))
+    }
+
+    /// Issues an authorization code of the validated request to the user, who has logged in with `allowed`
+    /// permissions on the consent page.
+    ///
+    /// Only a hash of the code is stored, and the code is returned in the redirect URI. Requests for permissions
+    /// beyond `allowed` are refused, so that apps can't bypass restrictions of logins, like
+    /// [`MandatoryWheelTwoFactor`](crate::app_settings::MandatoryWheelTwoFactor).
+    pub async fn authorize(
+        &self,
+        user: &User,
+        allowed: Permissions,
+        request: &AuthorizeRequest,
+    ) -> Result<Url, Error> {
+        let (client, permissions) = self.validate(request).await?;
+        if !allowed.contains(permissions) {
+            return Err(Error::restricted_user());
+        }
+
+        let code = secret::generate();
+        oauth_code::Entity::insert(oauth_code::ActiveModel {
+            code_hash: Set(secret::hash(&code)),
+            client_id: Set(client.client_id.0),
+            uid: Set(user.uid.0),
+            redirect_uri: Set(request.redirect_uri.to_string()),
+            permissions: Set(permissions.bits() as _),
+            code_challenge: Set(request.code_challenge.clone()),
+            expires_at: Set(Utc::now().naive_utc() + Self::CODE_VALID_IN),
+        })
+        .exec(&*self.0.database.conn)
+        .await?;
+
+        let mut redirect = request.redirect_uri.clone();
+        redirect.query_pairs_mut().append_pair("code", &code);
+        if let Some(state) = &request.state {
+            redirect.query_pairs_mut().append_pair("state", state);
+        }
+        Ok(redirect)
+    }
+
+    /// Exchanges an authorization code for a session.
+    ///
+    /// A code can only be exchanged once, even if the exchange fails.
+    pub async fn exchange(&self, exchange: &Exchange) -> Result<LoginResponse, Error> {
+        let code_hash = secret::hash(&exchange.code);
+        let model = oauth_code::Entity::find_by_id(&code_hash)
+            .one(&*self.0.database.conn)
+            .await?
+            .ok_or_else(Error::invalid_grant)?;
+        let deleted = oauth_code::Entity::delete_by_id(&code_hash)
+            .exec(&*self.0.database.conn)
+            .await?;
+        if deleted.rows_affected == 0 {
+            // The code was exchanged concurrently.
+            return Err(Error::invalid_grant());
+        }
+
+        if model.expires_at.and_utc() < Utc::now()
+            || !(43..=128).contains(&exchange.code_verifier.len())
+            || model.client_id != exchange.client_id.0
+            || model.redirect_uri != exchange.redirect_uri.as_str()
+            || BASE64_URL_SAFE_NO_PAD.encode(Sha256::digest(&exchange.code_verifier))
+                != model.code_challenge
+        {
+            return Err(Error::invalid_grant());
+        }
+        let client = oauth_client::Entity::find_by_id(&model.client_id)
+            .one(&*self.0.database.conn)
+            .await?
+            .ok_or_else(Error::invalid_grant)?;
+
+        // Sessions of client apps can always be refreshed, since client apps can't ask the user to login again.
+        let permissions =
+            Permissions::from_bits_retain(model.permissions as _) | Permissions::REFRESH;
+        let uid = Uid(model.uid);
+        let (refresh_token, access_token) = self
+            .0
+            .users()
+            .put_session(uid, permissions, client.name)
+            .await?;
+        Ok(LoginResponse {
+            refresh_token,
+            access_token,
+            uid,
+        })
+    }
+}
+
+/// Representation of a client ID.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(transparent)]
+#[repr(transparent)]
+pub struct ClientId(pub String);
+
+/// Request of registering a client app.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RegisterClient {
+    /// Name of the app, shown to users on the consent page.
+    pub name: String,
+
+    /// URIs that the app may receive authorization codes at.
+    pub redirect_uris: Vec<Url>,
+}
+
+/// A registered client app.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Client {
+    pub client_id: ClientId,
+    pub name: String,
+    pub owner: Uid,
+    pub redirect_uris: Vec<Url>,
+    pub created_at: DateTime<Utc>,
+}
+impl TryFrom<oauth_client::Model> for Client {
+    type Error = Error;
+
+    fn try_from(model: oauth_client::Model) -> Result<Self, Error> {
+        Ok(Self {
+            client_id: ClientId(model.client_id),
+            name: model.name,
+            owner: Uid(model.owner),
+            redirect_uris: serde_json::from_value(model.redirect_uris).map_err(Error::internal)?,
+            created_at: model.created_at.and_utc(),
+        })
+    }
+}
+
+/// An authorization request, as defined in RFC 6749 and RFC 7636.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuthorizeRequest {
+    pub response_type: String,
+    pub client_id: ClientId,
+    pub redirect_uri: Url,
+
+    /// Space-separated names of requested [`Permissions`], like `UPLOAD_SONG CREATE_ALBUM`.
+    pub scope: Option<String>,
+    pub state: Option<String>,
+    pub code_challenge: String,
+    pub code_challenge_method: String,
+}
+
+/// Request of exchanging an authorization code.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Exchange {
+    pub code: String,
+    pub client_id: ClientId,
+    pub redirect_uri: Url,
+    pub code_verifier: String,
+}
+
+/// Parses a scope into permissions.
+fn parse_scope(scope: &str) -> Result<Permissions, Error> {
+    scope
+        .split_ascii_whitespace()
+        .try_fold(Permissions::empty(), |permissions, name| {
+            Permissions::from_name(name)
+                .map(|x| permissions | x)
+                .ok_or_else(|| Error::bad_request(format!("unknown scope `{name}`")))
+        })
+}
+
+impl AppState {
+    /// Returns a manager of OAuth client apps and authorizations.
+    pub fn oauth(&self) -> OAuth<'_> {
+        OAuth(self)
+    }
+}
@@ -0,0 +1,593 @@
+use crate::{
+    AppState,
+    database::entity::{playlist, playlist_entry, song},
+    error::Error,
+    moderation::ModerationStatus,
+    policy::{Condition::MatchUid, ObjectPolicy, PolicyItem, Subject},
+    song::{Profile as SongProfile, SongId},
+    user::Uid,
+    util::page::{MAX_SCAN_BATCHES, Page, PageQuery},
+};
+use axum::body::Bytes;
+use chrono::{DateTime, Utc};
+use sea_orm::{
+    ActiveModelTrait, ActiveValue::NotSet, ColumnTrait, EntityTrait, IntoActiveModel, QueryFilter,
+    QueryOrder, QuerySelect, Set, SqlErr, TransactionTrait, sea_query::Expr,
+};
+use serde::{Deserialize, Serialize};
+use std::{collections::HashSet, io::Cursor};
+use vinioss::ObjectKey;
+use vinutie::{
+    def_verify,
+    verify::{VerifyExt, verify_option},
+};
+
+/// Manager of the playlist set.
+///
+/// The owner of a playlist can always read and write it. Other users are checked against the playlist's read and
+/// write policies, so a playlist is edited collaboratively by allowing the collaborators in its write policy.
+pub struct Playlists<'a>(&'a AppState);
+impl Playlists<'_> {
+    /// Resolution of playlist covers in the HQ quality.
+    const COVER_HQ_RESOLUTION: (u32, u32) = (640, 640);
+
+    /// Maximum times that appending an entry is attempted when it races with concurrent appends.
+    const MAX_APPEND_ATTEMPTS: usize = 8;
+
+    /// Creates a new playlist.
+    pub async fn create(&self, owner: Uid, create: Create) -> Result<PlaylistId, Error> {
+        create.title.verify_by::<TitleLike>()?;
+        verify_option::<DescriptionLike, _>(create.description.as_deref())?;
+
+        let now = Utc::now().naive_utc();
+        Ok(PlaylistId(
+            playlist::Entity::insert(playlist::ActiveModel {
+                playlist_id: NotSet,
+                title: Set(create.title),
+                description: Set(create.description),
+                owner: Set(owner.0),
+                read_policy: Set(ObjectPolicy::allowed().json()),
+                write_policy: Set(write_policy(owner).json()),
+                cover: Set(None),
+                created_at: Set(now),
+                updated_at: Set(now),
+            })
+            .exec(&*self.0.database.conn)
+            .await?
+            .last_insert_id,
+        ))
+    }
+
+    /// Returns the profile of the playlist.
+    pub async fn profile(
+        &self,
+        playlist_id: PlaylistId,
+        subject: &Subject,
+    ) -> Result<Profile, Error> {
+        let model = self.find(playlist_id).await?;
+        model.check_read(subject)?;
+
+        Profile::try_from(model)
+    }
+
+    /// Edits the title and the description of the playlist.
+    pub async fn edit(
+        &self,
+        playlist_id: PlaylistId,
+        subject: &Subject,
+        edit: Create,
+    ) -> Result<(), Error> {
+        edit.title.verify_by::<TitleLike>()?;
+        verify_option::<DescriptionLike, _>(edit.description.as_deref())?;
+        let model = self.find(playlist_id).await?;
+        model.check_write(subject)?;
+
+        let mut active_model = model.into_active_model();
+        active_model.title = Set(edit.title);
+        active_model.description = Set(edit.description);
+        active_model.updated_at = Set(Utc::now().naive_utc());
+        active_model.update(&*self.0.database.conn).await?;
+
+        Ok(())
+    }
+
+    /// Replaces the read and write policies of the playlist. Only the owner can do this.
+    pub async fn set_policies(
+        &self,
+        playlist_id: PlaylistId,
+        subject: &Subject,
+        policies: Policies,
+    ) -> Result<(), Error> {
+        let model = self.find(playlist_id).await?;
+        model.check_owner(subject)?;
+
+        let mut active_model = model.into_active_model();
+        active_model.read_policy = Set(policies.read_policy.json());
+        active_model.write_policy = Set(policies.write_policy.json());
+        active_model.updated_at = Set(Utc::now().naive_utc());
+        active_model.update(&*self.0.database.conn).await?;
+
+        Ok(())
+    }
+
+    /// Removes the playlist with its entries. Only the owner can do this.
+    pub async fn remove(&self, playlist_id: PlaylistId, subject: &Subject) -> Result<(), Error> {
+        let model = self.find(playlist_id).await?;
+        model.check_owner(subject)?;
+
+        if let Some(cover) = &model.cover {
+            self.0.objects.remove(ObjectKey(cover.clone())).await?;
+        }
+        playlist::Entity::delete_by_id(model.playlist_id)
+            .exec(&*self.0.database.conn)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Returns the HQ cover of the playlist.
+    pub async fn cover_hq(
+        &self,
+        playlist_id: PlaylistId,
+        subject: &Subject,
+    ) -> Result<ObjectKey, Error> {
+        let model = self.find(playlist_id).await?;
+        model.check_read(subject)?;
+
+        model.cover.ok_or_else(Error::not_found).map(ObjectKey)
+    }
+
+    /// Sets the HQ cover of the playlist.
+    pub async fn set_cover(
+        &self,
+        playlist_id: PlaylistId,
+        subject: &Subject,
+        image: Bytes,
+    ) -> Result<(), Error> {
+        let model = self.find(playlist_id).await?;
+        model.check_write(subject)?;
+
+        let mut buffer = Cursor::new(crate::util::image::recompress(
+            image,
+            Self::COVER_HQ_RESOLUTION,
+        )?);
+
+        let object_key = ObjectKey(vinutie::random::filename("playlist_cover", "avif"));
+        self.0
+            .objects
+            .put_stream(object_key.clone(), &mut buffer)
+            .await?;
+
+        let before = model.cover.clone();
+        let mut active_model = model.into_active_model();
+        active_model.cover = Set(Some(object_key.0.clone()));
+        active_model.updated_at = Set(Utc::now().naive_utc());
+        if let Err(err) = active_model.update(&*self.0.database.conn).await {
+            self.remove_cover(&object_key.0).await;
+            return Err(err.into());
+        }
+        // The old cover is only removed once the playlist no longer refers to it.
+        if let Some(origin) = &before {
+            self.remove_cover(origin).await;
+        }
+
+        Ok(())
+    }
+
+    /// Removes a cover object, logging failures instead of returning them.
+    async fn remove_cover(&self, key: &str) {
+        if let Err(err) = self.0.objects.remove(ObjectKey(key.into())).await {
+            tracing::warn!("failed to remove playlist cover `{key}`: {err}");
+        }
+    }
+
+    /// Lists entries of the playlist in order, skipping songs that the subject cannot see or is not allowed
+    /// to listen to.
+    pub async fn entries(
+        &self,
+        playlist_id: PlaylistId,
+        subject: &Subject,
+        query: &PageQuery,
+    ) -> Result<Page<Entry>, Error> {
+        self.find(playlist_id).await?.check_read(subject)?;
+
+        let limit = query.limit();
+        let mut position = query.position()?;
+        let mut items = Vec::new();
+
+        for _ in 0..MAX_SCAN_BATCHES {
+            let mut select = playlist_entry::Entity::find()
+                .filter(playlist_entry::Column::Playlist.eq(playlist_id.0))
+                .order_by_asc(playlist_entry::Column::Position)
+                .limit(limit);
+            if let Some(position) = position {
+                select = select.filter(playlist_entry::Column::Position.gt(position));
+            }
+            let models = select
+                .find_also_related(song::Entity)
+                .filter(ModerationStatus::visible_condition(
+                    song::Column::Status,
+                    song::Column::Uploader,
+                    subject,
+                ))
+                .all(&*self.0.database.conn)
+                .await?;
+            let exhausted = (models.len() as u64) < limit;
+
+            for (entry, song) in models {
+                position = Some(entry.position);
+                let Some(song) = song else {
+                    continue;
+                };
+                if song.listenable_by(subject)? {
+                    items.push(Entry {
+                        song: song.into_profile()?,
+                        added_by: Uid(entry.added_by),
+                        added_at: entry.added_at.and_utc(),
+                    });
+                }
+                if items.len() as u64 == limit {
+                    return Ok(Page::new(items, position));
+                }
+            }
+
+            if exhausted {
+                return Ok(Page::new(items, None));
+            }
+        }
+
+        Ok(Page::new(items, position))
+    }
+
+    /// Appends a song to the end of the playlist.
+    ///
+    /// Only songs visible to the subject can be added.
+    pub async fn add_entry(
+        &self,
+        playlist_id: PlaylistId,
+        subject: &Subject,
+        added_by: Uid,
+        song_id: SongId,
+    ) -> Result<(), Error> {
+        let model = self.find(playlist_id).await?;
+        model.check_write(subject)?;
+        let song = song::Entity::find_by_id(song_id.0)
+            .one(&*self.0.database.conn)
+            .await?
+            .ok_or_else(Error::non_existent_song)?;
+        if !song.visible_to(subject)? {
+            return Err(Error::non_existent_song());
+        }
+
+        for _ in 0..Self::MAX_APPEND_ATTEMPTS {
+            if self.append(&model, added_by, song_id).await? {
+                return Ok(());
+            }
+        }
+
+        Err(Error::internal(
+            "failed to append to the playlist because of contention",
+        ))
+    }
+
+    /// Appends a song to the end of the playlist. Returns `false` if another entry has been appended concurrently, in
+    /// which case this should be retried.
+    async fn append(
+        &self,
+        model: &playlist::Model,
+        added_by: Uid,
+        song_id: SongId,
+    ) -> Result<bool, Error> {
+        let txn = self.0.database.conn.begin().await?;
+        let exists = playlist_entry::Entity::find_by_id((model.playlist_id, song_id.0))
+            .one(&txn)
+            .await?;
+        if exists.is_some() {
+            return Err(Error::playlist_entry_conflict());
+        }
+
+        let position = playlist_entry::Entity::find()
+            .filter(playlist_entry::Column::Playlist.eq(model.playlist_id))
+            .order_by_desc(playlist_entry::Column::Position)
+            .one(&txn)
+            .await?
+            .map_or(0, |x| x.position + 1);
+        let inserted = playlist_entry::Entity::insert(playlist_entry::ActiveModel {
+            playlist: Set(model.playlist_id),
+            song: Set(song_id.0),
+            position: Set(position),
+            added_by: Set(added_by.0),
+            added_at: Set(Utc::now().naive_utc()),
+        })
+        .exec(&txn)
+        .await;
+        if let Err(err) = inserted {
+            return match err.sql_err() {
+                // Either the position or the song has been taken by a concurrent append, which the retry tells apart.
+                Some(SqlErr::UniqueConstraintViolation(_)) => Ok(false),
+                Some(SqlErr::ForeignKeyConstraintViolation(_)) => Err(Error::non_existent_song()),
+                _ => Err(Error::internal(err)),
+            };
+        }
+        touch(&txn, model.clone()).await?;
+        txn.commit().await?;
+
+        Ok(true)
+    }
+
+    /// Removes a song from the playlist.
+    pub async fn remove_entry(
+        &self,
+        playlist_id: PlaylistId,
+        subject: &Subject,
+        song_id: SongId,
+    ) -> Result<(), Error> {
+        let model = self.find(playlist_id).await?;
+        model.check_write(subject)?;
+
+        let txn = self.0.database.conn.begin().await?;
+        let result = playlist_entry::Entity::delete_by_id((playlist_id.0, song_id.0))
+            .exec(&txn)
+            .await?;
+        if result.rows_affected == 0 {
+            return Err(Error::not_found());
+        }
+        touch(&txn, model).await?;
+        txn.commit().await?;
+
+        Ok(())
+    }
+
+    /// Reorders entries of the playlist.
+    ///
+    /// `order` must contain every song in the playlist exactly once, in the new order.
+    pub async fn reorder(
+        &self,
+        playlist_id: PlaylistId,
+        subject: &Subject,
+        order: Vec<SongId>,
+    ) -> Result<(), Error> {
+        let model = self.find(playlist_id).await?;
+        model.check_write(subject)?;
+
+        let txn = self.0.database.conn.begin().await?;
+        let current: HashSet<i64> = playlist_entry::Entity::find()
+            .filter(playlist_entry::Column::Playlist.eq(playlist_id.0))
+            .all(&txn)
+            .await?
+            .into_iter()
+            .map(|x| x.song)
+            .collect();
+        let requested: HashSet<i64> = order.iter().map(|x| x.0).collect();
+        if requested.len() != order.len() || requested != current {
+            return Err(Error::bad_request(
+                "the order must contain every song in the playlist exactly once",
+            ));
+        }
+
+        // Positions are unique, so entries are moved out of the way to negative positions first.
+        playlist_entry::Entity::update_many()
+            .col_expr(
+                playlist_entry::Column::Position,
+                Expr::value(-1).sub(Expr::col(playlist_entry::Column::Position)),
+            )
+            .filter(playlist_entry::Column::Playlist.eq(playlist_id.0))
+            .exec(&txn)
+            .await?;
+        for (position, song_id) in order.into_iter().enumerate() {
+            playlist_entry::ActiveModel {
+                playlist: Set(playlist_id.0),
+                song: Set(song_id.0),
+                position: Set(position as _),
+                ..Default::default()
+            }
+            .update(&txn)
+            .await?;
+        }
+        touch(&txn, model).await?;
+        txn.commit().await?;
+
+        Ok(())
+    }
+
+    /// Lists playlists owned by the user that the subject can read, from the newest.
+    pub async fn list_by_owner(
+        &self,
+        owner: Uid,
+        subject: &Subject,
+        query: &PageQuery,
+    ) -> Result<Page<Profile>, Error> {
+        let limit = query.limit();
+        let mut position = query.position()?;
+        let mut items = Vec::new();
+
+        for _ in 0..MAX_SCAN_BATCHES {
+            let mut select = playlist::Entity::find()
+                .filter(playlist::Column::Owner.eq(owner.0))
+                .order_by_desc(playlist::Column::PlaylistId)
+                .limit(limit);
+            if let Some(position) = position {
+                select = select.filter(playlist::Column::PlaylistId.lt(position));
+            }
+            let models = select.all(&*self.0.database.conn).await?;
+            let exhausted = (models.len() as u64) < limit;
+
+            for model in models {
+                position = Some(model.playlist_id);
+                if model.check_read(subject).is_ok() {
+                    items.push(Profile::try_from(model)?);
+                }
+                if items.len() as u64 == limit {
+                    return Ok(Page::new(items, position));
+                }
+            }
+
+            if exhausted {
+                return Ok(Page::new(items, None));
+            }
+        }
+
+        Ok(Page::new(items, position))
+    }
+
+    /// Returns the model of the playlist.
+    async fn find(&self, playlist_id: PlaylistId) -> Result<playlist::Model, Error> {
+        playlist::Entity::find_by_id(playlist_id.0)
+            .one(&*self.0.database.conn)
+            .await?
+            .ok_or_else(Error::not_found)
+    }
+}
+
+impl playlist::Model {
+    /// Returns `Ok` if the subject is the owner of the playlist.
+    fn check_owner(&self, subject: &Subject) -> Result<(), Error> {
+        if MatchUid(Uid(self.owner)).matches(subject) {
+            Ok(())
+        } else {
+            Err(Error::restricted_user())
+        }
+    }
+
+    /// Returns `Ok` if the subject is allowed to read the playlist.
+    fn check_read(&self, subject: &Subject) -> Result<(), Error> {
+        self.check_policy(&self.read_policy, subject)
+    }
+
+    /// Returns `Ok` if the subject is allowed to write the playlist.
+    fn check_write(&self, subject: &Subject) -> Result<(), Error> {
+        self.check_policy(&self.write_policy, subject)
+    }
+
+    fn check_policy(&self, policy: &serde_json::Value, subject: &Subject) -> Result<(), Error> {
+        if self.check_owner(subject).is_ok() {
+            return Ok(());
+        }
+
+        let policy: ObjectPolicy =
+            serde_json::from_value(policy.clone()).map_err(Error::internal)?;
+        if policy.denies(subject) {
+            return Err(Error::denied_by_policy(&policy.class));
+        }
+        Ok(())
+    }
+}
+
+/// Updates modification time of the playlist.
+async fn touch(conn: &impl sea_orm::ConnectionTrait, model: playlist::Model) -> Result<(), Error> {
+    let mut active_model = model.into_active_model();
+    active_model.updated_at = Set(Utc::now().naive_utc());
+    active_model.update(conn).await?;
+    Ok(())
+}
+
+/// Request of playlist creation or editing.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Create {
+    /// Title of the playlist.
+    pub title: String,
+
+    /// Description of the playlist.
+    pub description: Option<String>,
+}
+
+def_verify!(pub TitleLike<str>(err: Error = Error::bad_request("malformed playlist title")) = |x: &str| {
+    !x.trim().is_empty() && x.chars().count() <= 100 && !x.chars().any(char::is_control)
+});
+def_verify!(pub DescriptionLike<str>(err: Error = Error::bad_request("malformed playlist description")) = |x: &str| {
+    x.chars().count() <= 2000
+});
+
+/// Access policies of a playlist.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Policies {
+    /// Policy of reading the playlist and its entries.
+    pub read_policy: ObjectPolicy,
+
+    /// Policy of editing the playlist and its entries.
+    pub write_policy: ObjectPolicy,
+}
+
+/// Representation of a Playlist ID.
+///
+/// A Playlist ID identifies a playlist uniquely and cannot be changed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(transparent)]
+#[repr(transparent)]
+pub struct PlaylistId(pub i64);
+
+/// Profile of a playlist.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Profile {
+    /// ID of the playlist.
+    pub playlist_id: PlaylistId,
+
+    /// Title of the playlist.
+    pub title: String,
+
+    /// Description of the playlist.
+    pub description: Option<String>,
+
+    /// Owner of the playlist.
+    pub owner: Uid,
+
+    /// Read policy class of the playlist.
+    pub read_policy_class: String,
+
+    /// Write policy class of the playlist.
+    pub write_policy_class: String,
+
+    /// Time when the playlist was created.
+    pub created_at: DateTime<Utc>,
+
+    /// Time when the playlist or its entries were last modified.
+    pub updated_at: DateTime<Utc>,
+}
+impl TryFrom<playlist::Model> for Profile {
+    type Error = Error;
+
+    fn try_from(model: playlist::Model) -> Result<Self, Error> {
+        let class = |x: serde_json::Value| {
+            serde_json::from_value::<ObjectPolicy>(x)
+                .map(|x| x.class)
+                .map_err(Error::internal)
+        };
+        Ok(Self {
+            playlist_id: PlaylistId(model.playlist_id),
+            title: model.title,
+            description: model.description,
+            owner: Uid(model.owner),
+            read_policy_class: class(model.read_policy)?,
+            write_policy_class: class(model.write_policy)?,
+            created_at: model.created_at.and_utc(),
+            updated_at: model.updated_at.and_utc(),
+        })
+    }
+}
+
+/// An entry in a playlist.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Entry {
+    /// The song.
+    pub song: SongProfile,
+
+    /// User who added the song into the playlist.
+    pub added_by: Uid,
+
+    /// Time when the song was added into the playlist.
+    pub added_at: DateTime<Utc>,
+}
+
+fn write_policy(owner: Uid) -> ObjectPolicy {
+    ObjectPolicy {
+        class: "PlaylistPolicy".into(),
+        items: vec![PolicyItem::Allow(MatchUid(owner))],
+    }
+}
+
+impl AppState {
+    /// Returns a manager of the playlist set.
+    pub fn playlists(&self) -> Playlists<'_> {
+        Playlists(self)
+    }
+}
@@ -122,4 +122,9 @@ impl Subject {
             groups: vec![],
         }
     }
+
+    /// Returns the UID of the subject, or `None` if the subject is anonymous.
+    pub fn uid(&self) -> Option<Uid> {
+        (!self.anon).then_some(self.uid)
+    }
 }
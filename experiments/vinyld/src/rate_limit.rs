@@ -0,0 +1,216 @@
+//! In-memory rate limiting of requests and lockout of accounts after failed logins.
+//!
+//! Counters live in the memory of the process, so they are reset on restart and not shared between processes.
+
+use crate::error::Error;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+/// Number of counters kept before expired ones are pruned.
+const PRUNE_THRESHOLD: usize = 4096;
+
+/// Limiter of requests and failed logins.
+#[derive(Debug)]
+pub struct RateLimiter {
+    windows: Mutex<HashMap<String, Window>>,
+    failures: Mutex<HashMap<String, Failures>>,
+
+    /// Number of reverse proxies in front of the server, each appending to the `X-Forwarded-For` header. The client
+    /// address is taken from the header only if it is non-zero.
+    pub trusted_proxies: usize,
+}
+impl RateLimiter {
+    pub fn new(trusted_proxies: usize) -> Self {
+        Self {
+            windows: Mutex::new(HashMap::new()),
+            failures: Mutex::new(HashMap::new()),
+            trusted_proxies,
+        }
+    }
+
+    /// Counts a request against the budget of every key, failing if the budget of any key has been used up in the
+    /// current window.
+    ///
+    /// Keys are only charged if all of them are within their budgets, so that rejected requests don't use up budgets
+    /// of the other keys.
+    pub fn hit(&self, keys: &[String], budget: &Budget) -> Result<(), Error> {
+        let now = Instant::now();
+        let mut windows = self.windows.lock().unwrap();
+        if windows.len() >= PRUNE_THRESHOLD {
+            windows.retain(|_, x| x.ends_at > now);
+        }
+
+        for key in keys {
+            if let Some(window) = windows.get(key)
+                && window.ends_at > now
+                && window.requests >= budget.requests
+            {
+                return Err(Error::too_many_requests(secs_until(now, window.ends_at)));
+            }
+        }
+
+        for key in keys {
+            windows
+                .entry(key.clone())
+                .and_modify(|x| {
+                    if x.ends_at <= now {
+                        *x = Window::new(now, budget);
+                    }
+                })
+                .or_insert_with(|| Window::new(now, budget))
+                .requests += 1;
+        }
+
+        Ok(())
+    }
+
+    /// Fails if logins with the key are locked out.
+    pub fn check_lockout(&self, key: &str) -> Result<(), Error> {
+        let now = Instant::now();
+        match self.failures.lock().unwrap().get(key) {
+            Some(Failures {
+                locked_until: Some(x),
+                ..
+            }) if *x > now => Err(Error::login_locked(secs_until(now, *x))),
+            _ => Ok(()),
+        }
+    }
+
+    /// Records a failed login with the key, locking out further logins if there have been too many.
+    pub fn record_failure(&self, key: &str, lockout: &Lockout) {
+        let now = Instant::now();
+        let mut failures = self.failures.lock().unwrap();
+        if failures.len() >= PRUNE_THRESHOLD {
+            failures.retain(|_, x| x.expires_at() > now);
+        }
+
+        let entry = failures
+            .entry(key.to_string())
+            .and_modify(|x| {
+                if x.expires_at() <= now {
+                    *x = Failures::new(now, lockout);
+                }
+            })
+            .or_insert_with(|| Failures::new(now, lockout));
+        entry.count += 1;
+        if entry.count >= lockout.max_failures {
+            entry.locked_until = Some(now + Duration::from_secs(lockout.duration_secs));
+            entry.count = 0;
+        }
+    }
+
+    /// Forgets failed logins with the key, after a successful one.
+    pub fn clear_failures(&self, key: &str) {
+        self.failures.lock().unwrap().remove(key);
+    }
+}
+
+/// Counter of requests in a fixed window.
+#[derive(Debug)]
+struct Window {
+    ends_at: Instant,
+    requests: u32,
+}
+impl Window {
+    fn new(now: Instant, budget: &Budget) -> Self {
+        Self {
+            ends_at: now + Duration::from_secs(budget.window_secs),
+            requests: 0,
+        }
+    }
+}
+
+/// Counter of failed logins.
+#[derive(Debug)]
+struct Failures {
+    /// End of the window in which failures are counted.
+    ends_at: Instant,
+    count: u32,
+    locked_until: Option<Instant>,
+}
+impl Failures {
+    fn new(now: Instant, lockout: &Lockout) -> Self {
+        Self {
+            ends_at: now + Duration::from_secs(lockout.window_secs),
+            count: 0,
+            locked_until: None,
+        }
+    }
+
+    /// Returns when the counter is of no use anymore.
+    fn expires_at(&self) -> Instant {
+        self.locked_until
+            .map_or(self.ends_at, |x| x.max(self.ends_at))
+    }
+}
+
+/// Returns seconds from now until the instant, rounded up.
+fn secs_until(now: Instant, instant: Instant) -> u64 {
+    (instant - now).as_millis().div_ceil(1000) as u64
+}
+
+/// Budgets of rate-limited routes, and the lockout policy of failed logins.
+///
+/// Each budget is counted separately for every client address, and for every username and token that the requests
+/// carry.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RateLimits {
+    /// Budget of logging in, including completing logins with second factors.
+    pub login: Budget,
+    pub register: Budget,
+    pub refresh_token: Budget,
+
+    /// Budget of sending verification codes and resetting passwords.
+    pub send_code: Budget,
+    pub lockout: Lockout,
+}
+impl Default for RateLimits {
+    fn default() -> Self {
+        Self {
+            login: Budget {
+                requests: 10,
+                window_secs: 60,
+            },
+            register: Budget {
+                requests: 5,
+                window_secs: 60 * 60,
+            },
+            refresh_token: Budget {
+                requests: 30,
+                window_secs: 60,
+            },
+            send_code: Budget {
+                requests: 5,
+                window_secs: 10 * 60,
+            },
+            lockout: Lockout {
+                max_failures: 5,
+                window_secs: 15 * 60,
+                duration_secs: 15 * 60,
+            },
+        }
+    }
+}
+
+/// Number of requests allowed in a window.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Budget {
+    pub requests: u32,
+    pub window_secs: u64,
+}
+
+/// Policy of locking out accounts after failed logins.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Lockout {
+    /// Number of failed logins in the window after which the account is locked out.
+    pub max_failures: u32,
+    pub window_secs: u64,
+
+    /// Time for which the account stays locked out.
+    pub duration_secs: u64,
+}
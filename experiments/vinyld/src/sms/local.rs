@@ -0,0 +1,59 @@
+//! SMS providers for testing and local development.
+
+use super::Provider;
+use async_trait::async_trait;
+use chrono::Utc;
+use std::path::PathBuf;
+use tokio::{fs::OpenOptions, io::AsyncWriteExt, sync::Mutex};
+
+/// Provider that prints messages to the standard output.
+#[derive(Debug)]
+pub struct Stdout;
+#[async_trait]
+impl Provider for Stdout {
+    async fn send(&self, to: &str, text: &str) -> anyhow::Result<()> {
+        println!("SMS to {to}: {text}");
+        Ok(())
+    }
+}
+
+/// Provider that appends messages to a log file, one per line.
+#[derive(Debug)]
+pub struct LogFile {
+    path: PathBuf,
+
+    /// Serializes writes, so that lines of concurrent messages don't interleave.
+    lock: Mutex<()>,
+}
+impl LogFile {
+    pub fn new(path: &str) -> anyhow::Result<Self> {
+        let path = PathBuf::from(path);
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        Ok(Self {
+            path,
+            lock: Mutex::new(()),
+        })
+    }
+}
+#[async_trait]
+impl Provider for LogFile {
+    async fn send(&self, to: &str, text: &str) -> anyhow::Result<()> {
+        let line = format!(
+            "{}\t{to}\t{}\n",
+            Utc::now().to_rfc3339(),
+            text.replace(['\r', '\n'], " "),
+        );
+
+        let _guard = self.lock.lock().await;
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .await?
+            .write_all(line.as_bytes())
+            .await?;
+        Ok(())
+    }
+}
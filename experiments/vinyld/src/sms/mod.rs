@@ -0,0 +1,51 @@
+//! Outgoing text messages.
+
+mod local;
+mod webhook;
+
+use crate::error::Error;
+use anyhow::anyhow;
+use async_trait::async_trait;
+use std::fmt::Debug;
+
+/// An SMS provider.
+#[async_trait]
+pub trait Provider: Debug + Send + Sync {
+    /// Sends a text message to the phone number, which is in E.164 format.
+    async fn send(&self, to: &str, text: &str) -> anyhow::Result<()>;
+}
+
+/// Sender of text messages through a provider.
+#[derive(Debug)]
+pub struct Texter(Box<dyn Provider>);
+impl Texter {
+    /// Creates a texter sending through the provider.
+    ///
+    /// The provider is one of:
+    ///  - `http://...` or `https://...`, posting messages as JSON objects like `{"to": "+1...", "text": "..."}` to a
+    ///    gateway of an SMS service;
+    ///  - `file:///path/to/file`, appending messages to the file, one per line;
+    ///  - `stdout`, printing messages to the standard output.
+    ///
+    /// The latter two are meant for testing and local development.
+    pub fn new(provider: &str) -> anyhow::Result<Self> {
+        let provider: Box<dyn Provider> = match provider {
+            "stdout" => Box::new(local::Stdout),
+            x if x.starts_with("file://") => Box::new(local::LogFile::new(&x["file://".len()..])?),
+            x if x.starts_with("http://") || x.starts_with("https://") => {
+                Box::new(webhook::Webhook::new(x.parse()?))
+            }
+            x => return Err(anyhow!("unknown SMS provider `{x}`")),
+        };
+
+        Ok(Self(provider))
+    }
+
+    /// Sends a text message.
+    pub async fn send(&self, to: &str, text: &str) -> Result<(), Error> {
+        self.0.send(to, text).await.map_err(|err| {
+            tracing::warn!("failed to send text message: {err}");
+            Error::internal(err)
+        })
+    }
+}
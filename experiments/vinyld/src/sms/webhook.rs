@@ -0,0 +1,39 @@
+//! SMS provider posting messages to an HTTP gateway.
+
+use super::Provider;
+use anyhow::anyhow;
+use async_trait::async_trait;
+use reqwest::header::CONTENT_TYPE;
+use url::Url;
+
+/// Provider that posts messages to an HTTP gateway, which delivers them through an SMS service.
+#[derive(Debug)]
+pub struct Webhook {
+    client: reqwest::Client,
+    url: Url,
+}
+impl Webhook {
+    pub fn new(url: Url) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            url,
+        }
+    }
+}
+#[async_trait]
+impl Provider for Webhook {
+    async fn send(&self, to: &str, text: &str) -> anyhow::Result<()> {
+        let body = serde_json::json! {{"to": to, "text": text}};
+        let response = self
+            .client
+            .post(self.url.clone())
+            .header(CONTENT_TYPE, "application/json")
+            .body(body.to_string())
+            .send()
+            .await?;
+        if !response.status().is_success() {
+            return Err(anyhow!("SMS gateway responded {}", response.status()));
+        }
+        Ok(())
+    }
+}
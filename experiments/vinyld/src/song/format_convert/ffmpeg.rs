@@ -0,0 +1,110 @@
+//! Backend based on the FFMpeg command line utility.
+
+use super::{Codec, Container, Conversion, MetadataOps, tags};
+use crate::local_data::temp::TempFile;
+use anyhow::anyhow;
+use std::process::Stdio;
+use tokio::io::{AsyncRead, AsyncWrite, AsyncWriteExt};
+
+pub async fn convert<
+    I: AsyncRead + Send + Unpin + 'static,
+    O: AsyncWrite + Send + Unpin + 'static,
+>(
+    mut conversion: Conversion<I>,
+    mut out: O,
+) -> anyhow::Result<()> {
+    let mut command = tokio::process::Command::new("ffmpeg");
+
+    command
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::inherit())
+        .arg("-loglevel")
+        .arg("fatal")
+        .arg("-i")
+        .arg("pipe:");
+
+    // The metadata is passed in a file, since a cover may exceed the length limit of command line arguments. The
+    // file must live until the process exits.
+    let _metadata_file = match &conversion.out_metadata {
+        MetadataOps::Set(metadata) => {
+            let file = TempFile::new().await?;
+            tokio::fs::write(file.path(), tags::ffmetadata(metadata)).await?;
+            command
+                .arg("-f")
+                .arg("ffmetadata")
+                .arg("-i")
+                .arg(file.path())
+                .arg("-map")
+                .arg("0:a");
+            Some(file)
+        }
+        _ => None,
+    };
+
+    match conversion.out_codec {
+        Some(Codec::Flac) => {
+            command.arg("-acodec").arg("flac");
+        }
+        Some(Codec::Opus) => {
+            command.arg("-acodec").arg("libopus");
+        }
+        None => {}
+    }
+
+    match conversion.out_container {
+        Some(Container::Flac) => {
+            command.arg("-f").arg("flac");
+        }
+        Some(Container::Ogg) => {
+            command.arg("-f").arg("ogg");
+        }
+        None => {}
+    }
+
+    match conversion.out_metadata {
+        MetadataOps::Keep => {}
+        MetadataOps::Set(_) => {
+            command.arg("-map_metadata").arg("1").arg("-vn");
+        }
+    }
+
+    if let Some(quality) = conversion.out_quality {
+        command
+            .arg("-b:a")
+            .arg(format!("{}k", quality.bitrate() / 1000));
+    }
+
+    command.arg("-");
+
+    tracing::trace!("Running `ffmpeg` CLI with command: {command:?}");
+
+    let mut child = command
+        .spawn()
+        .map_err(|err| anyhow!("failed to spawn ffmpeg process: {err}"))?;
+
+    let mut stdin = child.stdin.take().unwrap();
+    let mut stdout = child.stdout.take().unwrap();
+
+    tokio::spawn(async move { tokio::io::copy(&mut conversion.input, &mut stdin).await });
+    let output = tokio::spawn(async move {
+        tokio::io::copy(&mut stdout, &mut out).await?;
+        out.flush().await
+    });
+    let exit_status = child
+        .wait()
+        .await
+        .map_err(|err| anyhow!("failed to probe ffmpeg process: {err}"))?;
+    output
+        .await?
+        .map_err(|err| anyhow!("failed to write converted audio: {err}"))?;
+
+    if exit_status.success() {
+        Ok(())
+    } else {
+        Err(anyhow!(
+            "conversion failed: ffmpeg exited with code {}",
+            exit_status.code().unwrap_or(101)
+        ))
+    }
+}
@@ -0,0 +1,134 @@
+//! Audio format converter.
+//!
+//! Two backends are available: one based on the FFMpeg command line utility, and a native one which decodes with
+//! [Symphonia](https://github.com/pdeljanov/Symphonia) and encodes in-process. Both backends produce the same container and
+//! codec for a conversion.
+
+mod ffmpeg;
+mod native;
+mod tags;
+
+use tokio::io::{AsyncRead, AsyncWrite};
+
+#[derive(Debug, Clone, Copy)]
+pub enum Container {
+    Flac,
+    Ogg,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum Codec {
+    Flac,
+    Opus,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum LossyQuality {
+    High,
+    Medium,
+    Low,
+}
+impl LossyQuality {
+    /// Returns the target bitrate of the quality, in bits per second.
+    pub fn bitrate(&self) -> u32 {
+        match self {
+            Self::High => 320_000,
+            Self::Medium => 256_000,
+            Self::Low => 128_000,
+        }
+    }
+}
+
+/// Backend that performs conversions.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Backend {
+    /// The FFMpeg command line utility.
+    #[default]
+    Ffmpeg,
+
+    /// The native backend, based on Symphonia.
+    Native,
+}
+
+/// Tags to embed into the output.
+#[derive(Debug, Clone)]
+pub struct Metadata {
+    pub title: String,
+    pub album: String,
+    pub author: String,
+    pub cover: Option<Cover>,
+}
+
+/// Cover art to embed into the output.
+#[derive(Debug, Clone)]
+pub struct Cover {
+    /// MIME type of the image, e.g. `image/jpeg`.
+    pub mime: String,
+
+    /// Encoded image data.
+    pub data: Vec<u8>,
+}
+
+#[derive(Debug, Clone)]
+enum MetadataOps {
+    Keep,
+    Set(Metadata),
+}
+
+#[derive(Debug)]
+pub struct Conversion<I> {
+    input: I,
+    backend: Backend,
+    out_container: Option<Container>,
+    out_codec: Option<Codec>,
+    out_quality: Option<LossyQuality>,
+    out_metadata: MetadataOps,
+}
+impl<I: AsyncRead + Send + Unpin + 'static> Conversion<I> {
+    pub fn new(input: I) -> Self {
+        Self {
+            input,
+            backend: Backend::default(),
+            out_container: None,
+            out_codec: None,
+            out_quality: None,
+            out_metadata: MetadataOps::Keep,
+        }
+    }
+
+    pub fn backend(mut self, backend: Backend) -> Self {
+        self.backend = backend;
+        self
+    }
+
+    pub fn container(mut self, container: Container) -> Self {
+        self.out_container = Some(container);
+        self
+    }
+
+    pub fn codec(mut self, codec: Codec) -> Self {
+        self.out_codec = Some(codec);
+        self
+    }
+
+    /// Replaces metadata of the input with given tags.
+    pub fn metadata(mut self, metadata: Metadata) -> Self {
+        self.out_metadata = MetadataOps::Set(metadata);
+        self
+    }
+
+    pub fn lossy_quality(mut self, quality: LossyQuality) -> Self {
+        self.out_quality = Some(quality);
+        self
+    }
+
+    pub async fn perform<O: AsyncWrite + Send + Unpin + 'static>(
+        self,
+        out: O,
+    ) -> anyhow::Result<()> {
+        match self.backend {
+            Backend::Ffmpeg => ffmpeg::convert(self, out).await,
+            Backend::Native => native::convert(self, out).await,
+        }
+    }
+}
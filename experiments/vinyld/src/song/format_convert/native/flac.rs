@@ -0,0 +1,469 @@
+//! FLAC encoder.
+//!
+//! This is a simple encoder which uses fixed blocking, independent channels and fixed linear predictors. The output is
+//! not as small as the reference encoder's, but is still losslessly compressed and can be decoded by any player.
+
+use super::PcmSource;
+use crate::song::format_convert::{Metadata, tags};
+use anyhow::anyhow;
+use std::io::Write;
+
+/// Number of inter-channel samples in a block.
+const BLOCK_SIZE: usize = 4096;
+
+/// Maximum order of fixed predictors.
+const MAX_FIXED_ORDER: usize = 4;
+
+/// Maximum order of Rice partitions.
+const MAX_PARTITION_ORDER: u32 = 6;
+
+/// Bit depth used if the source doesn't have one, e.g. if it's a lossy stream.
+const DEFAULT_BITS_PER_SAMPLE: u32 = 16;
+
+/// Type of the `STREAMINFO` metadata block.
+const STREAMINFO: u8 = 0;
+
+/// Type of the `VORBIS_COMMENT` metadata block.
+const VORBIS_COMMENT: u8 = 4;
+
+/// Encodes the source into a FLAC stream, tagged with given metadata.
+pub fn encode(
+    source: &mut PcmSource,
+    metadata: Option<&Metadata>,
+    out: &mut impl Write,
+) -> anyhow::Result<()> {
+    let channels = source.channels();
+    if !(1..=8).contains(&channels) {
+        return Err(anyhow!("FLAC does not support {channels} channels"));
+    }
+    let bits = source
+        .bits_per_sample()
+        .unwrap_or(DEFAULT_BITS_PER_SAMPLE)
+        .clamp(8, 24);
+
+    let mut blocks = vec![(
+        STREAMINFO,
+        stream_info(source.sample_rate(), channels, bits),
+    )];
+    if let Some(metadata) = metadata {
+        blocks.push((
+            VORBIS_COMMENT,
+            tags::vorbis_comment(&metadata.comments_with_picture()),
+        ));
+    }
+    out.write_all(b"fLaC")?;
+    for (i, (kind, body)) in blocks.iter().enumerate() {
+        write_metadata_block(out, *kind, i == blocks.len() - 1, body)?;
+    }
+
+    let mut decoded = Vec::new();
+    let mut pending = Vec::new();
+    let mut frame_number = 0;
+    while source.next::<i32>(&mut decoded)? {
+        pending.extend(decoded.drain(..).map(|x| x >> (32 - bits)));
+        while pending.len() >= BLOCK_SIZE * channels {
+            write_frame(
+                out,
+                frame_number,
+                &pending[..BLOCK_SIZE * channels],
+                channels,
+                bits,
+            )?;
+            pending.drain(..BLOCK_SIZE * channels);
+            frame_number += 1;
+        }
+    }
+    if !pending.is_empty() {
+        write_frame(out, frame_number, &pending, channels, bits)?;
+    }
+
+    Ok(())
+}
+
+/// Writes a metadata block.
+fn write_metadata_block(
+    out: &mut impl Write,
+    kind: u8,
+    last: bool,
+    body: &[u8],
+) -> anyhow::Result<()> {
+    if body.len() >= 1 << 24 {
+        return Err(anyhow!("metadata block is too large"));
+    }
+
+    let mut w = BitWriter::default();
+    w.write(last as _, 1);
+    w.write(kind as _, 7);
+    w.write(body.len() as _, 24);
+
+    out.write_all(&w.bytes)?;
+    out.write_all(body)?;
+    Ok(())
+}
+
+/// Returns body of the `STREAMINFO` metadata block.
+fn stream_info(sample_rate: u32, channels: usize, bits: u32) -> Vec<u8> {
+    let mut w = BitWriter::default();
+    w.write(BLOCK_SIZE as _, 16);
+    w.write(BLOCK_SIZE as _, 16);
+    w.write(0, 24); // unknown minimum frame size
+    w.write(0, 24); // unknown maximum frame size
+    w.write(sample_rate as _, 20);
+    w.write(channels as u64 - 1, 3);
+    w.write(bits as u64 - 1, 5);
+    w.write(0, 36); // unknown total samples
+    for _ in 0..4 {
+        w.write(0, 32); // unknown MD5 signature
+    }
+    w.bytes
+}
+
+/// Writes a frame of interleaved samples.
+fn write_frame(
+    out: &mut impl Write,
+    frame_number: u64,
+    samples: &[i32],
+    channels: usize,
+    bits: u32,
+) -> anyhow::Result<()> {
+    let block_size = samples.len() / channels;
+
+    let mut w = BitWriter::default();
+    w.write(0b11111111111110, 14);
+    w.write(0, 1); // reserved
+    w.write(0, 1); // fixed blocking
+    w.write(0b0111, 4); // block size in 16 bits at the end of header
+    w.write(0b0000, 4); // sample rate from STREAMINFO
+    w.write(channels as u64 - 1, 4); // independent channels
+    w.write(0b000, 3); // bit depth from STREAMINFO
+    w.write(0, 1); // reserved
+    w.write_utf8(frame_number);
+    w.write(block_size as u64 - 1, 16);
+    w.write(crc8(&w.bytes) as _, 8);
+
+    for channel in 0..channels {
+        let channel_samples: Vec<i64> = samples
+            .iter()
+            .skip(channel)
+            .step_by(channels)
+            .map(|&x| x as i64)
+            .collect();
+        write_subframe(&mut w, &channel_samples, bits);
+    }
+
+    w.align();
+    w.write(crc16(&w.bytes) as _, 16);
+
+    out.write_all(&w.bytes)?;
+    Ok(())
+}
+
+/// Writes a subframe, choosing the smallest encoding between verbatim and fixed predictors.
+fn write_subframe(w: &mut BitWriter, samples: &[i64], bits: u32) {
+    let verbatim_cost = samples.len() as u64 * bits as u64;
+
+    let mut best: Option<(usize, Vec<u64>, RicePlan)> = None;
+    for order in 0..=MAX_FIXED_ORDER.min(samples.len() - 1) {
+        let residual = fixed_residual(samples, order);
+        let Some(plan) = RicePlan::new(&residual, order) else {
+            continue;
+        };
+        let cost = order as u64 * bits as u64 + plan.cost;
+        if best
+            .as_ref()
+            .is_none_or(|(o, _, p)| cost < *o as u64 * bits as u64 + p.cost)
+        {
+            best = Some((order, residual, plan));
+        }
+    }
+
+    w.write(0, 1); // padding
+    match best {
+        Some((order, residual, plan)) if order as u64 * bits as u64 + plan.cost < verbatim_cost => {
+            w.write(0b001000 | order as u64, 6);
+            w.write(0, 1); // no wasted bits
+            for &sample in &samples[..order] {
+                w.write_signed(sample, bits);
+            }
+            plan.write(w, &residual, order);
+        }
+        _ => {
+            w.write(0b000001, 6);
+            w.write(0, 1); // no wasted bits
+            for &sample in samples {
+                w.write_signed(sample, bits);
+            }
+        }
+    }
+}
+
+/// Returns the zigzag-encoded residual of the fixed predictor in given order. The first `order` items are unused.
+fn fixed_residual(samples: &[i64], order: usize) -> Vec<u64> {
+    let mut residual = vec![0; samples.len()];
+    for i in order..samples.len() {
+        let s = |n: usize| samples[i - n];
+        let r = match order {
+            0 => s(0),
+            1 => s(0) - s(1),
+            2 => s(0) - 2 * s(1) + s(2),
+            3 => s(0) - 3 * s(1) + 3 * s(2) - s(3),
+            4 => s(0) - 4 * s(1) + 6 * s(2) - 4 * s(3) + s(4),
+            _ => unreachable!(),
+        };
+        residual[i] = ((r << 1) ^ (r >> 63)) as u64;
+    }
+    residual
+}
+
+/// Plan of Rice coding a residual.
+struct RicePlan {
+    partition_order: u32,
+    params: Vec<u32>,
+    cost: u64,
+}
+impl RicePlan {
+    /// Chooses the partition order and Rice parameters with the estimated smallest output.
+    fn new(residual: &[u64], order: usize) -> Option<Self> {
+        let mut best: Option<Self> = None;
+
+        for partition_order in 0..=MAX_PARTITION_ORDER {
+            let partitions = 1 << partition_order;
+            if !residual.len().is_multiple_of(partitions) || residual.len() / partitions <= order {
+                break;
+            }
+            let len = residual.len() / partitions;
+
+            let mut params = Vec::with_capacity(partitions);
+            let mut cost = 0;
+            for i in 0..partitions {
+                let start = if i == 0 { order } else { i * len };
+                let part = &residual[start..(i + 1) * len];
+                let sum: u64 = part.iter().sum();
+                let (param, part_cost) = (0..=30)
+                    .map(|k| (k, part.len() as u64 * (k as u64 + 1) + (sum >> k)))
+                    .min_by_key(|x| x.1)
+                    .unwrap();
+                params.push(param);
+                cost += part_cost;
+            }
+            let param_bits = if params.iter().any(|&x| x > 14) { 5 } else { 4 };
+            cost += 6 + partitions as u64 * param_bits;
+
+            if best.as_ref().is_none_or(|x| cost < x.cost) {
+                best = Some(Self {
+                    partition_order,
+                    params,
+                    cost,
+                });
+            }
+        }
+
+        best
+    }
+
+    /// Writes the residual using this plan.
+    fn write(&self, w: &mut BitWriter, residual: &[u64], order: usize) {
+        let wide = self.params.iter().any(|&x| x > 14);
+        let len = residual.len() >> self.partition_order;
+
+        w.write(if wide { 0b01 } else { 0b00 }, 2);
+        w.write(self.partition_order as _, 4);
+        for (i, &param) in self.params.iter().enumerate() {
+            w.write(param as _, if wide { 5 } else { 4 });
+            let start = if i == 0 { order } else { i * len };
+            for &value in &residual[start..(i + 1) * len] {
+                w.write_unary(value >> param);
+                w.write(value, param);
+            }
+        }
+    }
+}
+
+/// A big-endian bit writer.
+#[derive(Default)]
+struct BitWriter {
+    bytes: Vec<u8>,
+    acc: u64,
+    len: u32,
+}
+impl BitWriter {
+    /// Writes the lowest `n` bits of the value.
+    fn write(&mut self, value: u64, n: u32) {
+        if n > 32 {
+            self.write(value >> 32, n - 32);
+            self.write(value & 0xFFFF_FFFF, 32);
+            return;
+        }
+        if n == 0 {
+            return;
+        }
+
+        self.acc = (self.acc << n) | (value & ((1 << n) - 1));
+        self.len += n;
+        while self.len >= 8 {
+            self.len -= 8;
+            self.bytes.push((self.acc >> self.len) as u8);
+        }
+        self.acc &= (1 << self.len) - 1;
+    }
+
+    /// Writes a signed value in `n` bits of two's complement.
+    fn write_signed(&mut self, value: i64, n: u32) {
+        self.write(value as u64, n);
+    }
+
+    /// Writes a value in unary, i.e. `value` zeros followed by a one.
+    fn write_unary(&mut self, mut value: u64) {
+        while value >= 32 {
+            self.write(0, 32);
+            value -= 32;
+        }
+        self.write(1, value as u32 + 1);
+    }
+
+    /// Writes a value in the UTF-8-like variable length coding used by frame headers.
+    fn write_utf8(&mut self, value: u64) {
+        if value < 0x80 {
+            self.write(value, 8);
+            return;
+        }
+
+        let n = (2..=7u32)
+            .find(|&n| value < 1 << (if n == 7 { 36 } else { 5 * n + 1 }))
+            .expect("frame number too large");
+        let prefix = (0xFF00u64 >> n) & 0xFF;
+        self.write(prefix | (value >> (6 * (n - 1))), 8);
+        for i in (0..n - 1).rev() {
+            self.write(0x80 | ((value >> (6 * i)) & 0x3F), 8);
+        }
+    }
+
+    /// Pads zeros to the byte boundary.
+    fn align(&mut self) {
+        if self.len != 0 {
+            self.write(0, 8 - self.len);
+        }
+    }
+}
+
+/// Calculates CRC-8 with polynomial `0x07`.
+fn crc8(data: &[u8]) -> u8 {
+    data.iter().fold(0u8, |mut crc, &byte| {
+        crc ^= byte;
+        for _ in 0..8 {
+            crc = if crc & 0x80 != 0 {
+                (crc << 1) ^ 0x07
+            } else {
+                crc << 1
+            };
+        }
+        crc
+    })
+}
+
+/// Calculates CRC-16 with polynomial `0x8005`.
+fn crc16(data: &[u8]) -> u16 {
+    data.iter().fold(0u16, |mut crc, &byte| {
+        crc ^= (byte as u16) << 8;
+        for _ in 0..8 {
+            crc = if crc & 0x8000 != 0 {
+                (crc << 1) ^ 0x8005
+            } else {
+                crc << 1
+            };
+        }
+        crc
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+    use symphonia::core::codecs::CODEC_TYPE_FLAC;
+
+    /// Returns pseudo-random noise mixed with a ramp, which exercises every predictor order.
+    fn noise(bits: u32, len: usize) -> Vec<i32> {
+        let mut state = 0x2545f491u32;
+        (0..len)
+            .map(|n| {
+                state ^= state << 13;
+                state ^= state >> 17;
+                state ^= state << 5;
+                let noise = (state >> (32 - bits + 4)) as i32 - (1 << (bits - 5));
+                noise + ((n * 37) % (1 << (bits - 2))) as i32
+            })
+            .collect()
+    }
+
+    /// Returns a PCM WAV file of the interleaved samples.
+    fn wav(sample_rate: u32, channels: u16, bits: u16, samples: &[i32]) -> Vec<u8> {
+        let data = samples
+            .iter()
+            .flat_map(|x| x.to_le_bytes()[..bits as usize / 8].to_vec())
+            .collect::<Vec<_>>();
+        let block_align = channels * bits / 8;
+
+        let mut wav = b"RIFF".to_vec();
+        wav.extend_from_slice(&(36 + data.len() as u32).to_le_bytes());
+        wav.extend_from_slice(b"WAVEfmt ");
+        wav.extend_from_slice(&16u32.to_le_bytes());
+        wav.extend_from_slice(&1u16.to_le_bytes());
+        wav.extend_from_slice(&channels.to_le_bytes());
+        wav.extend_from_slice(&sample_rate.to_le_bytes());
+        wav.extend_from_slice(&(sample_rate * block_align as u32).to_le_bytes());
+        wav.extend_from_slice(&block_align.to_le_bytes());
+        wav.extend_from_slice(&bits.to_le_bytes());
+        wav.extend_from_slice(b"data");
+        wav.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        wav.extend(data);
+        wav
+    }
+
+    /// Encodes the WAV file into FLAC, and checks that Symphonia decodes the FLAC stream to the same samples.
+    fn round_trip(wav: Vec<u8>) {
+        let mut flac = Vec::new();
+        encode(
+            &mut PcmSource::open(Cursor::new(wav.clone())).unwrap(),
+            None,
+            &mut flac,
+        )
+        .unwrap();
+
+        let mut source = PcmSource::open(Cursor::new(flac)).unwrap();
+        assert_eq!(
+            source.format.tracks()[0].codec_params.codec,
+            CODEC_TYPE_FLAC
+        );
+        let mut samples = Vec::new();
+        while source.next::<i32>(&mut samples).unwrap() {}
+
+        let mut source = PcmSource::open(Cursor::new(wav)).unwrap();
+        let mut expected = Vec::new();
+        while source.next::<i32>(&mut expected).unwrap() {}
+
+        assert_eq!(samples.len(), expected.len());
+        assert!(samples == expected, "decoded samples differ from the input");
+    }
+
+    #[test]
+    fn round_trip_16_bit_stereo() {
+        let frames = 3 * BLOCK_SIZE + 123;
+        round_trip(wav(44100, 2, 16, &noise(16, frames * 2)));
+    }
+
+    #[test]
+    fn round_trip_24_bit_mono() {
+        round_trip(wav(96000, 1, 24, &noise(24, BLOCK_SIZE + 1)));
+    }
+
+    #[test]
+    fn round_trip_surround() {
+        round_trip(wav(48000, 6, 16, &noise(16, BLOCK_SIZE / 2 * 6)));
+    }
+
+    #[test]
+    fn round_trip_silence() {
+        round_trip(wav(44100, 2, 16, &[0; 10000]));
+    }
+}
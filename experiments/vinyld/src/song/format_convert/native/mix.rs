@@ -0,0 +1,177 @@
+//! Channel mixer.
+
+use anyhow::anyhow;
+use std::f32::consts::FRAC_1_SQRT_2;
+use symphonia::core::audio::Channels;
+
+/// A matrix mixing interleaved audio down to mono or stereo.
+///
+/// Coefficients follow ITU-R BS.775: centre and surround channels are mixed into both sides at -3dB, and LFE channels
+/// are dropped. The matrix is scaled down so that the mix never clips.
+pub struct Downmix {
+    in_channels: usize,
+    out_channels: usize,
+
+    /// Coefficients, `out_channels` of them for each input channel.
+    matrix: Vec<f32>,
+}
+impl Downmix {
+    /// Creates a mixer from the channel layout of the input to `out_channels` channels.
+    pub fn new(layout: Channels, out_channels: usize) -> anyhow::Result<Self> {
+        let in_channels = layout.count();
+        if !(1..=2).contains(&out_channels) {
+            return Err(anyhow!("cannot mix audio down to {out_channels} channels"));
+        }
+        if in_channels == out_channels {
+            return Ok(Self {
+                in_channels,
+                out_channels,
+                matrix: Vec::new(),
+            });
+        }
+
+        let mut matrix = Vec::with_capacity(in_channels * out_channels);
+        for channel in layout.iter() {
+            let (left, right) = stereo_gains(channel);
+            match out_channels {
+                1 => matrix.push((left + right) / 2.0),
+                _ => matrix.extend([left, right]),
+            }
+        }
+
+        let loudest = (0..out_channels)
+            .map(|x| matrix.iter().skip(x).step_by(out_channels).sum::<f32>())
+            .fold(1.0, f32::max);
+        matrix.iter_mut().for_each(|x| *x /= loudest);
+
+        Ok(Self {
+            in_channels,
+            out_channels,
+            matrix,
+        })
+    }
+
+    /// Mixes the interleaved input, appending the output to `out`.
+    pub fn process(&self, input: &[f32], out: &mut Vec<f32>) {
+        if self.matrix.is_empty() {
+            out.extend_from_slice(input);
+            return;
+        }
+
+        for frame in input.chunks_exact(self.in_channels) {
+            let mut mixed = [0.0; 2];
+            for (sample, gains) in frame
+                .iter()
+                .zip(self.matrix.chunks_exact(self.out_channels))
+            {
+                for (x, gain) in mixed.iter_mut().zip(gains) {
+                    *x += sample * gain;
+                }
+            }
+            out.extend_from_slice(&mixed[..self.out_channels]);
+        }
+    }
+}
+
+/// Returns gains of the channel in the left and right channels of a stereo mix.
+fn stereo_gains(channel: Channels) -> (f32, f32) {
+    const LEFT: Channels = Channels::FRONT_LEFT
+        .union(Channels::FRONT_LEFT_CENTRE)
+        .union(Channels::FRONT_LEFT_WIDE)
+        .union(Channels::FRONT_LEFT_HIGH)
+        .union(Channels::TOP_FRONT_LEFT);
+    const RIGHT: Channels = Channels::FRONT_RIGHT
+        .union(Channels::FRONT_RIGHT_CENTRE)
+        .union(Channels::FRONT_RIGHT_WIDE)
+        .union(Channels::FRONT_RIGHT_HIGH)
+        .union(Channels::TOP_FRONT_RIGHT);
+    const SURROUND_LEFT: Channels = Channels::REAR_LEFT
+        .union(Channels::SIDE_LEFT)
+        .union(Channels::REAR_LEFT_CENTRE)
+        .union(Channels::TOP_REAR_LEFT);
+    const SURROUND_RIGHT: Channels = Channels::REAR_RIGHT
+        .union(Channels::SIDE_RIGHT)
+        .union(Channels::REAR_RIGHT_CENTRE)
+        .union(Channels::TOP_REAR_RIGHT);
+    const CENTRE: Channels = Channels::FRONT_CENTRE
+        .union(Channels::FRONT_CENTRE_HIGH)
+        .union(Channels::TOP_FRONT_CENTRE)
+        .union(Channels::TOP_CENTRE);
+    const SURROUND_CENTRE: Channels = Channels::REAR_CENTRE.union(Channels::TOP_REAR_CENTRE);
+
+    if LEFT.contains(channel) {
+        (1.0, 0.0)
+    } else if RIGHT.contains(channel) {
+        (0.0, 1.0)
+    } else if CENTRE.contains(channel) {
+        (FRAC_1_SQRT_2, FRAC_1_SQRT_2)
+    } else if SURROUND_LEFT.contains(channel) {
+        (FRAC_1_SQRT_2, 0.0)
+    } else if SURROUND_RIGHT.contains(channel) {
+        (0.0, FRAC_1_SQRT_2)
+    } else if SURROUND_CENTRE.contains(channel) {
+        (0.5, 0.5)
+    } else {
+        // LFE channels are dropped.
+        (0.0, 0.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SURROUND_5_1: Channels = Channels::FRONT_LEFT
+        .union(Channels::FRONT_RIGHT)
+        .union(Channels::FRONT_CENTRE)
+        .union(Channels::LFE1)
+        .union(Channels::REAR_LEFT)
+        .union(Channels::REAR_RIGHT);
+
+    /// Mixes a single frame of 5.1 audio down to stereo.
+    fn mix_5_1(frame: [f32; 6]) -> Vec<f32> {
+        let mut out = Vec::new();
+        Downmix::new(SURROUND_5_1, 2)
+            .unwrap()
+            .process(&frame, &mut out);
+        out
+    }
+
+    #[test]
+    fn centre_is_mixed_into_both_sides() {
+        let out = mix_5_1([0.0, 0.0, 1.0, 0.0, 0.0, 0.0]);
+        assert!(out[0] > 0.0);
+        assert_eq!(out[0], out[1]);
+    }
+
+    #[test]
+    fn lfe_is_dropped() {
+        assert_eq!(mix_5_1([0.0, 0.0, 0.0, 1.0, 0.0, 0.0]), [0.0, 0.0]);
+    }
+
+    #[test]
+    fn sides_stay_apart() {
+        let out = mix_5_1([1.0, 0.0, 0.0, 0.0, 1.0, 0.0]);
+        assert!(out[0] > 0.0);
+        assert_eq!(out[1], 0.0);
+
+        let out = mix_5_1([0.0, 1.0, 0.0, 0.0, 0.0, 1.0]);
+        assert_eq!(out[0], 0.0);
+        assert!(out[1] > 0.0);
+    }
+
+    #[test]
+    fn full_scale_does_not_clip() {
+        let out = mix_5_1([1.0; 6]);
+        assert!(out.iter().all(|x| x.abs() <= 1.0 + f32::EPSILON));
+    }
+
+    #[test]
+    fn stereo_mixes_down_to_mono() {
+        let mut out = Vec::new();
+        Downmix::new(Channels::FRONT_LEFT | Channels::FRONT_RIGHT, 1)
+            .unwrap()
+            .process(&[1.0, 0.0, 0.5, 0.5], &mut out);
+        assert_eq!(out, [0.5, 0.5]);
+    }
+}
@@ -0,0 +1,217 @@
+//! Native backend, which decodes with Symphonia and encodes in-process.
+
+mod flac;
+#[cfg(any(feature = "opus", test))]
+mod mix;
+#[cfg(feature = "opus")]
+mod opus;
+#[cfg(any(feature = "opus", test))]
+mod resample;
+
+use super::{Codec, Container, Conversion, MetadataOps};
+use anyhow::anyhow;
+use std::{
+    io::{ErrorKind, Read, Seek, SeekFrom, Write},
+    sync::{Mutex, PoisonError},
+};
+use symphonia::core::{
+    audio::{Channels, SampleBuffer},
+    codecs::{CODEC_TYPE_NULL, Decoder, DecoderOptions},
+    conv::ConvertibleSample,
+    errors::Error as DecodeError,
+    formats::{FormatOptions, FormatReader},
+    io::{MediaSource, MediaSourceStream},
+    meta::MetadataOptions,
+    probe::Hint,
+};
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio_util::io::SyncIoBridge;
+
+pub async fn convert<
+    I: AsyncRead + Send + Unpin + 'static,
+    O: AsyncWrite + Send + Unpin + 'static,
+>(
+    conversion: Conversion<I>,
+    out: O,
+) -> anyhow::Result<()> {
+    let codec = match (conversion.out_container, conversion.out_codec) {
+        (Some(Container::Flac) | None, Some(Codec::Flac)) | (Some(Container::Flac), None) => {
+            Codec::Flac
+        }
+        (Some(Container::Ogg) | None, Some(Codec::Opus)) | (Some(Container::Ogg), None) => {
+            Codec::Opus
+        }
+        (None, None) => return Err(anyhow!("no output format is specified")),
+        (container, codec) => {
+            return Err(anyhow!(
+                "codec `{codec:?}` in container `{container:?}` is not supported by the native backend"
+            ));
+        }
+    };
+    let quality = conversion.out_quality;
+    // Tags of the input are never copied, so `MetadataOps::Keep` writes no tags at all.
+    let metadata = match conversion.out_metadata {
+        MetadataOps::Set(metadata) => Some(metadata),
+        MetadataOps::Keep => None,
+    };
+    let input = SyncIoBridge::new(conversion.input);
+    let mut output = SyncIoBridge::new(out);
+
+    tokio::task::spawn_blocking(move || {
+        let mut source = PcmSource::open(input)?;
+        match codec {
+            Codec::Flac => flac::encode(&mut source, metadata.as_ref(), &mut output)?,
+            #[cfg(feature = "opus")]
+            Codec::Opus => opus::encode(
+                &mut source,
+                quality.unwrap_or(super::LossyQuality::High),
+                metadata.as_ref(),
+                &mut output,
+            )?,
+            #[cfg(not(feature = "opus"))]
+            Codec::Opus => {
+                _ = (quality, metadata);
+                return Err(anyhow!(
+                    "opus encoding requires vinyld to be built with the `opus` feature"
+                ));
+            }
+        }
+        output.flush()?;
+        output.shutdown()?;
+        Ok(())
+    })
+    .await?
+}
+
+/// Decoded PCM stream of an audio file.
+pub struct PcmSource {
+    format: Box<dyn FormatReader>,
+    decoder: Box<dyn Decoder>,
+    track_id: u32,
+    sample_rate: u32,
+    channels: Channels,
+    bits_per_sample: Option<u32>,
+}
+impl PcmSource {
+    /// Probes the input and opens its default audio track.
+    pub fn open(input: impl Read + Send + 'static) -> anyhow::Result<Self> {
+        let stream =
+            MediaSourceStream::new(Box::new(Unseekable(Mutex::new(input))), Default::default());
+        let format = symphonia::default::get_probe()
+            .format(
+                &Hint::new(),
+                stream,
+                &FormatOptions::default(),
+                &MetadataOptions::default(),
+            )
+            .map_err(|err| anyhow!("unrecognized audio format: {err}"))?
+            .format;
+        let track = format
+            .tracks()
+            .iter()
+            .find(|x| x.codec_params.codec != CODEC_TYPE_NULL)
+            .ok_or_else(|| anyhow!("no audio track found"))?;
+        let params = &track.codec_params;
+        let sample_rate = params
+            .sample_rate
+            .ok_or_else(|| anyhow!("unknown sample rate"))?;
+        let channels = params
+            .channels
+            .ok_or_else(|| anyhow!("unknown channel layout"))?;
+        let decoder = symphonia::default::get_codecs()
+            .make(params, &DecoderOptions::default())
+            .map_err(|err| anyhow!("unsupported audio codec: {err}"))?;
+
+        Ok(Self {
+            track_id: track.id,
+            sample_rate,
+            channels,
+            bits_per_sample: params.bits_per_sample,
+            format,
+            decoder,
+        })
+    }
+
+    /// Returns the sample rate of the stream.
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    /// Returns the number of channels of the stream.
+    pub fn channels(&self) -> usize {
+        self.channels.count()
+    }
+
+    /// Returns the channel layout of the stream.
+    #[cfg(feature = "opus")]
+    pub fn layout(&self) -> Channels {
+        self.channels
+    }
+
+    /// Returns the bit depth of the stream, if it has one.
+    pub fn bits_per_sample(&self) -> Option<u32> {
+        self.bits_per_sample
+    }
+
+    /// Decodes the next packet and appends its samples to `buf`, interleaved. Returns `false` if the stream ends.
+    pub fn next<S: ConvertibleSample>(&mut self, buf: &mut Vec<S>) -> anyhow::Result<bool> {
+        loop {
+            let packet = match self.format.next_packet() {
+                Ok(x) => x,
+                Err(DecodeError::IoError(err)) if err.kind() == ErrorKind::UnexpectedEof => {
+                    return Ok(false);
+                }
+                Err(DecodeError::ResetRequired) => return Ok(false),
+                Err(err) => return Err(err.into()),
+            };
+            if packet.track_id() != self.track_id {
+                continue;
+            }
+
+            match self.decoder.decode(&packet) {
+                Ok(decoded) => {
+                    if decoded.spec().channels.count() != self.channels.count() {
+                        return Err(anyhow!("channel layout changed in the middle of stream"));
+                    }
+                    let mut samples =
+                        SampleBuffer::<S>::new(decoded.capacity() as _, *decoded.spec());
+                    samples.copy_interleaved_ref(decoded);
+                    buf.extend_from_slice(samples.samples());
+                    return Ok(true);
+                }
+                Err(DecodeError::DecodeError(err)) => {
+                    tracing::debug!("skipping malformed audio packet: {err}");
+                }
+                Err(err) => return Err(err.into()),
+            }
+        }
+    }
+}
+
+/// A media source that cannot be seeked.
+struct Unseekable<R>(Mutex<R>);
+impl<R: Read> Read for Unseekable<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        self.0
+            .get_mut()
+            .unwrap_or_else(PoisonError::into_inner)
+            .read(buf)
+    }
+}
+impl<R> Seek for Unseekable<R> {
+    fn seek(&mut self, _: SeekFrom) -> std::io::Result<u64> {
+        Err(std::io::Error::new(
+            ErrorKind::Unsupported,
+            "the media source is not seekable",
+        ))
+    }
+}
+impl<R: Read + Send> MediaSource for Unseekable<R> {
+    fn is_seekable(&self) -> bool {
+        false
+    }
+
+    fn byte_len(&self) -> Option<u64> {
+        None
+    }
+}
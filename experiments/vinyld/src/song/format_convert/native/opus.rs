@@ -0,0 +1,122 @@
+//! Opus encoder, muxed into an Ogg container.
+
+use super::{PcmSource, mix::Downmix, resample::Resampler};
+use crate::song::format_convert::{LossyQuality, Metadata, tags};
+use anyhow::anyhow;
+use audiopus::{Application, Bitrate, Channels, SampleRate, coder::Encoder};
+use ogg::writing::{PacketWriteEndInfo, PacketWriter};
+use std::io::Write;
+
+/// Sample rate that Opus streams are encoded in.
+const SAMPLE_RATE: u32 = 48000;
+
+/// Number of inter-channel samples in a frame, which is 20ms in 48kHz.
+const FRAME_SIZE: usize = 960;
+
+/// Maximum size of an encoded packet, recommended by libopus.
+const MAX_PACKET_SIZE: usize = 4000;
+
+/// Encodes the source into an Ogg Opus stream, tagged with given metadata.
+pub fn encode(
+    source: &mut PcmSource,
+    quality: LossyQuality,
+    metadata: Option<&Metadata>,
+    out: &mut impl Write,
+) -> anyhow::Result<()> {
+    let (channels, out_channels) = match source.channels() {
+        1 => (Channels::Mono, 1usize),
+        _ => (Channels::Stereo, 2),
+    };
+    let downmix = Downmix::new(source.layout(), out_channels)?;
+
+    let mut encoder = Encoder::new(SampleRate::Hz48000, channels, Application::Audio)?;
+    encoder.set_bitrate(Bitrate::BitsPerSecond(quality.bitrate() as _))?;
+    let pre_skip = encoder.lookahead()? as u64;
+
+    let serial = rand::random();
+    let mut writer = PacketWriter::new(out);
+    writer.write_packet(
+        opus_head(out_channels as _, pre_skip as _, source.sample_rate()).into(),
+        serial,
+        PacketWriteEndInfo::EndPage,
+        0,
+    )?;
+    writer.write_packet(
+        opus_tags(metadata).into(),
+        serial,
+        PacketWriteEndInfo::EndPage,
+        0,
+    )?;
+
+    let mut resampler = Resampler::new(source.sample_rate(), SAMPLE_RATE, out_channels);
+    let mut decoded = Vec::new();
+    let mut mixed = Vec::new();
+    let mut resampled = Vec::new();
+    let mut packet = vec![0; MAX_PACKET_SIZE];
+    let mut pending: Option<(Vec<u8>, u64)> = None;
+    let mut real_samples;
+    let mut encoded_samples = 0;
+    let mut ended = false;
+
+    while !ended {
+        if source.next::<f32>(&mut decoded)? {
+            downmix.process(&decoded, &mut mixed);
+            decoded.clear();
+            resampler.process(&mixed, &mut resampled);
+            mixed.clear();
+            real_samples = encoded_samples + (resampled.len() / out_channels) as u64;
+        } else {
+            resampler.finish(&mut resampled);
+            real_samples = encoded_samples + (resampled.len() / out_channels) as u64;
+            // Pad the encoder's lookahead so that the tail of the audio is flushed.
+            resampled.extend(std::iter::repeat_n(0.0, pre_skip as usize * out_channels));
+            ended = true;
+        }
+
+        while resampled.len() >= FRAME_SIZE * out_channels || (ended && !resampled.is_empty()) {
+            let len = resampled.len().min(FRAME_SIZE * out_channels);
+            let mut frame: Vec<f32> = resampled.drain(..len).collect();
+            frame.resize(FRAME_SIZE * out_channels, 0.0);
+
+            let n = encoder.encode_float(&frame, &mut packet)?;
+            encoded_samples += FRAME_SIZE as u64;
+            let granule = pre_skip + encoded_samples.min(real_samples);
+            if let Some((data, granule)) = pending.replace((packet[..n].to_vec(), granule)) {
+                writer.write_packet(
+                    data.into(),
+                    serial,
+                    PacketWriteEndInfo::NormalPacket,
+                    granule,
+                )?;
+            }
+        }
+    }
+
+    let (data, granule) = pending.ok_or_else(|| anyhow!("no audio is decoded"))?;
+    writer.write_packet(data.into(), serial, PacketWriteEndInfo::EndStream, granule)?;
+
+    Ok(())
+}
+
+/// Returns the `OpusHead` identification header.
+fn opus_head(channels: u8, pre_skip: u16, input_sample_rate: u32) -> Vec<u8> {
+    let mut head = Vec::with_capacity(19);
+    head.extend_from_slice(b"OpusHead");
+    head.push(1);
+    head.push(channels);
+    head.extend_from_slice(&pre_skip.to_le_bytes());
+    head.extend_from_slice(&input_sample_rate.to_le_bytes());
+    head.extend_from_slice(&0i16.to_le_bytes());
+    head.push(0);
+    head
+}
+
+/// Returns the `OpusTags` comment header.
+fn opus_tags(metadata: Option<&Metadata>) -> Vec<u8> {
+    let comments = metadata
+        .map(Metadata::comments_with_picture)
+        .unwrap_or_default();
+    let mut header = b"OpusTags".to_vec();
+    header.extend(tags::vorbis_comment(&comments));
+    header
+}
@@ -0,0 +1,178 @@
+//! Sample rate converter.
+
+use std::f64::consts::PI;
+
+/// Half width of the interpolation kernel, in input frames.
+const RADIUS: usize = 16;
+
+/// Number of precomputed fractional phases of the kernel.
+const PHASES: usize = 512;
+
+/// A streaming windowed-sinc sample rate converter.
+pub struct Resampler {
+    channels: usize,
+    ratio: f64,
+    table: Vec<f32>,
+    buffer: Vec<f32>,
+    dropped: u64,
+    produced: u64,
+    consumed: u64,
+}
+impl Resampler {
+    /// Creates a resampler that converts interleaved audio from `from` Hz to `to` Hz.
+    pub fn new(from: u32, to: u32, channels: usize) -> Self {
+        let ratio = from as f64 / to as f64;
+        let cutoff = 0.95 * (to as f64 / from as f64).min(1.0);
+
+        let taps = 2 * RADIUS;
+        let mut table = Vec::with_capacity((PHASES + 1) * taps);
+        for phase in 0..=PHASES {
+            let frac = phase as f64 / PHASES as f64;
+            for k in 0..taps {
+                let distance = frac + RADIUS as f64 - 1.0 - k as f64;
+                let sinc = if distance == 0.0 {
+                    cutoff
+                } else {
+                    (PI * cutoff * distance).sin() / (PI * distance)
+                };
+                let window = 0.5 + 0.5 * (PI * distance / RADIUS as f64).cos();
+                table.push((sinc * window) as f32);
+            }
+        }
+
+        Self {
+            channels,
+            ratio,
+            table,
+            buffer: vec![0.0; RADIUS * channels],
+            dropped: 0,
+            produced: 0,
+            consumed: 0,
+        }
+    }
+
+    /// Feeds interleaved input, appending available output to `out`.
+    pub fn process(&mut self, input: &[f32], out: &mut Vec<f32>) {
+        if self.ratio == 1.0 {
+            out.extend_from_slice(input);
+            return;
+        }
+
+        self.consumed += (input.len() / self.channels) as u64;
+        self.buffer.extend_from_slice(input);
+        self.drain(out, None);
+    }
+
+    /// Flushes the remaining output to `out`.
+    pub fn finish(&mut self, out: &mut Vec<f32>) {
+        if self.ratio == 1.0 {
+            return;
+        }
+
+        self.buffer
+            .extend(std::iter::repeat_n(0.0, RADIUS * self.channels));
+        let total = (self.consumed as f64 / self.ratio).ceil() as u64;
+        self.drain(out, Some(total));
+    }
+
+    /// Produces as many output frames as the buffer allows.
+    fn drain(&mut self, out: &mut Vec<f32>, limit: Option<u64>) {
+        let taps = 2 * RADIUS;
+        let buffered = (self.buffer.len() / self.channels) as u64;
+
+        loop {
+            if limit.is_some_and(|x| self.produced >= x) {
+                break;
+            }
+            let time = self.produced as f64 * self.ratio;
+            let base = time.floor() as u64;
+            // Input frame `n` is at buffer index `n + RADIUS - dropped`.
+            if base + taps as u64 >= buffered + self.dropped {
+                break;
+            }
+
+            let phase = ((time - base as f64) * PHASES as f64).round() as usize;
+            let weights = &self.table[phase * taps..(phase + 1) * taps];
+            let start = (base + 1 - self.dropped) as usize;
+            for channel in 0..self.channels {
+                let mut sample = 0.0;
+                for (k, weight) in weights.iter().enumerate() {
+                    sample += self.buffer[(start + k) * self.channels + channel] * weight;
+                }
+                out.push(sample);
+            }
+            self.produced += 1;
+        }
+
+        let time = self.produced as f64 * self.ratio;
+        let needed = time.floor() as u64 + 1;
+        if needed > self.dropped {
+            let drop = (needed - self.dropped).min(buffered) as usize;
+            self.buffer.drain(..drop * self.channels);
+            self.dropped += drop as u64;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns `frames` frames of a stereo sine wave, with the right channel in opposite phase.
+    fn sine(frequency: f64, sample_rate: u32, frames: usize) -> Vec<f32> {
+        (0..frames)
+            .flat_map(|n| {
+                let x = (2.0 * PI * frequency * n as f64 / sample_rate as f64).sin() as f32;
+                [x, -x]
+            })
+            .collect()
+    }
+
+    /// Resamples the input, feeding it in chunks of given number of frames.
+    fn resample(input: &[f32], from: u32, to: u32, chunk: usize) -> Vec<f32> {
+        let mut resampler = Resampler::new(from, to, 2);
+        let mut out = Vec::new();
+        for x in input.chunks(chunk * 2) {
+            resampler.process(x, &mut out);
+        }
+        resampler.finish(&mut out);
+        out
+    }
+
+    #[test]
+    fn output_length_follows_ratio() {
+        for (from, to) in [(44100, 48000), (96000, 48000), (22050, 48000)] {
+            let out = resample(&sine(1000.0, from, 12345), from, to, 1000);
+            let expected = (12345.0 * to as f64 / from as f64).ceil() as usize;
+            assert_eq!(out.len(), expected * 2, "{from}Hz to {to}Hz");
+        }
+    }
+
+    #[test]
+    fn sine_is_preserved() {
+        for (from, to) in [(44100, 48000), (96000, 48000)] {
+            let out = resample(&sine(1000.0, from, from as usize), from, to, 4096);
+            let expected = sine(1000.0, to, out.len() / 2);
+            // Samples near the edges are affected by the zeros padded around the input.
+            let edge = 4 * RADIUS * 2;
+            for (i, (x, y)) in out.iter().zip(&expected).enumerate().skip(edge) {
+                if i >= out.len() - edge {
+                    break;
+                }
+                assert!(
+                    (x - y).abs() < 1e-2,
+                    "{from}Hz to {to}Hz at {i}: {x} != {y}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn chunking_does_not_change_output() {
+        let input = sine(440.0, 44100, 10000);
+        assert_eq!(
+            resample(&input, 44100, 48000, 10000),
+            resample(&input, 44100, 48000, 7),
+        );
+    }
+}
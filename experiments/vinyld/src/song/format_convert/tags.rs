@@ -0,0 +1,86 @@
+//! Encoding of tags shared by backends.
+
+use super::{Cover, Metadata};
+use base64::{Engine, prelude::BASE64_STANDARD};
+
+/// Vendor string written to Vorbis comment headers.
+pub const VENDOR: &str = concat!("vinyld v", env!("CARGO_PKG_VERSION"));
+
+/// Picture type of front covers, as defined by ID3v2 `APIC` frames.
+const FRONT_COVER: u32 = 3;
+
+impl Metadata {
+    /// Returns the textual metadata as Vorbis comments.
+    pub fn comments(&self) -> Vec<(&'static str, String)> {
+        vec![
+            ("TITLE", self.title.clone()),
+            ("ALBUM", self.album.clone()),
+            ("ARTIST", self.author.clone()),
+        ]
+    }
+
+    /// Returns the metadata as Vorbis comments, with the cover included as a `METADATA_BLOCK_PICTURE` comment.
+    ///
+    /// Both backends embed covers this way, even into FLAC streams which have `PICTURE` blocks, since FFMpeg only
+    /// writes `PICTURE` blocks of a few image formats which don't include AVIF.
+    pub fn comments_with_picture(&self) -> Vec<(&'static str, String)> {
+        let mut comments = self.comments();
+        if let Some(cover) = &self.cover {
+            comments.push((
+                "METADATA_BLOCK_PICTURE",
+                BASE64_STANDARD.encode(cover.picture_block()),
+            ));
+        }
+        comments
+    }
+}
+
+impl Cover {
+    /// Returns the cover as the body of a FLAC `PICTURE` metadata block.
+    pub fn picture_block(&self) -> Vec<u8> {
+        let mut block = Vec::with_capacity(32 + self.mime.len() + self.data.len());
+        block.extend_from_slice(&FRONT_COVER.to_be_bytes());
+        block.extend_from_slice(&(self.mime.len() as u32).to_be_bytes());
+        block.extend_from_slice(self.mime.as_bytes());
+        block.extend_from_slice(&0u32.to_be_bytes()); // empty description
+        block.extend_from_slice(&0u32.to_be_bytes()); // unknown width
+        block.extend_from_slice(&0u32.to_be_bytes()); // unknown height
+        block.extend_from_slice(&0u32.to_be_bytes()); // unknown color depth
+        block.extend_from_slice(&0u32.to_be_bytes()); // not an indexed-color picture
+        block.extend_from_slice(&(self.data.len() as u32).to_be_bytes());
+        block.extend_from_slice(&self.data);
+        block
+    }
+}
+
+/// Encodes a Vorbis comment header without the framing bit, as used by both `OpusTags` and FLAC `VORBIS_COMMENT`.
+pub fn vorbis_comment(comments: &[(&str, String)]) -> Vec<u8> {
+    let mut header = Vec::new();
+    header.extend_from_slice(&(VENDOR.len() as u32).to_le_bytes());
+    header.extend_from_slice(VENDOR.as_bytes());
+    header.extend_from_slice(&(comments.len() as u32).to_le_bytes());
+    for (key, value) in comments {
+        header.extend_from_slice(&((key.len() + 1 + value.len()) as u32).to_le_bytes());
+        header.extend_from_slice(key.as_bytes());
+        header.push(b'=');
+        header.extend_from_slice(value.as_bytes());
+    }
+    header
+}
+
+/// Encodes the metadata in FFMpeg's `ffmetadata` format.
+pub fn ffmetadata(metadata: &Metadata) -> String {
+    let mut file = String::from(";FFMETADATA1\n");
+    for (key, value) in metadata.comments_with_picture() {
+        file.push_str(key);
+        file.push('=');
+        for c in value.chars() {
+            if matches!(c, '=' | ';' | '#' | '\\' | '\n') {
+                file.push('\\');
+            }
+            file.push(c);
+        }
+        file.push('\n');
+    }
+    file
+}
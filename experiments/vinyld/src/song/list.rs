@@ -0,0 +1,125 @@
+//! Listing and searching of songs.
+
+use super::{Profile, Songs};
+use crate::{
+    album::AlbumId,
+    database::{entity::song, search},
+    error::Error,
+    moderation::ModerationStatus,
+    policy::Subject,
+    util::page::{MAX_SCAN_BATCHES, Page, PageQuery},
+};
+use sea_orm::{
+    ColumnTrait, ConnectionTrait, EntityTrait, Order, QueryFilter, QueryOrder, QuerySelect, Select,
+};
+
+/// Way of scanning songs in a listing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Scan {
+    /// Songs are scanned in the ascending order of IDs. Positions are song IDs.
+    Ascending,
+
+    /// Songs are scanned in the descending order of IDs. Positions are song IDs.
+    Descending,
+
+    /// Songs are scanned in the order of the select. Positions are offsets.
+    Ordered,
+}
+
+impl Songs<'_> {
+    /// Lists songs in the album, in the order of uploading.
+    pub async fn list_by_album(
+        &self,
+        album_id: AlbumId,
+        subject: &Subject,
+        query: &PageQuery,
+    ) -> Result<Page<Profile>, Error> {
+        self.0.albums().profile(album_id, subject).await?;
+
+        let select = song::Entity::find().filter(song::Column::Album.eq(album_id.0));
+        self.scan(select, Scan::Ascending, subject, query).await
+    }
+
+    /// Lists recently uploaded songs, from the newest.
+    pub async fn list_recent(
+        &self,
+        subject: &Subject,
+        query: &PageQuery,
+    ) -> Result<Page<Profile>, Error> {
+        self.scan(song::Entity::find(), Scan::Descending, subject, query)
+            .await
+    }
+
+    /// Searches songs by title, from the most relevant.
+    pub async fn search(
+        &self,
+        text: &str,
+        subject: &Subject,
+        query: &PageQuery,
+    ) -> Result<Page<Profile>, Error> {
+        let backend = self.0.database.conn.get_database_backend();
+        let select = song::Entity::find()
+            .filter(search::SONG.matches(backend, text))
+            .order_by(search::SONG.rank(backend, text), Order::Desc)
+            .order_by_desc(song::Column::SongId);
+        self.scan(select, Scan::Ordered, subject, query).await
+    }
+
+    /// Collects a page of songs from the select, skipping songs that the subject cannot see or is not allowed to listen
+    /// to.
+    ///
+    /// Songs hidden by moderation are filtered out by the query, while listen policies are checked here, in at most
+    /// [`MAX_SCAN_BATCHES`] batches.
+    async fn scan(
+        &self,
+        select: Select<song::Entity>,
+        scan: Scan,
+        subject: &Subject,
+        query: &PageQuery,
+    ) -> Result<Page<Profile>, Error> {
+        let limit = query.limit();
+        let mut position = query.position()?;
+        let mut items = Vec::new();
+        let select = select.filter(ModerationStatus::visible_condition(
+            song::Column::Status,
+            song::Column::Uploader,
+            subject,
+        ));
+
+        for _ in 0..MAX_SCAN_BATCHES {
+            let mut batch = select.clone().limit(limit);
+            batch = match (scan, position) {
+                (Scan::Ascending, Some(x)) => batch.filter(song::Column::SongId.gt(x)),
+                (Scan::Descending, Some(x)) => batch.filter(song::Column::SongId.lt(x)),
+                (Scan::Ordered, Some(x)) => batch.offset(x as u64),
+                (_, None) => batch,
+            };
+            batch = match scan {
+                Scan::Ascending => batch.order_by_asc(song::Column::SongId),
+                Scan::Descending => batch.order_by_desc(song::Column::SongId),
+                Scan::Ordered => batch,
+            };
+            let models = batch.all(&*self.0.database.conn).await?;
+            let exhausted = (models.len() as u64) < limit;
+
+            for model in models {
+                position = Some(match scan {
+                    Scan::Ascending | Scan::Descending => model.song_id,
+                    Scan::Ordered => position.unwrap_or(0) + 1,
+                });
+                if model.listenable_by(subject)? {
+                    items.push(model.into_profile()?);
+                }
+                if items.len() as u64 == limit {
+                    return Ok(Page::new(items, position));
+                }
+            }
+
+            if exhausted {
+                return Ok(Page::new(items, None));
+            }
+        }
+
+        Ok(Page::new(items, position))
+    }
+}
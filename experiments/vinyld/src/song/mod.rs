@@ -1,18 +1,31 @@
 mod format_convert;
+mod list;
+pub mod probe;
+pub mod transcode;
 
 use crate::{
     AppState,
     album::AlbumId,
-    database::entity::song,
+    app_settings::{MandatorySongCensorship, MaxSongDuration},
+    audit::Actor,
+    database::entity::{album, song, song_rendition, user},
     error::{Error, ErrorCode},
     local_data::temp::TempFile,
+    moderation::ModerationStatus,
     policy::{ObjectPolicy, Subject},
     user::{Uid, User},
 };
-use sea_orm::{ActiveValue::NotSet, EntityTrait, Set};
+use probe::AudioInfo;
+use sea_orm::{
+    ActiveModelTrait, ActiveValue::NotSet, ColumnTrait, Condition, EntityTrait, IntoActiveModel,
+    QueryFilter, Set, TransactionTrait, sea_query::OnConflict,
+};
 use serde::{Deserialize, Serialize};
+use tokio::io::AsyncReadExt;
 use vinioss::ObjectKey;
 
+pub use format_convert::Backend as AudioBackend;
+
 /// Manager of the song set.
 pub struct Songs<'a>(&'a AppState);
 impl Songs<'_> {
@@ -43,21 +56,35 @@ impl Songs<'_> {
             return Err(Error::denied_by_policy(&album_write_policy.class));
         }
 
-        let recompressed = self
-            .0
-            .local_data
-            .temp
-            .open()
-            .await
-            .map_err(|err| Error::internal(err))?;
-        format_convert::Conversion::new(unprocessed.reader().await.map_err(Error::internal)?)
-            .codec(upload.quality.codec())
-            .lossy_quality(upload.quality.lossy_quality())
-            .discard_metadata()
-            .container(upload.quality.container())
-            .perform(recompressed.appender().await.map_err(Error::internal)?)
-            .await
-            .map_err(|err| Error::bad_request(format!("failed to recompress audio: {err}")))?;
+        let language = upload
+            .language
+            .as_deref()
+            .map(crate::util::language::normalize)
+            .transpose()?;
+
+        let max_duration = self.0.app_settings().get::<MaxSongDuration>().await;
+        let probe = probe::probe(&unprocessed, max_duration).await?;
+        if upload.quality == AudioQuality::Lossless {
+            if !probe.lossless {
+                return Err(Error::audio_quality_mismatch("lossy_codec"));
+            }
+            if let Some(lowpass) = probe.lowpass {
+                tracing::info!("Rejecting a lossless upload with a lowpass at {lowpass}Hz.");
+                return Err(Error::audio_quality_mismatch("lossy_transcode"));
+            }
+        }
+        let info = probe.info;
+        let mandatory = self.0.app_settings().get::<MandatorySongCensorship>().await;
+        let status = ModerationStatus::initial_for(&uploader, mandatory);
+
+        let unprocessed_id = ObjectKey(vinutie::random::filename("song_upload", "bin"));
+        self.0
+            .objects
+            .put_stream(
+                unprocessed_id.clone(),
+                &mut unprocessed.reader().await.map_err(Error::internal)?,
+            )
+            .await?;
         drop(unprocessed);
 
         let object_id = ObjectKey(vinutie::random::filename(
@@ -65,31 +92,57 @@ impl Songs<'_> {
             upload.quality.filename_extension(),
         ));
 
-        let song = song::Entity::insert(song::ActiveModel {
-            song_id: NotSet,
-            title: Set(upload.title),
-            album: Set(upload.album.0),
-            uploader: Set(uploader.uid.0),
-            origin_audio: Set(object_id.0.clone()),
-            listen_policy: Set(None),
-            created_at: Set(chrono::Utc::now().naive_utc()),
-        })
-        .exec(&*self.0.database.conn)
-        .await
-        .map_err(|err| match err.sql_err() {
-            Some(sea_orm::SqlErr::ForeignKeyConstraintViolation(_)) => Error::non_existent_album(),
-            _ => Error::internal(err),
-        })?;
-
-        self.0
-            .objects
-            .put_stream(
-                object_id.clone(),
-                &mut recompressed.reader().await.map_err(Error::internal)?,
+        let inserted = async {
+            let txn = self.0.database.conn.begin().await?;
+            let song = song::Entity::insert(song::ActiveModel {
+                song_id: NotSet,
+                title: Set(upload.title),
+                album: Set(upload.album.0),
+                uploader: Set(uploader.uid.0),
+                origin_audio: Set(object_id.0.clone()),
+                listen_policy: Set(None),
+                created_at: Set(chrono::Utc::now().naive_utc()),
+                duration_ms: Set(Some(info.duration_ms as _)),
+                sample_rate: Set(Some(info.sample_rate as _)),
+                channels: Set(Some(info.channels as _)),
+                source_codec: Set(Some(info.source_codec)),
+                source_bitrate: Set(Some(info.source_bitrate as _)),
+                language: Set(language),
+                status: Set(status.as_str().into()),
+                rejection_reason: Set(None),
+            })
+            .exec(&txn)
+            .await
+            .map_err(|err| match err.sql_err() {
+                Some(sea_orm::SqlErr::ForeignKeyConstraintViolation(_)) => {
+                    Error::non_existent_album()
+                }
+                _ => Error::internal(err),
+            })?;
+            let song_id = SongId(song.last_insert_id);
+            self.enqueue_transcode(
+                &txn,
+                song_id,
+                unprocessed_id.clone(),
+                object_id,
+                upload.quality,
             )
             .await?;
+            txn.commit().await?;
 
-        Ok(SongId(song.last_insert_id))
+            Ok::<_, Error>(song_id)
+        }
+        .await;
+        if inserted.is_err()
+            && let Err(err) = self.0.objects.remove(unprocessed_id.clone()).await
+        {
+            tracing::warn!(
+                "failed to remove unprocessed audio `{}`: {err}",
+                unprocessed_id.0
+            );
+        }
+
+        inserted
     }
 
     /// Gets audio file of given song ID and audio quality.
@@ -102,31 +155,266 @@ impl Songs<'_> {
         }
     }
 
+    /// Returns the audio file of the song in given quality, transcoding it from the origin audio
+    /// file if it has not been rendered yet.
+    async fn rendition(
+        &self,
+        model: &song::Model,
+        quality: AudioQuality,
+    ) -> Result<ObjectKey, Error> {
+        if let Some(rendition) =
+            song_rendition::Entity::find_by_id((model.song_id, quality.name().into()))
+                .one(&*self.0.database.conn)
+                .await?
+        {
+            return Ok(ObjectKey(rendition.audio));
+        }
+
+        tracing::info!(
+            "Rendering song {} in quality `{}`...",
+            model.song_id,
+            quality.name()
+        );
+
+        let object_id = ObjectKey(vinutie::random::filename(
+            "song_audio",
+            quality.filename_extension(),
+        ));
+        self.transcode(
+            SongId(model.song_id),
+            ObjectKey(model.origin_audio.clone()),
+            object_id.clone(),
+            quality,
+        )
+        .await?;
+
+        let inserted = song_rendition::Entity::insert(song_rendition::ActiveModel {
+            song: Set(model.song_id),
+            quality: Set(quality.name().into()),
+            audio: Set(object_id.0.clone()),
+            created_at: Set(chrono::Utc::now().naive_utc()),
+        })
+        .on_conflict(
+            OnConflict::columns([
+                song_rendition::Column::Song,
+                song_rendition::Column::Quality,
+            ])
+            .do_nothing()
+            .to_owned(),
+        )
+        .exec(&*self.0.database.conn)
+        .await;
+
+        match inserted {
+            Ok(_) => Ok(object_id),
+            Err(sea_orm::DbErr::RecordNotInserted) => {
+                // Another request has rendered the same quality concurrently, so we prefer theirs.
+                self.0.objects.remove(object_id).await?;
+                song_rendition::Entity::find_by_id((model.song_id, quality.name().into()))
+                    .one(&*self.0.database.conn)
+                    .await?
+                    .map(|x| ObjectKey(x.audio))
+                    .ok_or_else(Error::not_found)
+            }
+            Err(err) => {
+                _ = self.0.objects.remove(object_id).await;
+                Err(err.into())
+            }
+        }
+    }
+
+    /// Removes rendered audio files of the songs matching the condition, so that they are rendered again when
+    /// requested.
+    ///
+    /// Renditions are tagged with metadata of their songs, so this must be called when the metadata changes.
+    pub(crate) async fn invalidate_renditions(&self, songs: Condition) -> Result<(), Error> {
+        let renditions = song_rendition::Entity::find()
+            .inner_join(song::Entity)
+            .filter(songs)
+            .all(&*self.0.database.conn)
+            .await?;
+        if renditions.is_empty() {
+            return Ok(());
+        }
+
+        song_rendition::Entity::delete_many()
+            .filter(song_rendition::Column::Audio.is_in(renditions.iter().map(|x| x.audio.clone())))
+            .exec(&*self.0.database.conn)
+            .await?;
+        for rendition in renditions {
+            if let Err(err) = self
+                .0
+                .objects
+                .remove(ObjectKey(rendition.audio.clone()))
+                .await
+            {
+                tracing::warn!("failed to remove rendition `{}`: {err}", rendition.audio);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Transcodes an audio object of the song into given quality, and stores the result at `target`.
+    ///
+    /// The output is tagged with metadata of the song at the time of transcoding.
+    async fn transcode(
+        &self,
+        song_id: SongId,
+        source: ObjectKey,
+        target: ObjectKey,
+        quality: AudioQuality,
+    ) -> Result<(), Error> {
+        let metadata = self.metadata(song_id).await?;
+        let source = self.0.objects.get_stream(source).await?;
+        let transcoded = self
+            .0
+            .local_data
+            .temp
+            .open()
+            .await
+            .map_err(Error::internal)?;
+        format_convert::Conversion::new(source)
+            .backend(self.0.audio_backend)
+            .codec(quality.codec())
+            .lossy_quality(quality.lossy_quality())
+            .metadata(metadata)
+            .container(quality.container())
+            .perform(transcoded.appender().await.map_err(Error::internal)?)
+            .await
+            .map_err(|err| Error::internal(format!("failed to transcode audio: {err}")))?;
+
+        self.0
+            .objects
+            .put_stream(
+                target,
+                &mut transcoded.reader().await.map_err(Error::internal)?,
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    /// Returns tags to be embedded into audio files of the song.
+    async fn metadata(&self, song_id: SongId) -> Result<format_convert::Metadata, Error> {
+        // Models are read directly, since tags are embedded regardless of who will listen to the audio.
+        let song = song::Entity::find_by_id(song_id.0)
+            .one(&*self.0.database.conn)
+            .await?
+            .ok_or_else(Error::not_found)?;
+        let album = album::Entity::find_by_id(song.album)
+            .one(&*self.0.database.conn)
+            .await?
+            .ok_or_else(Error::not_found)?;
+        let author = user::Entity::find_by_id(song.uploader)
+            .one(&*self.0.database.conn)
+            .await?
+            .ok_or_else(Error::not_found)?
+            .nickname;
+
+        // Covers set before JPEG copies were kept have none, and are not embedded.
+        let cover = match album.cover {
+            Some(object_key) => match self
+                .0
+                .objects
+                .get_stream(crate::album::embedded_cover(&object_key))
+                .await
+            {
+                Ok(mut stream) => {
+                    let mut data = Vec::new();
+                    stream
+                        .read_to_end(&mut data)
+                        .await
+                        .map_err(Error::internal)?;
+                    Some(format_convert::Cover {
+                        mime: "image/jpeg".into(),
+                        data,
+                    })
+                }
+                Err(vinioss::Error::ObjectNotFound) => None,
+                Err(err) => return Err(err.into()),
+            },
+            None => None,
+        };
+
+        Ok(format_convert::Metadata {
+            title: song.title,
+            album: album.title,
+            author,
+            cover,
+        })
+    }
+
     /// Returns the profile of the song.
-    pub async fn profile(&self, song_id: SongId) -> Result<Profile, Error> {
-        let mut listen_policy_class = None;
+    pub async fn profile(&self, song_id: SongId, subject: &Subject) -> Result<Profile, Error> {
+        let model = song::Entity::find_by_id(song_id.0)
+            .one(&*self.0.database.conn)
+            .await?
+            .ok_or(Error::not_found())?;
+        if !model.visible_to(subject)? {
+            return Err(Error::not_found());
+        }
 
+        model.into_profile()
+    }
+
+    /// Sets the moderation status of the song.
+    pub async fn set_status(
+        &self,
+        actor: &Actor,
+        song_id: SongId,
+        status: ModerationStatus,
+        reason: Option<String>,
+    ) -> Result<(), Error> {
         let model = song::Entity::find_by_id(song_id.0)
             .one(&*self.0.database.conn)
             .await?
             .ok_or(Error::not_found())?;
 
-        let origin_quality = model.origin_quality()?;
-        if let Some(listen_policy) = model.listen_policy {
-            let listen_policy: ObjectPolicy =
-                serde_json::from_value(listen_policy).map_err(Error::internal)?;
-            listen_policy_class = Some(listen_policy.class);
-        }
+        let before = serde_json::json! {{
+            "status": model.status,
+            "rejection_reason": model.rejection_reason,
+        }};
+        let after = serde_json::json! {{
+            "status": status,
+            "rejection_reason": reason,
+        }};
+        let mut active_model = model.into_active_model();
+        active_model.status = Set(status.as_str().into());
+        active_model.rejection_reason = Set(reason);
+        active_model.update(&*self.0.database.conn).await?;
 
-        Ok(Profile {
-            song_id: SongId(model.song_id),
-            title: model.title.clone(),
-            translated_title: model.title,
-            uploader: Uid(model.uploader),
-            album: AlbumId(model.album),
-            listen_policy_class,
-            origin_quality,
-        })
+        self.0
+            .audit_log()
+            .record(
+                actor,
+                "song.set_status",
+                format!("song/{}", song_id.0),
+                before,
+                after,
+            )
+            .await
+    }
+
+    /// Returns the model of the song, if the subject is allowed to listen to it.
+    pub async fn listenable(
+        &self,
+        song_id: SongId,
+        subject: &Subject,
+    ) -> Result<song::Model, Error> {
+        let model = song::Entity::find_by_id(song_id.0)
+            .one(&*self.0.database.conn)
+            .await?
+            .ok_or(Error::not_found())?;
+        if !model.visible_to(subject)? {
+            return Err(Error::not_found());
+        }
+        match model.parse_listen_policy()? {
+            Some(listen_policy) if listen_policy.denies(subject) => {
+                Err(Error::denied_by_policy(&listen_policy.class))
+            }
+            _ => Ok(model),
+        }
     }
 }
 
@@ -149,31 +437,26 @@ impl<'b> AudioCall<'_, 'b> {
     }
 
     pub async fn invoke(self) -> Result<ObjectKey, Error> {
-        let model = song::Entity::find_by_id(self.song_id.0)
-            .one(&*self.state.database.conn)
-            .await?
-            .ok_or(Error::not_found())?;
+        let subject = self
+            .user
+            .map(Subject::from_user)
+            .unwrap_or_else(|| Subject::anon());
+        let model = self
+            .state
+            .songs()
+            .listenable(self.song_id, &subject)
+            .await?;
 
         let origin_quality = model.origin_quality()?;
 
-        if let Some(listen_policy) = model.listen_policy {
-            let listen_policy: ObjectPolicy =
-                serde_json::from_value(listen_policy).map_err(Error::internal)?;
-            let subject = self
-                .user
-                .map(Subject::from_user)
-                .unwrap_or_else(|| Subject::anon());
-            if listen_policy.denies(&subject) {
-                return Err(Error::denied_by_policy(&listen_policy.class));
-            }
-        }
+        self.state.songs().ensure_origin_ready(self.song_id).await?;
 
         if self.quality == AudioQuality::Origin || self.quality == origin_quality {
             Ok(ObjectKey(model.origin_audio))
         } else if self.quality > origin_quality {
             Err(Error::not_found())
         } else {
-            todo!()
+            self.state.songs().rendition(&model, self.quality).await
         }
     }
 }
@@ -183,6 +466,59 @@ impl song::Model {
     fn origin_quality(&self) -> Result<AudioQuality, Error> {
         AudioQuality::from_filename(&self.origin_audio)
     }
+
+    /// Returns `true` if the song is approved, or the subject is its uploader or an administrator.
+    pub fn visible_to(&self, subject: &Subject) -> Result<bool, Error> {
+        Ok(self
+            .status
+            .parse::<ModerationStatus>()?
+            .visible_to(Uid(self.uploader), subject))
+    }
+
+    /// Returns `true` if the song is visible to the subject and its listen policy allows the subject.
+    pub fn listenable_by(&self, subject: &Subject) -> Result<bool, Error> {
+        Ok(self.visible_to(subject)?
+            && self
+                .parse_listen_policy()?
+                .is_none_or(|x| x.allows(subject)))
+    }
+
+    /// Returns the listen policy of the song, if it has one.
+    pub fn parse_listen_policy(&self) -> Result<Option<ObjectPolicy>, Error> {
+        self.listen_policy
+            .clone()
+            .map(serde_json::from_value)
+            .transpose()
+            .map_err(Error::internal)
+    }
+
+    /// Converts the model into the song's profile.
+    pub fn into_profile(self) -> Result<Profile, Error> {
+        Ok(Profile {
+            song_id: SongId(self.song_id),
+            origin_quality: self.origin_quality()?,
+            audio_info: self.audio_info(),
+            listen_policy_class: self.parse_listen_policy()?.map(|x| x.class),
+            title: self.title.clone(),
+            translated_title: self.title,
+            uploader: Uid(self.uploader),
+            album: AlbumId(self.album),
+            language: self.language,
+            status: self.status.parse()?,
+            rejection_reason: self.rejection_reason,
+        })
+    }
+
+    /// Returns facts of the uploaded audio file, if it was probed.
+    fn audio_info(&self) -> Option<AudioInfo> {
+        Some(AudioInfo {
+            duration_ms: self.duration_ms? as _,
+            sample_rate: self.sample_rate? as _,
+            channels: self.channels? as _,
+            source_codec: self.source_codec.clone()?,
+            source_bitrate: self.source_bitrate? as _,
+        })
+    }
 }
 
 /// Request of uploading a song.
@@ -198,6 +534,10 @@ pub struct Upload {
     ///
     /// Note that you cannot fill [`AudioQuality::Origin`] here, since it's an ambigious quality.
     pub quality: AudioQuality,
+
+    /// Original language of the song, as a BCP 47 language tag.
+    #[serde(default)]
+    pub language: Option<String>,
 }
 
 /// Profile of a song.
@@ -221,11 +561,25 @@ pub struct Profile {
     /// Album that this song belongs to.
     pub album: AlbumId,
 
+    /// Original language of this song, if known.
+    pub language: Option<String>,
+
     /// Listen policy class of this song.
     pub listen_policy_class: Option<String>,
 
+    /// Moderation status of this song.
+    pub status: ModerationStatus,
+
+    /// Reason of rejecting this song, if it was rejected.
+    pub rejection_reason: Option<String>,
+
     /// Quality of the "origin" quality.
     pub origin_quality: AudioQuality,
+
+    /// Facts of the uploaded audio file.
+    ///
+    /// This is `None` for songs uploaded before audio files were probed.
+    pub audio_info: Option<AudioInfo>,
 }
 
 /// Representation of a Song ID.
@@ -256,6 +610,27 @@ pub enum AudioQuality {
     Origin,
 }
 impl AudioQuality {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Low => "low",
+            Self::Medium => "medium",
+            Self::High => "high",
+            Self::Lossless => "lossless",
+            Self::Origin => "origin",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Result<Self, Error> {
+        match name {
+            "low" => Ok(Self::Low),
+            "medium" => Ok(Self::Medium),
+            "high" => Ok(Self::High),
+            "lossless" => Ok(Self::Lossless),
+            "origin" => Ok(Self::Origin),
+            _ => Err(Error::internal("unrecognized audio quality")),
+        }
+    }
+
     pub fn lossy_quality(&self) -> format_convert::LossyQuality {
         match self {
             Self::Low => format_convert::LossyQuality::Low,
@@ -0,0 +1,350 @@
+//! Probing of uploaded audio files.
+
+use crate::{error::Error, local_data::temp::TempFile};
+use serde::{Deserialize, Serialize};
+use std::{f64::consts::PI, io::ErrorKind, path::Path};
+use symphonia::core::{
+    audio::SampleBuffer,
+    codecs::{self, CODEC_TYPE_NULL, CodecParameters, CodecType, DecoderOptions},
+    errors::Error as DecodeError,
+    formats::FormatOptions,
+    io::MediaSourceStream,
+    meta::MetadataOptions,
+    probe::Hint,
+};
+
+/// Codecs that are lossless.
+const LOSSLESS_CODECS: &[CodecType] = &[
+    codecs::CODEC_TYPE_FLAC,
+    codecs::CODEC_TYPE_WAVPACK,
+    codecs::CODEC_TYPE_MONKEYS_AUDIO,
+    codecs::CODEC_TYPE_ALAC,
+    codecs::CODEC_TYPE_TTA,
+    codecs::CODEC_TYPE_PCM_S32LE,
+    codecs::CODEC_TYPE_PCM_S32LE_PLANAR,
+    codecs::CODEC_TYPE_PCM_S32BE,
+    codecs::CODEC_TYPE_PCM_S32BE_PLANAR,
+    codecs::CODEC_TYPE_PCM_S24LE,
+    codecs::CODEC_TYPE_PCM_S24LE_PLANAR,
+    codecs::CODEC_TYPE_PCM_S24BE,
+    codecs::CODEC_TYPE_PCM_S24BE_PLANAR,
+    codecs::CODEC_TYPE_PCM_S16LE,
+    codecs::CODEC_TYPE_PCM_S16LE_PLANAR,
+    codecs::CODEC_TYPE_PCM_S16BE,
+    codecs::CODEC_TYPE_PCM_S16BE_PLANAR,
+    codecs::CODEC_TYPE_PCM_S8,
+    codecs::CODEC_TYPE_PCM_S8_PLANAR,
+    codecs::CODEC_TYPE_PCM_U32LE,
+    codecs::CODEC_TYPE_PCM_U32LE_PLANAR,
+    codecs::CODEC_TYPE_PCM_U32BE,
+    codecs::CODEC_TYPE_PCM_U32BE_PLANAR,
+    codecs::CODEC_TYPE_PCM_U24LE,
+    codecs::CODEC_TYPE_PCM_U24LE_PLANAR,
+    codecs::CODEC_TYPE_PCM_U24BE,
+    codecs::CODEC_TYPE_PCM_U24BE_PLANAR,
+    codecs::CODEC_TYPE_PCM_U16LE,
+    codecs::CODEC_TYPE_PCM_U16LE_PLANAR,
+    codecs::CODEC_TYPE_PCM_U16BE,
+    codecs::CODEC_TYPE_PCM_U16BE_PLANAR,
+    codecs::CODEC_TYPE_PCM_U8,
+    codecs::CODEC_TYPE_PCM_U8_PLANAR,
+    codecs::CODEC_TYPE_PCM_F32LE,
+    codecs::CODEC_TYPE_PCM_F32LE_PLANAR,
+    codecs::CODEC_TYPE_PCM_F32BE,
+    codecs::CODEC_TYPE_PCM_F32BE_PLANAR,
+    codecs::CODEC_TYPE_PCM_F64LE,
+    codecs::CODEC_TYPE_PCM_F64LE_PLANAR,
+    codecs::CODEC_TYPE_PCM_F64BE,
+    codecs::CODEC_TYPE_PCM_F64BE_PLANAR,
+];
+
+/// Number of mono samples in an analysis window.
+const WINDOW_SIZE: usize = 4096;
+
+/// Only one of this many windows is analyzed, to keep probing cheap.
+const WINDOW_STRIDE: usize = 8;
+
+/// Maximum number of analyzed windows.
+const MAX_WINDOWS: usize = 256;
+
+/// Minimum number of non-silent windows for spectral analysis to be conclusive.
+const MIN_WINDOWS: usize = 8;
+
+/// Windows with RMS lower than this are considered silent and skipped.
+const SILENCE_RMS: f64 = 1e-3;
+
+/// Frequency range, in Hz, used as the reference level of spectral analysis.
+const REFERENCE_BAND: (f64, f64) = (2000.0, 8000.0);
+
+/// Lowest frequency, in Hz, that is checked for lowpass cliffs.
+const LOWPASS_SEARCH_FROM: f64 = 15000.0;
+
+/// Width of a high-frequency band, in Hz.
+const BAND_WIDTH: f64 = 1000.0;
+
+/// Number of Goertzel probes per band.
+const PROBES_PER_BAND: usize = 4;
+
+/// Bands quieter than this level, relative to the reference band, are considered empty.
+const EMPTY_BAND_DB: f64 = -70.0;
+
+/// Minimum level drop between adjacent bands, for the drop to be considered a lowpass cliff.
+const CLIFF_DB: f64 = 25.0;
+
+/// Facts of an audio file.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AudioInfo {
+    /// Duration of the audio, in milliseconds.
+    pub duration_ms: u64,
+
+    /// Sample rate of the audio, in Hz.
+    pub sample_rate: u32,
+
+    /// Number of channels of the audio.
+    pub channels: u32,
+
+    /// Short name of the codec that the uploaded file was encoded in, e.g. `mp3` or `flac`.
+    pub source_codec: String,
+
+    /// Average bitrate of the uploaded file, in bits per second.
+    pub source_bitrate: u32,
+}
+
+/// Result of probing an audio file.
+#[derive(Debug, Clone)]
+pub struct Probe {
+    /// Facts of the audio file.
+    pub info: AudioInfo,
+
+    /// Whether the audio file was encoded in a lossless codec.
+    pub lossless: bool,
+
+    /// Frequency, in Hz, of a sharp lowpass detected in the spectrum.
+    ///
+    /// Lossy encoders like MP3 remove high frequencies, so a lossless file with a sharp lowpass is very likely to be
+    /// transcoded from a lossy one. This is a heuristic and may miss lossy encodes at very high bitrates.
+    pub lowpass: Option<u32>,
+}
+
+/// Probes an uploaded audio file. Fails with [`Error::unrecognized_audio`] if the file is not a decodable audio file,
+/// or with [`Error::audio_too_long`] if it's longer than `max_duration_secs`.
+///
+/// Files whose headers declare their duration are rejected before being decoded, and other files are rejected as soon
+/// as the decoded audio exceeds the limit.
+pub async fn probe(file: &TempFile, max_duration_secs: u64) -> Result<Probe, Error> {
+    let path = file.path().to_owned();
+    tokio::task::spawn_blocking(move || probe_blocking(&path, max_duration_secs))
+        .await
+        .map_err(Error::internal)?
+}
+
+fn probe_blocking(path: &Path, max_duration_secs: u64) -> Result<Probe, Error> {
+    let file = std::fs::File::open(path).map_err(Error::internal)?;
+    let file_size = file.metadata().map_err(Error::internal)?.len();
+    let stream = MediaSourceStream::new(Box::new(file), Default::default());
+    let mut format = symphonia::default::get_probe()
+        .format(
+            &Hint::new(),
+            stream,
+            &FormatOptions::default(),
+            &MetadataOptions::default(),
+        )
+        .map_err(|_| Error::unrecognized_audio())?
+        .format;
+    let track = format
+        .tracks()
+        .iter()
+        .find(|x| x.codec_params.codec != CODEC_TYPE_NULL)
+        .ok_or_else(Error::unrecognized_audio)?;
+    let track_id = track.id;
+    let codec = track.codec_params.codec;
+    let declared_ms = match track.codec_params {
+        CodecParameters {
+            n_frames: Some(frames),
+            time_base: Some(time_base),
+            ..
+        } => {
+            let time = time_base.calc_time(frames);
+            Some(time.seconds * 1000 + (time.frac * 1000.0) as u64)
+        }
+        CodecParameters {
+            n_frames: Some(frames),
+            sample_rate: Some(sample_rate),
+            ..
+        } => Some(frames * 1000 / sample_rate.max(1) as u64),
+        _ => None,
+    };
+    if declared_ms.is_some_and(|x| x > max_duration_secs * 1000) {
+        return Err(Error::audio_too_long(max_duration_secs));
+    }
+    let mut decoder = symphonia::default::get_codecs()
+        .make(&track.codec_params, &DecoderOptions::default())
+        .map_err(|_| Error::unrecognized_audio())?;
+    let source_codec = symphonia::default::get_codecs()
+        .get_codec(codec)
+        .map(|x| x.short_name)
+        .unwrap_or("unknown")
+        .to_string();
+
+    let mut sample_rate = track.codec_params.sample_rate.unwrap_or_default();
+    let mut channels = track.codec_params.channels.map(|x| x.count()).unwrap_or(0);
+    let mut frames = 0u64;
+    let mut analyzer = None;
+    loop {
+        let packet = match format.next_packet() {
+            Ok(x) => x,
+            Err(DecodeError::IoError(err)) if err.kind() == ErrorKind::UnexpectedEof => break,
+            Err(DecodeError::ResetRequired) => break,
+            Err(_) => return Err(Error::unrecognized_audio()),
+        };
+        if packet.track_id() != track_id {
+            continue;
+        }
+
+        let decoded = match decoder.decode(&packet) {
+            Ok(x) => x,
+            Err(DecodeError::DecodeError(_)) => continue,
+            Err(_) => return Err(Error::unrecognized_audio()),
+        };
+        sample_rate = decoded.spec().rate;
+        channels = decoded.spec().channels.count();
+        frames += decoded.frames() as u64;
+        if frames > max_duration_secs * sample_rate as u64 {
+            return Err(Error::audio_too_long(max_duration_secs));
+        }
+
+        let analyzer = analyzer.get_or_insert_with(|| Analyzer::new(sample_rate));
+        if !analyzer.is_full() {
+            let mut samples = SampleBuffer::<f32>::new(decoded.capacity() as _, *decoded.spec());
+            samples.copy_interleaved_ref(decoded);
+            analyzer.push(samples.samples(), channels);
+        }
+    }
+
+    if frames == 0 || sample_rate == 0 || channels == 0 {
+        return Err(Error::unrecognized_audio());
+    }
+    let duration_ms = frames * 1000 / sample_rate as u64;
+
+    Ok(Probe {
+        info: AudioInfo {
+            duration_ms,
+            sample_rate,
+            channels: channels as _,
+            source_codec,
+            source_bitrate: (file_size * 8 * 1000 / duration_ms.max(1)) as _,
+        },
+        lossless: LOSSLESS_CODECS.contains(&codec),
+        lowpass: analyzer.and_then(|x| x.lowpass()),
+    })
+}
+
+/// Spectral analyzer that looks for lowpass cliffs.
+struct Analyzer {
+    sample_rate: u32,
+    bands: Vec<f64>,
+    window: Vec<f64>,
+    buffer: Vec<f64>,
+    reference: f64,
+    levels: Vec<f64>,
+    seen: usize,
+    analyzed: usize,
+}
+impl Analyzer {
+    fn new(sample_rate: u32) -> Self {
+        let nyquist = sample_rate as f64 / 2.0;
+        let bands = (0..)
+            .map(|i| LOWPASS_SEARCH_FROM - BAND_WIDTH + i as f64 * BAND_WIDTH)
+            .take_while(|&x| x + BAND_WIDTH < nyquist)
+            .collect::<Vec<_>>();
+        let window = (0..WINDOW_SIZE)
+            .map(|i| 0.5 - 0.5 * (2.0 * PI * i as f64 / (WINDOW_SIZE - 1) as f64).cos())
+            .collect();
+
+        Self {
+            sample_rate,
+            levels: vec![0.0; bands.len()],
+            bands,
+            window,
+            buffer: Vec::with_capacity(WINDOW_SIZE),
+            reference: 0.0,
+            seen: 0,
+            analyzed: 0,
+        }
+    }
+
+    /// Returns `true` if enough windows have been analyzed.
+    fn is_full(&self) -> bool {
+        self.analyzed >= MAX_WINDOWS || self.bands.len() < 2
+    }
+
+    /// Feeds interleaved samples to the analyzer.
+    fn push(&mut self, samples: &[f32], channels: usize) {
+        for frame in samples.chunks_exact(channels) {
+            self.buffer
+                .push(frame.iter().map(|&x| x as f64).sum::<f64>() / channels as f64);
+            if self.buffer.len() == WINDOW_SIZE {
+                if self.seen.is_multiple_of(WINDOW_STRIDE) && !self.is_full() {
+                    self.analyze();
+                }
+                self.seen += 1;
+                self.buffer.clear();
+            }
+        }
+    }
+
+    /// Accumulates band levels of the buffered window.
+    fn analyze(&mut self) {
+        let rms = (self.buffer.iter().map(|x| x * x).sum::<f64>() / WINDOW_SIZE as f64).sqrt();
+        if rms < SILENCE_RMS {
+            return;
+        }
+        for (x, w) in self.buffer.iter_mut().zip(&self.window) {
+            *x *= w;
+        }
+
+        self.reference += self.band_power(REFERENCE_BAND.0, REFERENCE_BAND.1);
+        for i in 0..self.bands.len() {
+            let from = self.bands[i];
+            self.levels[i] += self.band_power(from, from + BAND_WIDTH);
+        }
+        self.analyzed += 1;
+    }
+
+    /// Returns the average power of probes within the frequency range.
+    fn band_power(&self, from: f64, to: f64) -> f64 {
+        let step = (to - from) / PROBES_PER_BAND as f64;
+        (0..PROBES_PER_BAND)
+            .map(|i| goertzel(&self.buffer, from + step * i as f64, self.sample_rate))
+            .sum::<f64>()
+            / PROBES_PER_BAND as f64
+    }
+
+    /// Returns the frequency of the detected lowpass cliff, if any.
+    fn lowpass(&self) -> Option<u32> {
+        if self.analyzed < MIN_WINDOWS || self.reference <= 0.0 {
+            return None;
+        }
+
+        let db = self
+            .levels
+            .iter()
+            .map(|&x| 10.0 * (x.max(f64::MIN_POSITIVE) / self.reference).log10())
+            .collect::<Vec<_>>();
+        (1..db.len())
+            .find(|&i| db[i..].iter().all(|&x| x < EMPTY_BAND_DB) && db[i - 1] - db[i] >= CLIFF_DB)
+            .map(|i| self.bands[i] as u32)
+    }
+}
+
+/// Returns power of the frequency in the samples, using the Goertzel algorithm.
+fn goertzel(samples: &[f64], frequency: f64, sample_rate: u32) -> f64 {
+    let coeff = 2.0 * (2.0 * PI * frequency / sample_rate as f64).cos();
+    let (mut s1, mut s2) = (0.0, 0.0);
+    for &x in samples {
+        let s = x + coeff * s1 - s2;
+        s2 = s1;
+        s1 = s;
+    }
+    s1 * s1 + s2 * s2 - coeff * s1 * s2
+}
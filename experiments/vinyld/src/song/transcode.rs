@@ -0,0 +1,328 @@
+//! Background transcoding of uploaded audio files.
+
+use super::{AudioQuality, SongId, Songs};
+use crate::{
+    database::entity::{song, transcode_job},
+    error::Error,
+    user::Uid,
+    util::secret,
+};
+use chrono::{DateTime, Utc};
+use sea_orm::{
+    ActiveValue::NotSet, ColumnTrait, Condition, ConnectionTrait, EntityTrait, QueryFilter,
+    QueryOrder, Set, sea_query::Expr,
+};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use tokio::time::{MissedTickBehavior, interval_at};
+use vinioss::ObjectKey;
+
+/// Maximum times that a transcode job is attempted before it's marked as failed.
+const MAX_ATTEMPTS: i32 = 3;
+
+/// Time that a claim of a transcode job lasts without being renewed. Running jobs whose lease has expired are
+/// considered interrupted, and may be claimed by other workers.
+const LEASE: Duration = Duration::from_secs(2 * 60);
+
+/// Interval between renewals of the lease of a running transcode job.
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(30);
+
+/// State of a transcode job.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JobState {
+    /// The job is waiting for a worker.
+    Pending,
+
+    /// The job is being performed by a worker.
+    Running,
+
+    /// The job has failed too many times and will not be retried.
+    Failed,
+
+    /// The job has been done successfully.
+    Done,
+}
+impl JobState {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Pending => "pending",
+            Self::Running => "running",
+            Self::Failed => "failed",
+            Self::Done => "done",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Result<Self, Error> {
+        match name {
+            "pending" => Ok(Self::Pending),
+            "running" => Ok(Self::Running),
+            "failed" => Ok(Self::Failed),
+            "done" => Ok(Self::Done),
+            _ => Err(Error::internal("unrecognized transcode job state")),
+        }
+    }
+}
+
+/// Status of a transcode job.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JobStatus {
+    /// State of the job.
+    pub state: JobState,
+
+    /// Times that the job has been attempted.
+    pub attempts: u32,
+
+    /// Error message of the last failed attempt.
+    pub error: Option<String>,
+
+    /// Time when the job was created.
+    pub created_at: DateTime<Utc>,
+
+    /// Time when the job was updated last time.
+    pub updated_at: DateTime<Utc>,
+}
+impl From<transcode_job::Model> for JobStatus {
+    fn from(model: transcode_job::Model) -> Self {
+        Self {
+            state: JobState::from_name(&model.state).unwrap_or(JobState::Failed),
+            attempts: model.attempts as _,
+            error: model.error,
+            created_at: model.created_at.and_utc(),
+            updated_at: model.updated_at.and_utc(),
+        }
+    }
+}
+
+impl Songs<'_> {
+    /// Queues a job that transcodes `source` into `target` in given quality.
+    pub(super) async fn enqueue_transcode(
+        &self,
+        conn: &impl ConnectionTrait,
+        song_id: SongId,
+        source: ObjectKey,
+        target: ObjectKey,
+        quality: AudioQuality,
+    ) -> Result<(), Error> {
+        transcode_job::Entity::insert(transcode_job::ActiveModel {
+            job_id: NotSet,
+            song: Set(song_id.0),
+            source: Set(source.0),
+            target: Set(target.0),
+            quality: Set(quality.name().into()),
+            state: Set(JobState::Pending.name().into()),
+            attempts: Set(0),
+            error: Set(None),
+            created_at: Set(Utc::now().naive_utc()),
+            updated_at: Set(Utc::now().naive_utc()),
+            claim: Set(None),
+            lease_expires_at: Set(None),
+        })
+        .exec(conn)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Returns status of the latest transcode job of the song.
+    ///
+    /// Only the uploader of the song is allowed to see this.
+    pub async fn transcode_status(&self, song_id: SongId, uid: Uid) -> Result<JobStatus, Error> {
+        let song = song::Entity::find_by_id(song_id.0)
+            .one(&*self.0.database.conn)
+            .await?
+            .ok_or_else(Error::not_found)?;
+        if song.uploader != uid.0 {
+            return Err(Error::restricted_user());
+        }
+
+        transcode_job::Entity::find()
+            .filter(transcode_job::Column::Song.eq(song_id.0))
+            .order_by_desc(transcode_job::Column::JobId)
+            .one(&*self.0.database.conn)
+            .await?
+            .ok_or_else(Error::not_found)
+            .map(JobStatus::from)
+    }
+
+    /// Succeeds if the origin audio file of the song has been transcoded.
+    ///
+    /// Fails with [`Error::audio_processing_failed`] if a transcode job of the song has failed for good, as retrying
+    /// won't help then.
+    pub(super) async fn ensure_origin_ready(&self, song_id: SongId) -> Result<(), Error> {
+        let unfinished = transcode_job::Entity::find()
+            .filter(transcode_job::Column::Song.eq(song_id.0))
+            .filter(transcode_job::Column::State.ne(JobState::Done.name()))
+            .all(&*self.0.database.conn)
+            .await?;
+
+        if unfinished
+            .iter()
+            .any(|x| x.state == JobState::Failed.name())
+        {
+            Err(Error::audio_processing_failed())
+        } else if !unfinished.is_empty() {
+            Err(Error::audio_not_ready())
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Claims a pending or interrupted transcode job and performs it. Returns `false` if there are no such jobs.
+    ///
+    /// The claim is a lease renewed while the job is running, so that jobs of workers that are gone, like ones of a
+    /// crashed instance, are claimed again once their lease expires, while jobs of live workers are left alone. Each
+    /// claim is identified by a random token, so that a worker that has lost its claim can't touch the job anymore.
+    pub async fn run_transcode_job(&self) -> Result<bool, Error> {
+        let Some(job) = transcode_job::Entity::find()
+            .filter(claimable())
+            .order_by_asc(transcode_job::Column::JobId)
+            .one(&*self.0.database.conn)
+            .await?
+        else {
+            return Ok(false);
+        };
+
+        let claim = secret::generate();
+        let claimed = transcode_job::Entity::update_many()
+            .col_expr(
+                transcode_job::Column::State,
+                Expr::value(JobState::Running.name()),
+            )
+            .col_expr(
+                transcode_job::Column::Attempts,
+                Expr::col(transcode_job::Column::Attempts).add(1),
+            )
+            .col_expr(
+                transcode_job::Column::UpdatedAt,
+                Expr::value(Utc::now().naive_utc()),
+            )
+            .col_expr(transcode_job::Column::Claim, Expr::value(&claim))
+            .col_expr(
+                transcode_job::Column::LeaseExpiresAt,
+                Expr::value(Utc::now().naive_utc() + LEASE),
+            )
+            .filter(transcode_job::Column::JobId.eq(job.job_id))
+            .filter(claimable())
+            .exec(&*self.0.database.conn)
+            .await?;
+        if claimed.rows_affected == 0 {
+            // Another worker has claimed the job before us.
+            return Ok(true);
+        }
+
+        tracing::info!(
+            "Running transcode job {} for song {}...",
+            job.job_id,
+            job.song
+        );
+        // Jobs interrupted on every attempt, like ones crashing the server, are not retried forever.
+        let result = match AudioQuality::from_name(&job.quality) {
+            Ok(_) if job.attempts >= MAX_ATTEMPTS => Err(Error::internal(
+                "transcode job was interrupted too many times",
+            )),
+            Ok(quality) => {
+                let transcode = self.transcode(
+                    SongId(job.song),
+                    ObjectKey(job.source.clone()),
+                    ObjectKey(job.target.clone()),
+                    quality,
+                );
+                tokio::select! {
+                    result = transcode => result,
+                    err = self.renew_transcode_lease(job.job_id, &claim) => Err(err),
+                }
+            }
+            Err(err) => Err(err),
+        };
+
+        let (state, error) = match result {
+            Ok(()) => (JobState::Done, None),
+            Err(err) if job.attempts + 1 >= MAX_ATTEMPTS => {
+                tracing::warn!("transcode job {} failed: {}", job.job_id, err.message);
+                (JobState::Failed, Some(err.message))
+            }
+            Err(err) => {
+                tracing::info!(
+                    "transcode job {} failed, it will be retried: {}",
+                    job.job_id,
+                    err.message
+                );
+                (JobState::Pending, Some(err.message))
+            }
+        };
+
+        let released = transcode_job::Entity::update_many()
+            .col_expr(transcode_job::Column::State, Expr::value(state.name()))
+            .col_expr(transcode_job::Column::Error, Expr::value(error))
+            .col_expr(
+                transcode_job::Column::UpdatedAt,
+                Expr::value(Utc::now().naive_utc()),
+            )
+            .col_expr(transcode_job::Column::Claim, Expr::value(None::<String>))
+            .col_expr(
+                transcode_job::Column::LeaseExpiresAt,
+                Expr::value(None::<chrono::NaiveDateTime>),
+            )
+            .filter(transcode_job::Column::JobId.eq(job.job_id))
+            .filter(transcode_job::Column::Claim.eq(&claim))
+            .exec(&*self.0.database.conn)
+            .await?;
+        if released.rows_affected == 0 {
+            // The lease has expired and the job has been claimed by another worker, which owns it now.
+            tracing::warn!("lost the claim of transcode job {}", job.job_id);
+            return Ok(true);
+        }
+
+        // The source is of no use once the job is over, whether it's done or failed for good.
+        if matches!(state, JobState::Done | JobState::Failed)
+            && let Err(err) = self.0.objects.remove(ObjectKey(job.source.clone())).await
+        {
+            tracing::warn!("failed to remove unprocessed audio `{}`: {err}", job.source);
+        }
+
+        Ok(true)
+    }
+
+    /// Renews the lease of a claimed transcode job periodically. This never returns unless renewing fails, in which
+    /// case the job must be abandoned, since other workers may have claimed it.
+    async fn renew_transcode_lease(&self, job_id: i64, claim: &str) -> Error {
+        let mut interval = interval_at(
+            tokio::time::Instant::now() + HEARTBEAT_INTERVAL,
+            HEARTBEAT_INTERVAL,
+        );
+        interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        loop {
+            interval.tick().await;
+
+            let renewed = transcode_job::Entity::update_many()
+                .col_expr(
+                    transcode_job::Column::LeaseExpiresAt,
+                    Expr::value(Utc::now().naive_utc() + LEASE),
+                )
+                .filter(transcode_job::Column::JobId.eq(job_id))
+                .filter(transcode_job::Column::Claim.eq(claim))
+                .exec(&*self.0.database.conn)
+                .await;
+            match renewed {
+                Ok(x) if x.rows_affected == 0 => {
+                    return Error::internal("transcode job was claimed by another worker");
+                }
+                Ok(_) => (),
+                Err(err) => return err.into(),
+            }
+        }
+    }
+}
+
+/// Returns a condition matching transcode jobs that may be claimed, which are pending ones and running ones whose
+/// lease has expired.
+fn claimable() -> Condition {
+    Condition::any()
+        .add(transcode_job::Column::State.eq(JobState::Pending.name()))
+        .add(
+            Condition::all()
+                .add(transcode_job::Column::State.eq(JobState::Running.name()))
+                .add(transcode_job::Column::LeaseExpiresAt.lt(Utc::now().naive_utc())),
+        )
+}
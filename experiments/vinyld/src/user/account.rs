@@ -0,0 +1,410 @@
+//! Self-service of accounts: editing profiles, claiming usernames, changing passwords and deleting accounts.
+
+use super::{
+    NicknameLike, Uid, UsernameLike, Users,
+    auth::password::{self, PasswordLike},
+    login::UserProber,
+    session::Session,
+};
+use crate::{
+    audit::Actor,
+    database::entity::{
+        album, playlist, session, song, song_comment, song_rendition, transcode_job, user,
+        user_auth_password,
+    },
+    error::Error,
+    song::transcode::JobState,
+};
+use axum::body::Bytes;
+use chrono::{NaiveDate, Utc};
+use sea_orm::{
+    ActiveValue::Set,
+    ColumnTrait, Condition, EntityTrait, QueryFilter, SqlErr, TransactionTrait,
+    sea_query::{Expr, OnConflict},
+};
+use serde::{Deserialize, Serialize};
+use std::io::Cursor;
+use vinioss::ObjectKey;
+use vinutie::{
+    def_verify,
+    verify::{VerifyExt, verify_option},
+};
+
+impl Users<'_> {
+    /// Replaces the editable fields of the user's profile.
+    pub async fn update_profile(&self, session: &Session, edit: ProfileEdit) -> Result<(), Error> {
+        session.will_manage_sessions()?;
+        let uid = session.user.uid;
+        edit.nickname.verify_by::<NicknameLike>()?;
+        verify_option::<GenderLike, _>(edit.gender.as_deref())?;
+        verify_option::<DateOfBirthLike, _>(edit.date_of_birth.as_ref())?;
+        verify_option::<CountryLike, _>(edit.country.as_deref())?;
+        verify_option::<CityLike, _>(edit.city.as_deref())?;
+        verify_option::<SignatureLike, _>(edit.signature.as_deref())?;
+
+        let nickname = user::Entity::find_by_id(uid.0)
+            .one(&*self.0.database.conn)
+            .await?
+            .ok_or_else(Error::not_found)?
+            .nickname;
+        let nickname_changed = nickname != edit.nickname;
+
+        let updated = user::Entity::update_many()
+            .set(user::ActiveModel {
+                nickname: Set(edit.nickname),
+                gender: Set(edit.gender),
+                date_of_birth: Set(edit.date_of_birth),
+                country: Set(edit.country),
+                city: Set(edit.city),
+                signature: Set(edit.signature),
+                ..Default::default()
+            })
+            .filter(user::Column::Uid.eq(uid.0))
+            .exec(&*self.0.database.conn)
+            .await?;
+        if updated.rows_affected == 0 {
+            return Err(Error::not_found());
+        }
+        // The nickname is embedded into audio files of the user's songs as their artist.
+        if nickname_changed {
+            self.0
+                .songs()
+                .invalidate_renditions(Condition::all().add(song::Column::Uploader.eq(uid.0)))
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    /// Replaces the username generated on registration with one of the user's choice.
+    ///
+    /// This can only be done once, as chosen usernames are never replaced.
+    pub async fn claim_username(&self, session: &Session, username: &str) -> Result<(), Error> {
+        session.will_manage_sessions()?;
+        username.verify_by::<UsernameLike>()?;
+        if self.find_by_username(username).await.is_ok() {
+            return Err(Error::username_conflict());
+        }
+
+        let updated = user::Entity::update_many()
+            .col_expr(user::Column::Username, username.into())
+            .col_expr(user::Column::UsernameGenerated, false.into())
+            .filter(user::Column::Uid.eq(session.user.uid.0))
+            .filter(user::Column::UsernameGenerated.eq(true))
+            .exec(&*self.0.database.conn)
+            .await
+            .map_err(|err| match err.sql_err() {
+                Some(SqlErr::UniqueConstraintViolation(_)) => Error::username_conflict(),
+                _ => Error::internal(err),
+            })?;
+        if updated.rows_affected == 0 {
+            return Err(Error::bad_request("the username has been chosen already"));
+        }
+
+        Ok(())
+    }
+
+    /// Sets an image of the user's profile, replacing the old one.
+    pub async fn set_profile_image(
+        &self,
+        session: &Session,
+        kind: ProfileImage,
+        image: Bytes,
+    ) -> Result<(), Error> {
+        session.will_manage_sessions()?;
+        let mut buffer = Cursor::new(crate::util::image::recompress(image, kind.resolution())?);
+
+        let object_key = ObjectKey(vinutie::random::filename(kind.prefix(), "avif"));
+        self.0
+            .objects
+            .put_stream(object_key.clone(), &mut buffer)
+            .await?;
+
+        self.replace_profile_image(session.user.uid, kind, Some(object_key))
+            .await
+    }
+
+    /// Removes an image of the user's profile.
+    pub async fn remove_profile_image(
+        &self,
+        session: &Session,
+        kind: ProfileImage,
+    ) -> Result<(), Error> {
+        session.will_manage_sessions()?;
+        self.replace_profile_image(session.user.uid, kind, None)
+            .await
+    }
+
+    /// Changes the password of the user, and revokes every other session of the user.
+    ///
+    /// The current password is required if the user has one. Users who registered without a password can set one
+    /// this way.
+    pub async fn change_password(
+        &self,
+        session: &Session,
+        current_password: Option<&str>,
+        new_password: &str,
+    ) -> Result<(), Error> {
+        session.will_manage_sessions()?;
+        self.confirm_password(session, current_password).await?;
+        new_password.verify_by::<PasswordLike>()?;
+        let hashed_password = password::hash(new_password)?;
+
+        let txn = self.0.database.conn.begin().await?;
+        user_auth_password::Entity::insert(user_auth_password::ActiveModel {
+            uid: Set(session.user.uid.0),
+            password: Set(hashed_password),
+        })
+        .on_conflict(
+            OnConflict::column(user_auth_password::Column::Uid)
+                .update_column(user_auth_password::Column::Password)
+                .to_owned(),
+        )
+        .exec(&txn)
+        .await?;
+        session::Entity::delete_many()
+            .filter(session::Column::Uid.eq(session.user.uid.0))
+            .filter(session::Column::Numeral.ne(session.numeral.0))
+            .exec(&txn)
+            .await?;
+        txn.commit().await?;
+
+        Ok(())
+    }
+
+    /// Deletes the account of the session's user, along with everything the user owns.
+    ///
+    /// The password is required if the user has one, and so is a second factor if TOTP is enabled. Songs can't live
+    /// outside albums, so this fails with [`Error::shared_album_conflict`] while albums of the user contain songs
+    /// uploaded by others.
+    pub async fn delete_account(
+        &self,
+        session: &Session,
+        password: Option<&str>,
+        code: Option<&str>,
+    ) -> Result<(), Error> {
+        session.will_manage_sessions()?;
+        let uid = session.user.uid;
+        self.confirm_password(session, password).await?;
+        if self.totp_enabled(uid).await? {
+            self.check_second_factor(uid, code.ok_or_else(Error::invalid_verification_code)?)
+                .await?;
+        }
+
+        let conn = &*self.0.database.conn;
+        let model = user::Entity::find_by_id(uid.0)
+            .one(conn)
+            .await?
+            .ok_or_else(Error::not_found)?;
+        let albums = album::Entity::find()
+            .filter(album::Column::Uploader.eq(uid.0))
+            .all(conn)
+            .await?;
+        let mut shared_albums = song::Entity::find()
+            .filter(song::Column::Album.is_in(albums.iter().map(|x| x.album_id)))
+            .filter(song::Column::Uploader.ne(uid.0))
+            .all(conn)
+            .await?
+            .into_iter()
+            .map(|x| x.album)
+            .collect::<Vec<_>>();
+        if !shared_albums.is_empty() {
+            shared_albums.sort_unstable();
+            shared_albums.dedup();
+            return Err(Error::shared_album_conflict(&shared_albums));
+        }
+        let songs = song::Entity::find()
+            .filter(song::Column::Uploader.eq(uid.0))
+            .all(conn)
+            .await?;
+        let song_ids = songs.iter().map(|x| x.song_id).collect::<Vec<_>>();
+        let renditions = song_rendition::Entity::find()
+            .filter(song_rendition::Column::Song.is_in(song_ids.clone()))
+            .all(conn)
+            .await?;
+        // Sources of finished and failed jobs have been removed by the workers already.
+        let jobs = transcode_job::Entity::find()
+            .filter(transcode_job::Column::Song.is_in(song_ids.clone()))
+            .filter(
+                transcode_job::Column::State
+                    .is_not_in([JobState::Done.name(), JobState::Failed.name()]),
+            )
+            .all(conn)
+            .await?;
+        let comments = song_comment::Entity::find()
+            .filter(
+                Condition::any()
+                    .add(song_comment::Column::CreatedBy.eq(uid.0))
+                    .add(song_comment::Column::Song.is_in(song_ids.clone())),
+            )
+            .all(conn)
+            .await?;
+        let playlists = playlist::Entity::find()
+            .filter(playlist::Column::Owner.eq(uid.0))
+            .all(conn)
+            .await?;
+
+        let keys = [model.avatar, model.banner_image]
+            .into_iter()
+            .flatten()
+            .chain(
+                albums
+                    .into_iter()
+                    .filter_map(|x| x.cover)
+                    .flat_map(|x| [crate::album::embedded_cover(&x).0, x]),
+            )
+            .chain(songs.into_iter().map(|x| x.origin_audio))
+            .chain(renditions.into_iter().map(|x| x.audio))
+            .chain(jobs.into_iter().map(|x| x.source))
+            .chain(comments.into_iter().filter_map(|x| x.attachments).flatten())
+            .chain(playlists.into_iter().filter_map(|x| x.cover))
+            .collect::<Vec<_>>();
+
+        // Everything else of the user goes away with these rows by cascading.
+        let txn = conn.begin().await?;
+        song::Entity::delete_many()
+            .filter(song::Column::SongId.is_in(song_ids))
+            .exec(&txn)
+            .await?;
+        album::Entity::delete_many()
+            .filter(album::Column::Uploader.eq(uid.0))
+            .exec(&txn)
+            .await?;
+        session::Entity::delete_many()
+            .filter(session::Column::Uid.eq(uid.0))
+            .exec(&txn)
+            .await?;
+        user::Entity::delete_by_id(uid.0).exec(&txn).await?;
+        txn.commit().await?;
+
+        // The account is gone at this point, so leftover objects are only logged rather than failing the request.
+        for key in keys {
+            if let Err(err) = self.0.objects.remove(ObjectKey(key.clone())).await {
+                tracing::warn!(
+                    "failed to remove object `{key}` of deleted user {}: {err}",
+                    uid.0
+                );
+            }
+        }
+
+        self.0
+            .audit_log()
+            .record(
+                &Actor::from(session),
+                "user.delete_account",
+                format!("user/{}", uid.0),
+                serde_json::json!(session.user.username),
+                serde_json::Value::Null,
+            )
+            .await
+    }
+
+    /// Points an image of the user's profile to another object, and removes the old object.
+    async fn replace_profile_image(
+        &self,
+        uid: Uid,
+        kind: ProfileImage,
+        object_key: Option<ObjectKey>,
+    ) -> Result<(), Error> {
+        let model = user::Entity::find_by_id(uid.0)
+            .one(&*self.0.database.conn)
+            .await?
+            .ok_or_else(Error::not_found)?;
+        let before = match kind {
+            ProfileImage::Avatar => model.avatar,
+            ProfileImage::Banner => model.banner_image,
+        };
+
+        user::Entity::update_many()
+            .col_expr(kind.column(), Expr::value(object_key.map(|x| x.0)))
+            .filter(user::Column::Uid.eq(uid.0))
+            .exec(&*self.0.database.conn)
+            .await?;
+
+        if let Some(before) = before {
+            self.0.objects.remove(ObjectKey(before)).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Checks the password of the session's user, if the user has one.
+    async fn confirm_password(
+        &self,
+        session: &Session,
+        password: Option<&str>,
+    ) -> Result<(), Error> {
+        let has_password = user_auth_password::Entity::find_by_id(session.user.uid.0)
+            .one(&*self.0.database.conn)
+            .await?
+            .is_some();
+        if !has_password {
+            return Ok(());
+        }
+
+        let prober = UserProber::Username(session.user.username.clone());
+        self.check_password(&prober, password.ok_or_else(Error::login_incorrect)?)
+            .await
+            .map(|_| ())
+    }
+}
+
+/// Editable fields of a profile. Unset fields are cleared.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProfileEdit {
+    pub nickname: String,
+    pub gender: Option<String>,
+    pub date_of_birth: Option<NaiveDate>,
+
+    /// Country code in ISO 3166-1 alpha-2, like `JP`.
+    pub country: Option<String>,
+    pub city: Option<String>,
+    pub signature: Option<String>,
+}
+
+/// Kind of images on a profile.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ProfileImage {
+    Avatar,
+    Banner,
+}
+impl ProfileImage {
+    fn column(self) -> user::Column {
+        match self {
+            Self::Avatar => user::Column::Avatar,
+            Self::Banner => user::Column::BannerImage,
+        }
+    }
+
+    /// Resolution of the image in the HQ quality.
+    fn resolution(self) -> (u32, u32) {
+        match self {
+            Self::Avatar => (512, 512),
+            Self::Banner => (1500, 500),
+        }
+    }
+
+    fn prefix(self) -> &'static str {
+        match self {
+            Self::Avatar => "avatar",
+            Self::Banner => "banner",
+        }
+    }
+}
+
+def_verify!(pub GenderLike<str>(err: Error = Error::invalid_profile("gender")) = |x: &str| {
+    x.chars().count() <= 24 && !x.chars().any(char::is_control)
+});
+def_verify!(pub DateOfBirthLike<NaiveDate>(err: Error = Error::invalid_profile("date_of_birth")) = |x: &NaiveDate| {
+    NaiveDate::from_ymd_opt(1900, 1, 1).is_some_and(|y| *x >= y) && *x <= Utc::now().date_naive()
+});
+def_verify!(pub CountryLike<str>(err: Error = Error::invalid_profile("country")) = |x: &str| {
+    x.len() == 2 && x.bytes().all(|y| y.is_ascii_uppercase())
+});
+def_verify!(pub CityLike<str>(err: Error = Error::invalid_profile("city")) = |x: &str| {
+    x.chars().count() <= 64 && !x.chars().any(char::is_control)
+});
+def_verify!(pub SignatureLike<str>(err: Error = Error::invalid_profile("signature")) = |x: &str| {
+    x.chars().count() <= 256
+});
@@ -0,0 +1,221 @@
+//! Administration of users.
+
+use super::{Ban, BanCode, Uid, Users, group};
+use crate::{
+    audit::Actor,
+    database::{entity::user, search},
+    error::Error,
+    util::page::{Page, PageQuery},
+};
+use chrono::{DateTime, TimeDelta, Utc};
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, EntityTrait, IntoActiveModel, QueryFilter, QueryOrder,
+    QuerySelect, Set,
+};
+use serde::{Deserialize, Serialize};
+use vinutie::verify::VerifyExt;
+
+impl Users<'_> {
+    /// Searches users whose username or nickname contains the query.
+    pub async fn search(&self, query: &str, page: &PageQuery) -> Result<Page<Details>, Error> {
+        let limit = page.limit();
+        let mut select = user::Entity::find()
+            .filter(search::contains(&["username", "nickname"], query))
+            .order_by_asc(user::Column::Uid)
+            .limit(limit + 1);
+        if let Some(position) = page.position()? {
+            select = select.filter(user::Column::Uid.gt(position));
+        }
+
+        let mut models = select.all(&*self.0.database.conn).await?;
+        let next_position = if models.len() as u64 > limit {
+            models.truncate(limit as _);
+            models.last().map(|x| x.uid)
+        } else {
+            None
+        };
+
+        let items = models
+            .into_iter()
+            .map(Details::try_from)
+            .collect::<Result<_, _>>()?;
+        Ok(Page::new(items, next_position))
+    }
+
+    /// Returns details of a user, including what is hidden from the public profile.
+    pub async fn details(&self, uid: Uid) -> Result<Details, Error> {
+        self.find_model(uid).await?.try_into()
+    }
+
+    /// Bans a user for a duration.
+    ///
+    /// The ban replaces any existing one of the user.
+    pub async fn ban(&self, actor: &Actor, uid: Uid, request: BanRequest) -> Result<(), Error> {
+        if actor.uid == uid {
+            return Err(Error::bad_request("cannot ban oneself"));
+        }
+        let duration = TimeDelta::try_seconds(request.duration_secs)
+            .filter(|x| *x > TimeDelta::zero())
+            .ok_or_else(|| Error::bad_request("invalid ban duration"))?;
+
+        let begin_time = Utc::now();
+        let ban = Ban {
+            code: request.code,
+            begin_time,
+            end_time: begin_time + duration,
+            message: request.message,
+        };
+        let payload = serde_json::to_value(&ban).map_err(Error::internal)?;
+
+        let model = self.find_model(uid).await?;
+        let before = model.banned.clone().unwrap_or_default();
+        let mut active_model = model.into_active_model();
+        active_model.banned = Set(Some(payload.clone()));
+        active_model.update(&*self.0.database.conn).await?;
+
+        self.0
+            .audit_log()
+            .record(
+                actor,
+                "user.ban",
+                format!("user/{}", uid.0),
+                before,
+                payload,
+            )
+            .await
+    }
+
+    /// Lifts the ban of a user. Nothing is changed if the user is not banned.
+    pub async fn unban(&self, actor: &Actor, uid: Uid) -> Result<(), Error> {
+        let model = self.find_model(uid).await?;
+        let Some(before) = model.banned.clone() else {
+            return Ok(());
+        };
+        let mut active_model = model.into_active_model();
+        active_model.banned = Set(None);
+        active_model.update(&*self.0.database.conn).await?;
+
+        self.0
+            .audit_log()
+            .record(
+                actor,
+                "user.unban",
+                format!("user/{}", uid.0),
+                before,
+                serde_json::Value::Null,
+            )
+            .await
+    }
+
+    /// Adds a user to a group. Nothing is changed if the user is already in the group.
+    pub async fn add_group(&self, actor: &Actor, uid: Uid, group: &str) -> Result<(), Error> {
+        group.verify_by::<group::GroupLike>()?;
+
+        let model = self.find_model(uid).await?;
+        if model.groups.iter().any(|x| x == group) {
+            return Ok(());
+        }
+        let before = model.groups.clone();
+        let mut groups = model.groups.clone();
+        groups.push(group.into());
+        let mut active_model = model.into_active_model();
+        active_model.groups = Set(groups.clone());
+        active_model.update(&*self.0.database.conn).await?;
+
+        self.0
+            .audit_log()
+            .record(
+                actor,
+                "user.add_group",
+                format!("user/{}", uid.0),
+                serde_json::json!(before),
+                serde_json::json!(groups),
+            )
+            .await
+    }
+
+    /// Removes a user from a group. Nothing is changed if the user is not in the group.
+    ///
+    /// Administrators cannot remove themselves from the wheel group, so that the server is never left without one
+    /// by accident.
+    pub async fn remove_group(&self, actor: &Actor, uid: Uid, group: &str) -> Result<(), Error> {
+        if actor.uid == uid && group == group::WHEEL {
+            return Err(Error::bad_request(
+                "cannot leave the wheel group by oneself",
+            ));
+        }
+
+        let model = self.find_model(uid).await?;
+        if !model.groups.iter().any(|x| x == group) {
+            return Ok(());
+        }
+        let before = model.groups.clone();
+        let groups: Vec<_> = before.iter().filter(|x| *x != group).cloned().collect();
+        let mut active_model = model.into_active_model();
+        active_model.groups = Set(groups.clone());
+        active_model.update(&*self.0.database.conn).await?;
+
+        self.0
+            .audit_log()
+            .record(
+                actor,
+                "user.remove_group",
+                format!("user/{}", uid.0),
+                serde_json::json!(before),
+                serde_json::json!(groups),
+            )
+            .await
+    }
+
+    async fn find_model(&self, uid: Uid) -> Result<user::Model, Error> {
+        user::Entity::find_by_id(uid.0)
+            .one(&*self.0.database.conn)
+            .await?
+            .ok_or_else(Error::not_found)
+    }
+}
+
+/// Request of banning a user.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BanRequest {
+    pub code: BanCode,
+
+    /// Length of the ban in seconds.
+    pub duration_secs: i64,
+
+    /// Message shown to the user while the ban is in effect.
+    pub message: String,
+}
+
+/// Details of a user, as seen by administrators.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Details {
+    pub uid: Uid,
+    pub username: String,
+    pub nickname: String,
+    pub groups: Vec<String>,
+
+    /// The ban of the user. This may be an expired one that hasn't been cleared yet.
+    pub ban: Option<Ban>,
+    pub created_at: DateTime<Utc>,
+    pub last_logined_at: DateTime<Utc>,
+}
+impl TryFrom<user::Model> for Details {
+    type Error = Error;
+
+    fn try_from(model: user::Model) -> Result<Self, Self::Error> {
+        Ok(Self {
+            uid: Uid(model.uid),
+            username: model.username,
+            nickname: model.nickname,
+            groups: model.groups,
+            ban: model
+                .banned
+                .map(serde_json::from_value)
+                .transpose()
+                .map_err(Error::internal)?,
+            created_at: model.created_at.and_utc(),
+            last_logined_at: model.last_logined_at.and_utc(),
+        })
+    }
+}
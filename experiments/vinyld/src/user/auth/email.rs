@@ -0,0 +1,353 @@
+//! Email addresses of users, their verification and password reset through them.
+
+use super::password::{self, PasswordLike};
+use crate::{
+    app_settings::SiteName,
+    database::entity::{email_code, session, user_auth_email, user_auth_password},
+    error::Error,
+    mail::Mail,
+    user::{Uid, Users},
+    util::secret,
+};
+use chrono::Utc;
+use lettre::Address;
+use sea_orm::{
+    ActiveValue::Set, ColumnTrait, ConnectionTrait, EntityTrait, QueryFilter, TransactionTrait,
+    sea_query::OnConflict,
+};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use vinutie::verify::VerifyExt;
+
+/// Time before a verification code expires.
+const CODE_VALID_IN: Duration = Duration::from_secs(30 * 60);
+
+impl Users<'_> {
+    /// Sends a code proving the ownership of an address, which is required to register with it when email
+    /// verification is mandatory.
+    pub async fn send_register_code(&self, email: &str) -> Result<(), Error> {
+        let email = normalize(email)?;
+        self.ensure_email_unused(&*self.0.database.conn, &email, None)
+            .await?;
+
+        self.send_code(&email, None, Purpose::Register).await
+    }
+
+    /// Returns the email address of the user.
+    pub async fn email(&self, uid: Uid) -> Result<Option<EmailStatus>, Error> {
+        Ok(user_auth_email::Entity::find_by_id(uid.0)
+            .one(&*self.0.database.conn)
+            .await?
+            .map(|x| EmailStatus {
+                email: x.email,
+                verified: x.verified_at.is_some(),
+            }))
+    }
+
+    /// Sets the email address of the user, and sends a code for verifying it.
+    ///
+    /// The address stays unverified until [`Users::verify_email`] is called with the code.
+    pub async fn set_email(&self, uid: Uid, email: &str) -> Result<(), Error> {
+        let email = normalize(email)?;
+        self.store_email(&*self.0.database.conn, uid, &email)
+            .await?;
+
+        self.send_verify_code(uid, &email).await
+    }
+
+    /// Verifies the email address of the user with a code sent by [`Users::set_email`].
+    pub async fn verify_email(&self, uid: Uid, code: &str) -> Result<(), Error> {
+        let model = user_auth_email::Entity::find_by_id(uid.0)
+            .one(&*self.0.database.conn)
+            .await?
+            .ok_or_else(Error::invalid_verification_code)?;
+        let txn = self.0.database.conn.begin().await?;
+        let code = self
+            .consume_email_code(&txn, code, Purpose::Verify, &model.email)
+            .await?;
+        if code.uid != Some(uid.0) {
+            return Err(Error::invalid_verification_code());
+        }
+        self.mark_verified(&txn, uid, &model.email).await?;
+        txn.commit().await?;
+
+        Ok(())
+    }
+
+    /// Sends a code for resetting the password to the address, if it's verified by a user.
+    ///
+    /// Nothing is sent if the address is unknown, and no error is returned in that case, so that this can't be used
+    /// to find out who uses an address.
+    pub async fn send_reset_code(&self, email: &str) -> Result<(), Error> {
+        let email = normalize(email)?;
+        let Some(model) = user_auth_email::Entity::find()
+            .filter(user_auth_email::Column::Email.eq(&email))
+            .filter(user_auth_email::Column::VerifiedAt.is_not_null())
+            .one(&*self.0.database.conn)
+            .await?
+        else {
+            return Ok(());
+        };
+
+        self.send_code(&email, Some(Uid(model.uid)), Purpose::Reset)
+            .await
+    }
+
+    /// Resets the password of the user owning the address with a code sent by [`Users::send_reset_code`].
+    ///
+    /// Every session of the user is revoked. The code is kept if the new password is rejected, and is refused if the
+    /// user no longer owns the address.
+    pub async fn reset_password(
+        &self,
+        email: &str,
+        code: &str,
+        new_password: &str,
+    ) -> Result<(), Error> {
+        let email = normalize(email)?;
+        new_password.verify_by::<PasswordLike>()?;
+        let hashed_password = password::hash(new_password)?;
+
+        let txn = self.0.database.conn.begin().await?;
+        let code = self
+            .consume_email_code(&txn, code, Purpose::Reset, &email)
+            .await?;
+        let uid = code.uid.ok_or_else(Error::invalid_verification_code)?;
+        let owned = user_auth_email::Entity::find_by_id(uid)
+            .filter(user_auth_email::Column::Email.eq(&email))
+            .filter(user_auth_email::Column::VerifiedAt.is_not_null())
+            .one(&txn)
+            .await?;
+        if owned.is_none() {
+            return Err(Error::invalid_verification_code());
+        }
+
+        user_auth_password::Entity::insert(user_auth_password::ActiveModel {
+            uid: Set(uid),
+            password: Set(hashed_password),
+        })
+        .on_conflict(
+            OnConflict::column(user_auth_password::Column::Uid)
+                .update_column(user_auth_password::Column::Password)
+                .to_owned(),
+        )
+        .exec(&txn)
+        .await?;
+        session::Entity::delete_many()
+            .filter(session::Column::Uid.eq(uid))
+            .exec(&txn)
+            .await?;
+        txn.commit().await?;
+
+        Ok(())
+    }
+
+    /// Consumes a code sent by [`Users::send_register_code`].
+    pub(in crate::user) async fn consume_register_code(
+        &self,
+        conn: &impl ConnectionTrait,
+        email: &str,
+        code: &str,
+    ) -> Result<(), Error> {
+        self.consume_email_code(conn, code, Purpose::Register, email)
+            .await
+            .map(|_| ())
+    }
+
+    /// Records the address as the unverified address of the user.
+    pub(in crate::user) async fn store_email(
+        &self,
+        conn: &impl ConnectionTrait,
+        uid: Uid,
+        email: &str,
+    ) -> Result<(), Error> {
+        self.ensure_email_unused(conn, email, Some(uid)).await?;
+
+        user_auth_email::Entity::insert(user_auth_email::ActiveModel {
+            uid: Set(uid.0),
+            email: Set(email.into()),
+            verified_at: Set(None),
+        })
+        .on_conflict(
+            OnConflict::column(user_auth_email::Column::Uid)
+                .update_columns([
+                    user_auth_email::Column::Email,
+                    user_auth_email::Column::VerifiedAt,
+                ])
+                .to_owned(),
+        )
+        .exec(conn)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Sends a code for verifying the address stored by [`Users::store_email`].
+    pub(in crate::user) async fn send_verify_code(
+        &self,
+        uid: Uid,
+        email: &str,
+    ) -> Result<(), Error> {
+        self.send_code(email, Some(uid), Purpose::Verify).await
+    }
+
+    /// Records the address as verified by the user, dropping unverified claims of it by others.
+    ///
+    /// This writes several rows, so `conn` should be a transaction.
+    pub(in crate::user) async fn mark_verified(
+        &self,
+        conn: &impl ConnectionTrait,
+        uid: Uid,
+        email: &str,
+    ) -> Result<(), Error> {
+        self.ensure_email_unused(conn, email, Some(uid)).await?;
+
+        user_auth_email::Entity::delete_many()
+            .filter(user_auth_email::Column::Email.eq(email))
+            .filter(user_auth_email::Column::Uid.ne(uid.0))
+            .exec(conn)
+            .await?;
+        user_auth_email::Entity::insert(user_auth_email::ActiveModel {
+            uid: Set(uid.0),
+            email: Set(email.into()),
+            verified_at: Set(Some(Utc::now().naive_utc())),
+        })
+        .on_conflict(
+            OnConflict::column(user_auth_email::Column::Uid)
+                .update_columns([
+                    user_auth_email::Column::Email,
+                    user_auth_email::Column::VerifiedAt,
+                ])
+                .to_owned(),
+        )
+        .exec(conn)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Fails if the address is verified by a user other than `uid`.
+    async fn ensure_email_unused(
+        &self,
+        conn: &impl ConnectionTrait,
+        email: &str,
+        uid: Option<Uid>,
+    ) -> Result<(), Error> {
+        let owner = user_auth_email::Entity::find()
+            .filter(user_auth_email::Column::Email.eq(email))
+            .filter(user_auth_email::Column::VerifiedAt.is_not_null())
+            .one(conn)
+            .await?;
+        match owner {
+            Some(owner) if Some(Uid(owner.uid)) != uid => Err(Error::email_conflict()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Generates a code and sends it to the address.
+    async fn send_code(
+        &self,
+        email: &str,
+        uid: Option<Uid>,
+        purpose: Purpose,
+    ) -> Result<(), Error> {
+        let code = secret::generate();
+        email_code::Entity::insert(email_code::ActiveModel {
+            code_hash: Set(secret::hash(&code)),
+            email: Set(email.into()),
+            uid: Set(uid.map(|x| x.0)),
+            purpose: Set(purpose.as_str().into()),
+            expires_at: Set(Utc::now().naive_utc() + CODE_VALID_IN),
+        })
+        .exec(&*self.0.database.conn)
+        .await?;
+
+        let site_name = self.0.app_settings().get::<SiteName>().await;
+        self.0
+            .mailer
+            .send(Mail {
+                to: email.into(),
+                subject: format!("{} for {site_name}", purpose.subject()),
+                body: format!(
+                    "Your code is:\n\n    {code}\n\nIt expires in {} minutes. If you didn't request this, you can \
+                     safely ignore this mail.\n",
+                    CODE_VALID_IN.as_secs() / 60,
+                ),
+            })
+            .await
+    }
+
+    /// Consumes a code of the purpose sent to the address. A code can only be consumed once, even if it's wrong,
+    /// unless `conn` is a transaction that is rolled back.
+    async fn consume_email_code(
+        &self,
+        conn: &impl ConnectionTrait,
+        code: &str,
+        purpose: Purpose,
+        email: &str,
+    ) -> Result<email_code::Model, Error> {
+        let code_hash = secret::hash(code);
+        let model = email_code::Entity::find_by_id(&code_hash)
+            .one(conn)
+            .await?
+            .ok_or_else(Error::invalid_verification_code)?;
+        let deleted = email_code::Entity::delete_by_id(&code_hash)
+            .exec(conn)
+            .await?;
+
+        if deleted.rows_affected == 0
+            || model.expires_at.and_utc() < Utc::now()
+            || model.purpose != purpose.as_str()
+            || model.email != email
+        {
+            return Err(Error::invalid_verification_code());
+        }
+
+        Ok(model)
+    }
+}
+
+/// Email address of a user.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EmailStatus {
+    pub email: String,
+    pub verified: bool,
+}
+
+/// Purpose of a verification code.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Purpose {
+    /// Proving the ownership of an address before registering with it.
+    Register,
+
+    /// Verifying the address of an existing user.
+    Verify,
+
+    /// Resetting the password of the user owning the address.
+    Reset,
+}
+impl Purpose {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Register => "register",
+            Self::Verify => "verify",
+            Self::Reset => "reset",
+        }
+    }
+
+    fn subject(self) -> &'static str {
+        match self {
+            Self::Register => "Registration code",
+            Self::Verify => "Verification code",
+            Self::Reset => "Password reset code",
+        }
+    }
+}
+
+/// Validates an email address, and returns its lowercase form for comparison.
+pub fn normalize(email: &str) -> Result<String, Error> {
+    email
+        .trim()
+        .parse::<Address>()
+        .map(|x| x.to_string().to_lowercase())
+        .map_err(|_| Error::invalid_email())
+}
@@ -1,3 +1,5 @@
 pub mod email;
 pub mod password;
 pub mod phone;
+pub mod totp;
+pub mod webauthn;
@@ -1,5 +1,5 @@
 use crate::error::Error;
-use argon2::Argon2;
+use argon2::{Argon2, PasswordHasher, password_hash::SaltString};
 use vinutie::def_verify;
 
 def_verify!(pub PasswordLike<str>(err: Error = Error::invalid_password()) = |x: &str| x.len() <= 128);
@@ -11,3 +11,14 @@ pub fn hasher() -> Argon2<'static> {
         argon2::Params::default(),
     )
 }
+
+/// Hashes a password for storage.
+pub fn hash(password: &str) -> Result<String, Error> {
+    hasher()
+        .hash_password(
+            password.as_bytes(),
+            &SaltString::generate(&mut argon2::password_hash::rand_core::OsRng),
+        )
+        .map(|x| x.to_string())
+        .map_err(|_| Error::invalid_password())
+}
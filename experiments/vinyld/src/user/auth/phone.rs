@@ -0,0 +1,428 @@
+//! Phone numbers of users, their verification and login through codes sent by SMS.
+//!
+//! Codes sent by SMS are short enough to be typed on a phone, so unlike email codes they can be guessed. Each code
+//! therefore accepts a limited number of attempts, and only the latest code sent to a number for a purpose is
+//! accepted. Sending codes to a number is rate limited as well, since every message costs money.
+
+use crate::{
+    app_settings::SiteName,
+    database::entity::{phone_code, user_auth_phone},
+    error::Error,
+    user::{Uid, Users, login::LoginStep},
+    util::secret,
+};
+use chrono::Utc;
+use rand::Rng;
+use sea_orm::{
+    ActiveValue::{NotSet, Set},
+    ColumnTrait, ConnectionTrait, EntityTrait, PaginatorTrait, QueryFilter, QueryOrder,
+    TransactionTrait,
+    sea_query::{Expr, OnConflict},
+};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// Time before a verification code expires.
+const CODE_VALID_IN: Duration = Duration::from_secs(10 * 60);
+
+/// Number of wrong attempts after which a code is no longer accepted.
+const MAX_ATTEMPTS: i32 = 5;
+
+/// Minimum time between two codes sent to a number.
+const RESEND_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Maximum number of codes sent to a number in [`RATE_WINDOW`].
+const MAX_CODES_IN_WINDOW: u64 = 5;
+
+/// Window of counting codes sent to a number.
+const RATE_WINDOW: Duration = Duration::from_secs(60 * 60);
+
+impl Users<'_> {
+    /// Sends a code proving the ownership of a number, which can be used to register with it as verified.
+    pub async fn send_phone_register_code(&self, phone: &str) -> Result<(), Error> {
+        let phone = normalize(phone)?;
+        self.ensure_phone_unused(&*self.0.database.conn, &phone, None)
+            .await?;
+
+        self.send_phone_code(&phone, None, Purpose::Register).await
+    }
+
+    /// Returns the phone number of the user.
+    pub async fn phone(&self, uid: Uid) -> Result<Option<PhoneStatus>, Error> {
+        Ok(user_auth_phone::Entity::find_by_id(uid.0)
+            .one(&*self.0.database.conn)
+            .await?
+            .map(|x| PhoneStatus {
+                phone: x.phone,
+                verified: x.verified_at.is_some(),
+            }))
+    }
+
+    /// Sets the phone number of the user, and sends a code for verifying it.
+    ///
+    /// The number stays unverified until [`Users::verify_phone`] is called with the code.
+    pub async fn set_phone(&self, uid: Uid, phone: &str) -> Result<(), Error> {
+        let phone = normalize(phone)?;
+        self.store_phone(&*self.0.database.conn, uid, &phone)
+            .await?;
+
+        self.send_phone_verify_code(uid, &phone).await
+    }
+
+    /// Verifies the phone number of the user with a code sent by [`Users::set_phone`].
+    pub async fn verify_phone(&self, uid: Uid, code: &str) -> Result<(), Error> {
+        let model = user_auth_phone::Entity::find_by_id(uid.0)
+            .one(&*self.0.database.conn)
+            .await?
+            .ok_or_else(Error::invalid_verification_code)?;
+        let code = self
+            .check_phone_code(code, Purpose::Verify, &model.phone)
+            .await?;
+        if code.uid != Some(uid.0) {
+            return Err(Error::invalid_verification_code());
+        }
+
+        let txn = self.0.database.conn.begin().await?;
+        self.take_phone_code(&txn, &code).await?;
+        self.mark_phone_verified(&txn, uid, &model.phone).await?;
+        txn.commit().await?;
+
+        Ok(())
+    }
+
+    /// Sends a code for logging in to the number, if it's verified by a user.
+    ///
+    /// Nothing is sent if the number is unknown, and no error is returned in that case, so that this can't be used
+    /// to find out who uses a number.
+    pub async fn send_login_code(&self, phone: &str) -> Result<(), Error> {
+        let phone = normalize(phone)?;
+        let Some(model) = user_auth_phone::Entity::find()
+            .filter(user_auth_phone::Column::Phone.eq(&phone))
+            .filter(user_auth_phone::Column::VerifiedAt.is_not_null())
+            .one(&*self.0.database.conn)
+            .await?
+        else {
+            return Ok(());
+        };
+
+        self.send_phone_code(&phone, Some(Uid(model.uid)), Purpose::Login)
+            .await
+    }
+
+    /// Logs in as the user owning the number with a code sent by [`Users::send_login_code`].
+    ///
+    /// Like logging in with a password, this requires a second factor if the user has enabled two-factor
+    /// authentication.
+    pub async fn login_via_phone_code(
+        &self,
+        phone: &str,
+        code: &str,
+        source: String,
+    ) -> Result<LoginStep, Error> {
+        let phone = normalize(phone)?;
+        let code = self
+            .consume_phone_code(code, Purpose::Login, &phone)
+            .await?;
+        let uid = Uid(code.uid.ok_or_else(Error::invalid_verification_code)?);
+        let user = self.find_by_uid(uid).await?;
+
+        self.start_login(&user, source).await
+    }
+
+    /// Checks a code sent by [`Users::send_phone_register_code`], without consuming it. The code must be consumed by
+    /// [`Users::take_phone_code`] once it's used.
+    pub(in crate::user) async fn check_phone_register_code(
+        &self,
+        phone: &str,
+        code: &str,
+    ) -> Result<phone_code::Model, Error> {
+        self.check_phone_code(code, Purpose::Register, phone).await
+    }
+
+    /// Consumes a code checked before. This fails if the code was consumed concurrently.
+    pub(in crate::user) async fn take_phone_code(
+        &self,
+        conn: &impl ConnectionTrait,
+        model: &phone_code::Model,
+    ) -> Result<(), Error> {
+        let deleted = phone_code::Entity::delete_by_id(model.phone_code_id)
+            .exec(conn)
+            .await?;
+        if deleted.rows_affected == 0 {
+            return Err(Error::invalid_verification_code());
+        }
+
+        Ok(())
+    }
+
+    /// Records the number as the unverified number of the user.
+    pub(in crate::user) async fn store_phone(
+        &self,
+        conn: &impl ConnectionTrait,
+        uid: Uid,
+        phone: &str,
+    ) -> Result<(), Error> {
+        self.ensure_phone_unused(conn, phone, Some(uid)).await?;
+
+        user_auth_phone::Entity::insert(user_auth_phone::ActiveModel {
+            uid: Set(uid.0),
+            phone: Set(phone.into()),
+            verified_at: Set(None),
+        })
+        .on_conflict(
+            OnConflict::column(user_auth_phone::Column::Uid)
+                .update_columns([
+                    user_auth_phone::Column::Phone,
+                    user_auth_phone::Column::VerifiedAt,
+                ])
+                .to_owned(),
+        )
+        .exec(conn)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Sends a code for verifying the number stored by [`Users::store_phone`].
+    pub(in crate::user) async fn send_phone_verify_code(
+        &self,
+        uid: Uid,
+        phone: &str,
+    ) -> Result<(), Error> {
+        self.send_phone_code(phone, Some(uid), Purpose::Verify)
+            .await
+    }
+
+    /// Records the number as verified by the user, dropping unverified claims of it by others.
+    ///
+    /// This writes several rows, so `conn` should be a transaction.
+    pub(in crate::user) async fn mark_phone_verified(
+        &self,
+        conn: &impl ConnectionTrait,
+        uid: Uid,
+        phone: &str,
+    ) -> Result<(), Error> {
+        self.ensure_phone_unused(conn, phone, Some(uid)).await?;
+
+        user_auth_phone::Entity::delete_many()
+            .filter(user_auth_phone::Column::Phone.eq(phone))
+            .filter(user_auth_phone::Column::Uid.ne(uid.0))
+            .exec(conn)
+            .await?;
+        user_auth_phone::Entity::insert(user_auth_phone::ActiveModel {
+            uid: Set(uid.0),
+            phone: Set(phone.into()),
+            verified_at: Set(Some(Utc::now().naive_utc())),
+        })
+        .on_conflict(
+            OnConflict::column(user_auth_phone::Column::Uid)
+                .update_columns([
+                    user_auth_phone::Column::Phone,
+                    user_auth_phone::Column::VerifiedAt,
+                ])
+                .to_owned(),
+        )
+        .exec(conn)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Fails if the number is verified by a user other than `uid`.
+    async fn ensure_phone_unused(
+        &self,
+        conn: &impl ConnectionTrait,
+        phone: &str,
+        uid: Option<Uid>,
+    ) -> Result<(), Error> {
+        let owner = user_auth_phone::Entity::find()
+            .filter(user_auth_phone::Column::Phone.eq(phone))
+            .filter(user_auth_phone::Column::VerifiedAt.is_not_null())
+            .one(conn)
+            .await?;
+        match owner {
+            Some(owner) if Some(Uid(owner.uid)) != uid => Err(Error::phone_conflict()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Generates a code and sends it to the number, unless too many codes have been sent to it recently.
+    async fn send_phone_code(
+        &self,
+        phone: &str,
+        uid: Option<Uid>,
+        purpose: Purpose,
+    ) -> Result<(), Error> {
+        let now = Utc::now().naive_utc();
+        let window_start = now - RATE_WINDOW;
+
+        // Codes older than the window are neither accepted nor counted anymore.
+        phone_code::Entity::delete_many()
+            .filter(phone_code::Column::Phone.eq(phone))
+            .filter(phone_code::Column::CreatedAt.lt(window_start))
+            .exec(&*self.0.database.conn)
+            .await?;
+        let recent = phone_code::Entity::find().filter(phone_code::Column::Phone.eq(phone));
+        if let Some(latest) = recent
+            .clone()
+            .order_by_desc(phone_code::Column::CreatedAt)
+            .one(&*self.0.database.conn)
+            .await?
+        {
+            let elapsed = (now - latest.created_at).to_std().unwrap_or_default();
+            if elapsed < RESEND_INTERVAL {
+                return Err(Error::too_many_requests(
+                    (RESEND_INTERVAL - elapsed).as_secs() + 1,
+                ));
+            }
+        }
+        if recent.clone().count(&*self.0.database.conn).await? >= MAX_CODES_IN_WINDOW {
+            let oldest = recent
+                .order_by_asc(phone_code::Column::CreatedAt)
+                .one(&*self.0.database.conn)
+                .await?
+                .map_or(now, |x| x.created_at);
+            let elapsed = (now - oldest).to_std().unwrap_or_default();
+            return Err(Error::too_many_requests(
+                RATE_WINDOW.saturating_sub(elapsed).as_secs() + 1,
+            ));
+        }
+
+        let code = format!("{:06}", rand::rng().random_range(0..1_000_000));
+        phone_code::Entity::insert(phone_code::ActiveModel {
+            phone_code_id: NotSet,
+            phone: Set(phone.into()),
+            uid: Set(uid.map(|x| x.0)),
+            purpose: Set(purpose.as_str().into()),
+            code_hash: Set(secret::hash(&code)),
+            attempts: Set(0),
+            created_at: Set(now),
+            expires_at: Set(now + CODE_VALID_IN),
+        })
+        .exec(&*self.0.database.conn)
+        .await?;
+
+        let site_name = self.0.app_settings().get::<SiteName>().await;
+        self.0
+            .texter
+            .send(
+                phone,
+                &format!(
+                    "{code} is your {site_name} {} code. It expires in {} minutes.",
+                    purpose.noun(),
+                    CODE_VALID_IN.as_secs() / 60,
+                ),
+            )
+            .await
+    }
+
+    /// Consumes the latest code of the purpose sent to the number.
+    ///
+    /// A wrong code counts as an attempt, and a correct code can only be consumed once.
+    async fn consume_phone_code(
+        &self,
+        code: &str,
+        purpose: Purpose,
+        phone: &str,
+    ) -> Result<phone_code::Model, Error> {
+        let model = self.check_phone_code(code, purpose, phone).await?;
+        self.take_phone_code(&*self.0.database.conn, &model).await?;
+
+        Ok(model)
+    }
+
+    /// Checks the latest code of the purpose sent to the number, without consuming it.
+    ///
+    /// A wrong code counts as an attempt. Attempts are counted outside of transactions, so that rolling back can't
+    /// undo them.
+    async fn check_phone_code(
+        &self,
+        code: &str,
+        purpose: Purpose,
+        phone: &str,
+    ) -> Result<phone_code::Model, Error> {
+        let model = phone_code::Entity::find()
+            .filter(phone_code::Column::Phone.eq(phone))
+            .filter(phone_code::Column::Purpose.eq(purpose.as_str()))
+            .order_by_desc(phone_code::Column::CreatedAt)
+            .one(&*self.0.database.conn)
+            .await?
+            .ok_or_else(Error::invalid_verification_code)?;
+        if model.expires_at.and_utc() < Utc::now() {
+            return Err(Error::invalid_verification_code());
+        }
+
+        // Counting the attempt before comparing the code, so that concurrent guesses can't exceed the limit.
+        let counted = phone_code::Entity::update_many()
+            .col_expr(
+                phone_code::Column::Attempts,
+                Expr::col(phone_code::Column::Attempts).add(1),
+            )
+            .filter(phone_code::Column::PhoneCodeId.eq(model.phone_code_id))
+            .filter(phone_code::Column::Attempts.lt(MAX_ATTEMPTS))
+            .exec(&*self.0.database.conn)
+            .await?;
+        if counted.rows_affected == 0 || secret::hash(code) != model.code_hash {
+            return Err(Error::invalid_verification_code());
+        }
+
+        Ok(model)
+    }
+}
+
+/// Phone number of a user.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PhoneStatus {
+    pub phone: String,
+    pub verified: bool,
+}
+
+/// Purpose of a verification code.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Purpose {
+    /// Proving the ownership of a number before registering with it.
+    Register,
+
+    /// Verifying the number of an existing user.
+    Verify,
+
+    /// Logging in as the user owning the number.
+    Login,
+}
+impl Purpose {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Register => "register",
+            Self::Verify => "verify",
+            Self::Login => "login",
+        }
+    }
+
+    fn noun(self) -> &'static str {
+        match self {
+            Self::Register => "registration",
+            Self::Verify => "verification",
+            Self::Login => "login",
+        }
+    }
+}
+
+/// Validates a phone number in E.164 format, like `+14155550123`, and returns its canonical form for comparison.
+///
+/// Spaces, dashes, dots and parentheses that are commonly used for grouping digits are ignored.
+pub fn normalize(phone: &str) -> Result<String, Error> {
+    let phone = phone
+        .trim()
+        .chars()
+        .filter(|x| !matches!(x, ' ' | '-' | '.' | '(' | ')'))
+        .collect::<String>();
+    let digits = phone.strip_prefix('+').ok_or_else(Error::invalid_phone)?;
+    if !(8..=15).contains(&digits.len())
+        || !digits.bytes().all(|x| x.is_ascii_digit())
+        || digits.starts_with('0')
+    {
+        return Err(Error::invalid_phone());
+    }
+
+    Ok(phone)
+}
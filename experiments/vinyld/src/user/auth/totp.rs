@@ -0,0 +1,350 @@
+//! Time-based one-time passwords as a second factor, and recovery codes that stand in for them.
+//!
+//! Codes are generated as defined in RFC 6238, with the parameters that every authenticator app supports: HMAC-SHA1,
+//! 6 digits and 30-second steps.
+
+use crate::{
+    app_settings::{MandatoryWheelTwoFactor, RateLimits, SiteName},
+    database::entity::{recovery_code, user_auth_totp},
+    error::{Error, ErrorCode},
+    user::{Uid, Users, group::WHEEL, session::Session},
+    util::secret,
+};
+use base32::Alphabet;
+use chrono::Utc;
+use hmac::{Hmac, Mac};
+use rand::{Rng, RngCore, distr::Alphanumeric};
+use sea_orm::{
+    ActiveValue::Set, ColumnTrait, EntityTrait, PaginatorTrait, QueryFilter, TransactionTrait,
+    sea_query::OnConflict,
+};
+use serde::{Deserialize, Serialize};
+use sha1::Sha1;
+use url::Url;
+
+/// Length of a step in seconds.
+const STEP: i64 = 30;
+
+/// Number of digits of a code.
+const DIGITS: u32 = 6;
+
+/// Number of steps before and after the current one whose codes are accepted, tolerating clock drift.
+const SKEW: i64 = 1;
+
+/// Number of recovery codes generated at once.
+const RECOVERY_CODES: usize = 10;
+
+/// Alphabet of TOTP secrets, as expected by authenticator apps.
+const SECRET_ALPHABET: Alphabet = Alphabet::Rfc4648 { padding: false };
+
+impl Users<'_> {
+    /// Starts enrolling a TOTP authenticator, replacing an unconfirmed enrollment if there is one.
+    ///
+    /// Two-factor authentication is not enabled until [`Users::confirm_totp`] is called with a code generated from
+    /// the returned secret.
+    pub async fn enroll_totp(&self, session: &Session) -> Result<TotpEnrollment, Error> {
+        session.will_manage_sessions()?;
+        let uid = session.user.uid;
+        if self.totp_enabled(uid).await? {
+            return Err(Error::bad_request("TOTP is already enabled"));
+        }
+
+        let mut data = [0u8; 20];
+        rand::rng().fill_bytes(&mut data);
+        let secret = base32::encode(SECRET_ALPHABET, &data);
+        user_auth_totp::Entity::insert(user_auth_totp::ActiveModel {
+            uid: Set(uid.0),
+            secret: Set(secret.clone()),
+            confirmed_at: Set(None),
+            last_step: Set(0),
+        })
+        .on_conflict(
+            OnConflict::column(user_auth_totp::Column::Uid)
+                .update_columns([
+                    user_auth_totp::Column::Secret,
+                    user_auth_totp::Column::ConfirmedAt,
+                    user_auth_totp::Column::LastStep,
+                ])
+                .to_owned(),
+        )
+        .exec(&*self.0.database.conn)
+        .await?;
+
+        let issuer = self.0.app_settings().get::<SiteName>().await;
+        let mut uri = Url::parse("otpauth://totp/").unwrap();
+        uri.set_path(&format!("{issuer}:{}", session.user.username));
+        uri.query_pairs_mut()
+            .append_pair("secret", &secret)
+            .append_pair("issuer", &issuer)
+            .append_pair("algorithm", "SHA1")
+            .append_pair("digits", &DIGITS.to_string())
+            .append_pair("period", &STEP.to_string());
+
+        Ok(TotpEnrollment { secret, uri })
+    }
+
+    /// Confirms the TOTP enrollment with a code, enabling two-factor authentication.
+    ///
+    /// Returns the recovery codes of the user, which are only shown once.
+    pub async fn confirm_totp(&self, session: &Session, code: &str) -> Result<Vec<String>, Error> {
+        session.will_manage_sessions()?;
+        let uid = session.user.uid;
+        let model = user_auth_totp::Entity::find_by_id(uid.0)
+            .one(&*self.0.database.conn)
+            .await?
+            .ok_or_else(|| Error::bad_request("no pending TOTP enrollment"))?;
+        if model.confirmed_at.is_some() {
+            return Err(Error::bad_request("TOTP is already enabled"));
+        }
+        self.verify_totp(&model, code).await?;
+
+        user_auth_totp::Entity::update_many()
+            .col_expr(
+                user_auth_totp::Column::ConfirmedAt,
+                Utc::now().naive_utc().into(),
+            )
+            .filter(user_auth_totp::Column::Uid.eq(uid.0))
+            .exec(&*self.0.database.conn)
+            .await?;
+
+        self.replace_recovery_codes(uid).await
+    }
+
+    /// Returns whether the user has enabled two-factor authentication, and how many recovery codes are left.
+    pub async fn two_factor_status(&self, uid: Uid) -> Result<TwoFactorStatus, Error> {
+        Ok(TwoFactorStatus {
+            enabled: self.two_factor_enabled(uid).await?,
+            totp: self.totp_enabled(uid).await?,
+            recovery_codes_left: recovery_code::Entity::find()
+                .filter(recovery_code::Column::Uid.eq(uid.0))
+                .count(&*self.0.database.conn)
+                .await?,
+        })
+    }
+
+    /// Replaces the recovery codes of the user after checking a second factor.
+    pub async fn regenerate_recovery_codes(
+        &self,
+        session: &Session,
+        code: &str,
+    ) -> Result<Vec<String>, Error> {
+        session.will_manage_sessions()?;
+        self.check_second_factor(session.user.uid, code).await?;
+
+        self.replace_recovery_codes(session.user.uid).await
+    }
+
+    /// Disables TOTP and drops the recovery codes after checking a second factor.
+    ///
+    /// Members of the wheel group cannot do this while [`MandatoryWheelTwoFactor`] is set, unless they have registered
+    /// a WebAuthn credential as another second factor.
+    pub async fn disable_totp(&self, session: &Session, code: &str) -> Result<(), Error> {
+        session.will_manage_sessions()?;
+        let uid = session.user.uid;
+        if session.user.groups.iter().any(|x| x == WHEEL)
+            && self.0.app_settings().get::<MandatoryWheelTwoFactor>().await
+            && !self.webauthn_enabled(uid).await?
+        {
+            return Err(Error::restricted_user());
+        }
+        self.check_second_factor(uid, code).await?;
+
+        let txn = self.0.database.conn.begin().await?;
+        user_auth_totp::Entity::delete_by_id(uid.0)
+            .exec(&txn)
+            .await?;
+        recovery_code::Entity::delete_many()
+            .filter(recovery_code::Column::Uid.eq(uid.0))
+            .exec(&txn)
+            .await?;
+        txn.commit().await?;
+
+        Ok(())
+    }
+
+    /// Returns whether the user has enabled TOTP.
+    pub(in crate::user) async fn totp_enabled(&self, uid: Uid) -> Result<bool, Error> {
+        Ok(user_auth_totp::Entity::find_by_id(uid.0)
+            .one(&*self.0.database.conn)
+            .await?
+            .is_some_and(|x| x.confirmed_at.is_some()))
+    }
+
+    /// Checks a second factor of the user, which is either a TOTP code or a recovery code.
+    ///
+    /// A recovery code is consumed once it's accepted. Checks are locked out for a while after too many wrong codes,
+    /// as configured by [`RateLimits`], wherever the code is entered.
+    pub async fn check_second_factor(&self, uid: Uid, code: &str) -> Result<(), Error> {
+        let key = format!("second_factor/{}", uid.0);
+        self.0.rate_limiter.check_lockout(&key)?;
+
+        match self.verify_second_factor(uid, code).await {
+            Ok(()) => {
+                self.0.rate_limiter.clear_failures(&key);
+                Ok(())
+            }
+            Err(err) if err.code == ErrorCode::INVALID_VERIFICATION_CODE => {
+                let limits = self.0.app_settings().get::<RateLimits>().await;
+                self.0.rate_limiter.record_failure(&key, &limits.lockout);
+                Err(err)
+            }
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Verifies a second factor of the user, consuming it if it's a recovery code.
+    async fn verify_second_factor(&self, uid: Uid, code: &str) -> Result<(), Error> {
+        let code = code
+            .chars()
+            .filter(|x| !matches!(x, ' ' | '-'))
+            .collect::<String>()
+            .to_ascii_lowercase();
+
+        if code.len() == DIGITS as usize && code.bytes().all(|x| x.is_ascii_digit()) {
+            let model = user_auth_totp::Entity::find_by_id(uid.0)
+                .one(&*self.0.database.conn)
+                .await?
+                .filter(|x| x.confirmed_at.is_some())
+                .ok_or_else(Error::invalid_verification_code)?;
+            return self.verify_totp(&model, &code).await;
+        }
+
+        let deleted = recovery_code::Entity::delete_many()
+            .filter(recovery_code::Column::CodeHash.eq(secret::hash(&code)))
+            .filter(recovery_code::Column::Uid.eq(uid.0))
+            .exec(&*self.0.database.conn)
+            .await?;
+        if deleted.rows_affected == 0 {
+            return Err(Error::invalid_verification_code());
+        }
+
+        Ok(())
+    }
+
+    /// Verifies a TOTP code, and records its step so that it can't be used again.
+    async fn verify_totp(&self, model: &user_auth_totp::Model, code: &str) -> Result<(), Error> {
+        let secret = base32::decode(SECRET_ALPHABET, &model.secret)
+            .ok_or_else(|| Error::internal("malformed TOTP secret"))?;
+        let current = Utc::now().timestamp() / STEP;
+        let step = (current - SKEW..=current + SKEW)
+            .filter(|&x| x > model.last_step)
+            .find(|&x| totp(&secret, x) == code)
+            .ok_or_else(Error::invalid_verification_code)?;
+
+        // Recording the step only if it's newer, so that concurrent uses of the same code can't both succeed.
+        let updated = user_auth_totp::Entity::update_many()
+            .col_expr(user_auth_totp::Column::LastStep, step.into())
+            .filter(user_auth_totp::Column::Uid.eq(model.uid))
+            .filter(user_auth_totp::Column::LastStep.lt(step))
+            .exec(&*self.0.database.conn)
+            .await?;
+        if updated.rows_affected == 0 {
+            return Err(Error::invalid_verification_code());
+        }
+
+        Ok(())
+    }
+
+    /// Generates new recovery codes of the user, invalidating the old ones.
+    async fn replace_recovery_codes(&self, uid: Uid) -> Result<Vec<String>, Error> {
+        let codes = (0..RECOVERY_CODES)
+            .map(|_| {
+                let code = rand::rng()
+                    .sample_iter(Alphanumeric)
+                    .take(10)
+                    .map(|x| (x as char).to_ascii_lowercase())
+                    .collect::<String>();
+                format!("{}-{}", &code[..5], &code[5..])
+            })
+            .collect::<Vec<_>>();
+
+        let txn = self.0.database.conn.begin().await?;
+        recovery_code::Entity::delete_many()
+            .filter(recovery_code::Column::Uid.eq(uid.0))
+            .exec(&txn)
+            .await?;
+        recovery_code::Entity::insert_many(codes.iter().map(|x| recovery_code::ActiveModel {
+            code_hash: Set(secret::hash(&x.replace('-', ""))),
+            uid: Set(uid.0),
+        }))
+        .exec(&txn)
+        .await?;
+        txn.commit().await?;
+
+        Ok(codes)
+    }
+}
+
+/// A started TOTP enrollment.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TotpEnrollment {
+    /// The secret in base32, for entering into an authenticator app by hand.
+    pub secret: String,
+
+    /// The `otpauth://` provisioning URI, usually shown as a QR code.
+    pub uri: Url,
+}
+
+/// Two-factor authentication status of a user.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TwoFactorStatus {
+    /// Whether a second factor is required on login, by either TOTP or WebAuthn.
+    pub enabled: bool,
+    pub totp: bool,
+    pub recovery_codes_left: u64,
+}
+
+/// Returns the TOTP code of the step, as defined in RFC 6238.
+fn totp(secret: &[u8], step: i64) -> String {
+    format!(
+        "{:0width$}",
+        hotp(secret, step as u64),
+        width = DIGITS as usize
+    )
+}
+
+/// Computes an HOTP value, as defined in RFC 4226.
+fn hotp(secret: &[u8], counter: u64) -> u32 {
+    let mut mac = Hmac::<Sha1>::new_from_slice(secret).expect("HMAC accepts keys of any length");
+    mac.update(&counter.to_be_bytes());
+    let digest = mac.finalize().into_bytes();
+
+    let offset = (digest[digest.len() - 1] & 0x0f) as usize;
+    let binary = u32::from_be_bytes(digest[offset..offset + 4].try_into().unwrap()) & 0x7fff_ffff;
+    binary % 10u32.pow(DIGITS)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Secret of the test vectors of RFC 4226 and RFC 6238, for HMAC-SHA1.
+    const SECRET: &[u8] = b"12345678901234567890";
+
+    #[test]
+    fn hotp_matches_rfc4226() {
+        // Appendix D of RFC 4226.
+        let expected = [
+            755224, 287082, 359152, 969429, 338314, 254676, 287922, 162583, 399871, 520489,
+        ];
+        for (counter, value) in expected.into_iter().enumerate() {
+            assert_eq!(hotp(SECRET, counter as u64), value, "counter {counter}");
+        }
+    }
+
+    #[test]
+    fn totp_matches_rfc6238() {
+        // Appendix B of RFC 6238, keeping the last 6 of the 8 digits.
+        let expected = [
+            (59, "287082"),
+            (1111111109, "081804"),
+            (1111111111, "050471"),
+            (1234567890, "005924"),
+            (2000000000, "279037"),
+            (20000000000, "353130"),
+        ];
+        for (time, code) in expected {
+            assert_eq!(totp(SECRET, time / STEP), code, "time {time}");
+        }
+    }
+}
@@ -0,0 +1,761 @@
+//! WebAuthn credentials, like passkeys and security keys, used for logging in without a password and as a second
+//! factor.
+//!
+//! Only what passkeys need from WebAuthn Level 2 is implemented. Credentials must use ES256, and attestation statements
+//! are not verified, so authenticators are trusted as much as the users registering them.
+
+use crate::{
+    app_settings::{MandatoryWheelTwoFactor, SiteName},
+    database::entity::{login_challenge, user_auth_webauthn, webauthn_challenge},
+    error::Error,
+    user::{
+        Uid, Users,
+        group::WHEEL,
+        login::LoginResponse,
+        session::{Permissions, Session},
+    },
+    util::secret,
+};
+use anyhow::anyhow;
+use base64::{
+    Engine,
+    prelude::{BASE64_STANDARD, BASE64_URL_SAFE_NO_PAD},
+};
+use chrono::{DateTime, Utc};
+use coset::{
+    AsCborValue, CoseKey, KeyType, Label,
+    iana::{self, EnumI64},
+};
+use p256::ecdsa::{Signature, VerifyingKey, signature::Verifier};
+use sea_orm::{
+    ActiveValue::Set, ColumnTrait, EntityTrait, PaginatorTrait, QueryFilter, QueryOrder,
+    sea_query::OnConflict,
+};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::time::Duration;
+use url::Url;
+
+/// Time before a ceremony challenge expires.
+const CHALLENGE_VALID_IN: Duration = Duration::from_secs(5 * 60);
+
+/// Maximum number of credentials of a user.
+const MAX_CREDENTIALS: u64 = 16;
+
+/// Flag of authenticator data indicating that the user was present.
+const FLAG_USER_PRESENT: u8 = 0x01;
+
+/// Flag of authenticator data indicating that the user was verified, like by a PIN or biometrics.
+const FLAG_USER_VERIFIED: u8 = 0x04;
+
+/// Flag of authenticator data indicating that attested credential data is included.
+const FLAG_ATTESTED: u8 = 0x40;
+
+/// The relying party that credentials are scoped to.
+#[derive(Debug, Clone)]
+pub struct RelyingParty {
+    /// The RP ID, which is a registrable domain of the origin.
+    pub id: String,
+
+    /// Origin of the web app performing ceremonies, like `https://vinyl.example.com`.
+    pub origin: String,
+}
+impl RelyingParty {
+    /// Creates a relying party of the web app at the origin. The RP ID defaults to the host of the origin.
+    pub fn new(origin: &Url, id: Option<String>) -> anyhow::Result<Self> {
+        let host = origin
+            .host_str()
+            .ok_or_else(|| anyhow!("WebAuthn origin `{origin}` has no host"))?;
+        let id = id.unwrap_or_else(|| host.into());
+        if host != id && !host.ends_with(&format!(".{id}")) {
+            return Err(anyhow!(
+                "WebAuthn RP ID `{id}` is not a registrable domain of `{host}`"
+            ));
+        }
+
+        Ok(Self {
+            id,
+            origin: origin.origin().ascii_serialization(),
+        })
+    }
+}
+
+impl Users<'_> {
+    /// Starts registering a credential of the user, returning options for `navigator.credentials.create()`.
+    pub async fn begin_webauthn_registration(
+        &self,
+        session: &Session,
+    ) -> Result<CreationOptions, Error> {
+        session.will_manage_sessions()?;
+        let rp = self.relying_party()?;
+        let uid = session.user.uid;
+        let existing = self.credentials_of(uid).await?;
+        if existing.len() as u64 >= MAX_CREDENTIALS {
+            return Err(Error::bad_request("too many WebAuthn credentials"));
+        }
+
+        let challenge = self
+            .issue_webauthn_challenge(Some(uid), Purpose::Register, None)
+            .await?;
+        Ok(CreationOptions {
+            challenge,
+            rp: RpEntity {
+                id: rp.id.clone(),
+                name: self.0.app_settings().get::<SiteName>().await,
+            },
+            user: UserEntity {
+                id: user_handle(uid),
+                name: session.user.username.clone(),
+                display_name: session.user.username.clone(),
+            },
+            pub_key_cred_params: vec![CredentialParameters {
+                kind: "public-key",
+                alg: iana::Algorithm::ES256.to_i64(),
+            }],
+            timeout: CHALLENGE_VALID_IN.as_millis() as _,
+            exclude_credentials: existing.iter().map(CredentialDescriptor::from).collect(),
+            authenticator_selection: AuthenticatorSelection {
+                resident_key: "preferred",
+                user_verification: "preferred",
+            },
+            attestation: "none",
+        })
+    }
+
+    /// Finishes registering a credential of the user with the response of `navigator.credentials.create()`.
+    pub async fn finish_webauthn_registration(
+        &self,
+        session: &Session,
+        name: String,
+        credential: &RegistrationCredential,
+    ) -> Result<Credential, Error> {
+        session.will_manage_sessions()?;
+        let rp = self.relying_party()?;
+        let uid = session.user.uid;
+        if name.trim().is_empty() || name.chars().count() > 64 {
+            return Err(Error::bad_request("malformed credential name"));
+        }
+
+        let client_data =
+            ClientData::parse(&credential.response.client_data_json, rp, "webauthn.create")?;
+        let challenge = self
+            .consume_webauthn_challenge(&client_data.challenge, Purpose::Register)
+            .await?;
+        if challenge.uid != Some(uid.0) {
+            return Err(Error::bad_request("invalid WebAuthn challenge"));
+        }
+
+        let auth_data = AuthenticatorData::parse(
+            &attested_auth_data(&credential.response.attestation_object)?,
+            rp,
+        )?;
+        let Some((credential_id, public_key)) = auth_data.attested else {
+            return Err(Error::bad_request("missing attested credential data"));
+        };
+        let credential_id = BASE64_URL_SAFE_NO_PAD.encode(credential_id);
+        if credential_id != credential.id {
+            return Err(Error::bad_request("mismatched credential ID"));
+        }
+
+        let now = Utc::now().naive_utc();
+        user_auth_webauthn::Entity::insert(user_auth_webauthn::ActiveModel {
+            credential_id: Set(credential_id.clone()),
+            uid: Set(uid.0),
+            name: Set(name.clone()),
+            public_key: Set(BASE64_STANDARD.encode(public_key.to_encoded_point(false))),
+            sign_count: Set(auth_data.sign_count as _),
+            created_at: Set(now),
+            last_used_at: Set(None),
+        })
+        .on_conflict(
+            OnConflict::column(user_auth_webauthn::Column::CredentialId)
+                .do_nothing()
+                .to_owned(),
+        )
+        .exec(&*self.0.database.conn)
+        .await
+        .map_err(|err| match err {
+            sea_orm::DbErr::RecordNotInserted => {
+                Error::bad_request("the credential has been registered")
+            }
+            err => Error::internal(err),
+        })?;
+
+        Ok(Credential {
+            id: credential_id,
+            name,
+            created_at: now.and_utc(),
+            last_used_at: None,
+        })
+    }
+
+    /// Lists credentials of the user, from the newest.
+    pub async fn list_webauthn_credentials(
+        &self,
+        session: &Session,
+    ) -> Result<Vec<Credential>, Error> {
+        session.will_manage_sessions()?;
+
+        Ok(self
+            .credentials_of(session.user.uid)
+            .await?
+            .into_iter()
+            .map(Credential::from)
+            .collect())
+    }
+
+    /// Removes a credential of the user.
+    ///
+    /// Members of the wheel group cannot remove their last second factor while [`MandatoryWheelTwoFactor`] is set.
+    pub async fn remove_webauthn_credential(
+        &self,
+        session: &Session,
+        credential_id: &str,
+    ) -> Result<(), Error> {
+        session.will_manage_sessions()?;
+        let uid = session.user.uid;
+        if session.user.groups.iter().any(|x| x == WHEEL)
+            && self.0.app_settings().get::<MandatoryWheelTwoFactor>().await
+            && !self.totp_enabled(uid).await?
+            && self.credentials_of(uid).await?.len() <= 1
+        {
+            return Err(Error::restricted_user());
+        }
+
+        let result = user_auth_webauthn::Entity::delete_many()
+            .filter(user_auth_webauthn::Column::CredentialId.eq(credential_id))
+            .filter(user_auth_webauthn::Column::Uid.eq(uid.0))
+            .exec(&*self.0.database.conn)
+            .await?;
+        if result.rows_affected == 0 {
+            return Err(Error::not_found());
+        }
+
+        Ok(())
+    }
+
+    /// Starts logging in without a password, returning options for `navigator.credentials.get()`.
+    ///
+    /// No credentials are listed in the options, so the authenticator offers the passkeys it holds for the site.
+    pub async fn begin_webauthn_login(&self) -> Result<RequestOptions, Error> {
+        let rp = self.relying_party()?;
+        let challenge = self
+            .issue_webauthn_challenge(None, Purpose::Login, None)
+            .await?;
+
+        Ok(RequestOptions {
+            challenge,
+            rp_id: rp.id.clone(),
+            allow_credentials: Vec::new(),
+            user_verification: "required",
+            timeout: CHALLENGE_VALID_IN.as_millis() as _,
+        })
+    }
+
+    /// Logs in with the response of `navigator.credentials.get()` to [`Users::begin_webauthn_login`].
+    ///
+    /// The authenticator must have verified the user, so this counts as two factors by itself.
+    pub async fn login_via_webauthn(
+        &self,
+        credential: &AssertionCredential,
+        source: String,
+    ) -> Result<LoginResponse, Error> {
+        let (_, model) = self
+            .verify_assertion(credential, Purpose::Login, true)
+            .await?;
+
+        let uid = Uid(model.uid);
+        let (refresh_token, access_token) =
+            self.put_session(uid, Permissions::all(), source).await?;
+        Ok(LoginResponse {
+            refresh_token,
+            access_token,
+            uid,
+        })
+    }
+
+    /// Starts completing a login challenge with a credential as the second factor, returning options for
+    /// `navigator.credentials.get()`.
+    pub async fn begin_webauthn_second_factor(
+        &self,
+        login_challenge: &str,
+    ) -> Result<RequestOptions, Error> {
+        let rp = self.relying_party()?;
+        let challenge_hash = secret::hash(login_challenge);
+        let login = login_challenge::Entity::find_by_id(&challenge_hash)
+            .one(&*self.0.database.conn)
+            .await?
+            .filter(|x| x.expires_at.and_utc() > Utc::now())
+            .ok_or_else(Error::login_incorrect)?;
+        let credentials = self.credentials_of(Uid(login.uid)).await?;
+        if credentials.is_empty() {
+            return Err(Error::login_incorrect());
+        }
+
+        let challenge = self
+            .issue_webauthn_challenge(
+                Some(Uid(login.uid)),
+                Purpose::SecondFactor,
+                Some(challenge_hash),
+            )
+            .await?;
+        Ok(RequestOptions {
+            challenge,
+            rp_id: rp.id.clone(),
+            allow_credentials: credentials.iter().map(CredentialDescriptor::from).collect(),
+            user_verification: "discouraged",
+            timeout: CHALLENGE_VALID_IN.as_millis() as _,
+        })
+    }
+
+    /// Completes a login challenge with the response of `navigator.credentials.get()` to
+    /// [`Users::begin_webauthn_second_factor`].
+    pub async fn complete_login_via_webauthn(
+        &self,
+        login_challenge: &str,
+        credential: &AssertionCredential,
+    ) -> Result<LoginResponse, Error> {
+        let login = self.attempt_challenge(login_challenge).await?;
+        let (challenge, model) = self
+            .verify_assertion(credential, Purpose::SecondFactor, false)
+            .await?;
+        if challenge.login_challenge_hash.as_ref() != Some(&login.challenge_hash)
+            || model.uid != login.uid
+        {
+            return Err(Error::login_incorrect());
+        }
+
+        self.finish_challenge(login).await
+    }
+
+    /// Returns whether the user has registered any credentials.
+    pub(in crate::user) async fn webauthn_enabled(&self, uid: Uid) -> Result<bool, Error> {
+        Ok(user_auth_webauthn::Entity::find()
+            .filter(user_auth_webauthn::Column::Uid.eq(uid.0))
+            .count(&*self.0.database.conn)
+            .await?
+            > 0)
+    }
+
+    /// Verifies an assertion, returning the consumed challenge and the credential that made it.
+    ///
+    /// The stored signature counter of the credential is advanced, and an assertion whose counter doesn't advance is
+    /// rejected, since it may come from a cloned authenticator.
+    async fn verify_assertion(
+        &self,
+        credential: &AssertionCredential,
+        purpose: Purpose,
+        require_verified: bool,
+    ) -> Result<(webauthn_challenge::Model, user_auth_webauthn::Model), Error> {
+        let rp = self.relying_party()?;
+        let client_data_json = decode_base64(&credential.response.client_data_json)?;
+        let client_data =
+            ClientData::parse(&credential.response.client_data_json, rp, "webauthn.get")?;
+        let challenge = self
+            .consume_webauthn_challenge(&client_data.challenge, purpose)
+            .await?;
+
+        let model = user_auth_webauthn::Entity::find_by_id(&credential.id)
+            .one(&*self.0.database.conn)
+            .await?
+            .ok_or_else(Error::login_incorrect)?;
+        if challenge.uid.is_some_and(|x| x != model.uid)
+            || credential
+                .response
+                .user_handle
+                .as_ref()
+                .is_some_and(|x| *x != user_handle(Uid(model.uid)))
+        {
+            return Err(Error::login_incorrect());
+        }
+
+        let raw_auth_data = decode_base64(&credential.response.authenticator_data)?;
+        let auth_data = AuthenticatorData::parse(&raw_auth_data, rp)?;
+        if require_verified && auth_data.flags & FLAG_USER_VERIFIED == 0 {
+            return Err(Error::login_incorrect());
+        }
+
+        let public_key = BASE64_STANDARD
+            .decode(&model.public_key)
+            .ok()
+            .and_then(|x| VerifyingKey::from_sec1_bytes(&x).ok())
+            .ok_or_else(|| Error::internal("malformed WebAuthn public key"))?;
+        let signature = decode_base64(&credential.response.signature)
+            .ok()
+            .and_then(|x| Signature::from_der(&x).ok())
+            .ok_or_else(Error::login_incorrect)?;
+        let signature = signature.normalize_s().unwrap_or(signature);
+        let mut message = raw_auth_data;
+        message.extend_from_slice(&Sha256::digest(&client_data_json));
+        public_key
+            .verify(&message, &signature)
+            .map_err(|_| Error::login_incorrect())?;
+
+        // Authenticators that don't implement the counter always report zero.
+        let sign_count = auth_data.sign_count as i64;
+        if (sign_count != 0 || model.sign_count != 0) && sign_count <= model.sign_count {
+            tracing::warn!(
+                "WebAuthn credential {} of user {} reported a stale signature counter",
+                model.credential_id,
+                model.uid
+            );
+            return Err(Error::login_incorrect());
+        }
+        let updated = user_auth_webauthn::Entity::update_many()
+            .col_expr(user_auth_webauthn::Column::SignCount, sign_count.into())
+            .col_expr(
+                user_auth_webauthn::Column::LastUsedAt,
+                Some(Utc::now().naive_utc()).into(),
+            )
+            .filter(user_auth_webauthn::Column::CredentialId.eq(&model.credential_id))
+            .filter(user_auth_webauthn::Column::SignCount.eq(model.sign_count))
+            .exec(&*self.0.database.conn)
+            .await?;
+        if updated.rows_affected == 0 {
+            return Err(Error::login_incorrect());
+        }
+
+        Ok((challenge, model))
+    }
+
+    /// Issues a ceremony challenge.
+    async fn issue_webauthn_challenge(
+        &self,
+        uid: Option<Uid>,
+        purpose: Purpose,
+        login_challenge_hash: Option<String>,
+    ) -> Result<String, Error> {
+        let challenge = secret::generate();
+        webauthn_challenge::Entity::insert(webauthn_challenge::ActiveModel {
+            challenge_hash: Set(secret::hash(&challenge)),
+            uid: Set(uid.map(|x| x.0)),
+            purpose: Set(purpose.as_str().into()),
+            login_challenge_hash: Set(login_challenge_hash),
+            expires_at: Set(Utc::now().naive_utc() + CHALLENGE_VALID_IN),
+        })
+        .exec(&*self.0.database.conn)
+        .await?;
+
+        Ok(challenge)
+    }
+
+    /// Consumes a ceremony challenge of the purpose. A challenge can only be consumed once.
+    async fn consume_webauthn_challenge(
+        &self,
+        challenge: &str,
+        purpose: Purpose,
+    ) -> Result<webauthn_challenge::Model, Error> {
+        let challenge_hash = secret::hash(challenge);
+        let model = webauthn_challenge::Entity::find_by_id(&challenge_hash)
+            .one(&*self.0.database.conn)
+            .await?
+            .ok_or_else(|| Error::bad_request("invalid WebAuthn challenge"))?;
+        let deleted = webauthn_challenge::Entity::delete_by_id(&challenge_hash)
+            .exec(&*self.0.database.conn)
+            .await?;
+
+        if deleted.rows_affected == 0
+            || model.expires_at.and_utc() < Utc::now()
+            || model.purpose != purpose.as_str()
+        {
+            return Err(Error::bad_request("invalid WebAuthn challenge"));
+        }
+
+        Ok(model)
+    }
+
+    /// Returns credentials of the user, from the newest.
+    async fn credentials_of(&self, uid: Uid) -> Result<Vec<user_auth_webauthn::Model>, Error> {
+        Ok(user_auth_webauthn::Entity::find()
+            .filter(user_auth_webauthn::Column::Uid.eq(uid.0))
+            .order_by_desc(user_auth_webauthn::Column::CreatedAt)
+            .all(&*self.0.database.conn)
+            .await?)
+    }
+
+    /// Returns the relying party, failing if WebAuthn is not configured.
+    fn relying_party(&self) -> Result<&RelyingParty, Error> {
+        self.0
+            .relying_party
+            .as_ref()
+            .ok_or_else(|| Error::bad_request("WebAuthn is not configured on this server"))
+    }
+}
+
+/// A registered credential, as shown to its user.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Credential {
+    pub id: String,
+    pub name: String,
+    pub created_at: DateTime<Utc>,
+    pub last_used_at: Option<DateTime<Utc>>,
+}
+impl From<user_auth_webauthn::Model> for Credential {
+    fn from(model: user_auth_webauthn::Model) -> Self {
+        Self {
+            id: model.credential_id,
+            name: model.name,
+            created_at: model.created_at.and_utc(),
+            last_used_at: model.last_used_at.map(|x| x.and_utc()),
+        }
+    }
+}
+
+/// Options for `navigator.credentials.create()`, in the JSON form of `PublicKeyCredentialCreationOptions`.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreationOptions {
+    pub challenge: String,
+    pub rp: RpEntity,
+    pub user: UserEntity,
+    pub pub_key_cred_params: Vec<CredentialParameters>,
+    pub timeout: u64,
+    pub exclude_credentials: Vec<CredentialDescriptor>,
+    pub authenticator_selection: AuthenticatorSelection,
+    pub attestation: &'static str,
+}
+
+/// Options for `navigator.credentials.get()`, in the JSON form of `PublicKeyCredentialRequestOptions`.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RequestOptions {
+    pub challenge: String,
+    pub rp_id: String,
+    pub allow_credentials: Vec<CredentialDescriptor>,
+    pub user_verification: &'static str,
+    pub timeout: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RpEntity {
+    pub id: String,
+    pub name: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UserEntity {
+    pub id: String,
+    pub name: String,
+    pub display_name: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CredentialParameters {
+    #[serde(rename = "type")]
+    pub kind: &'static str,
+    pub alg: i64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CredentialDescriptor {
+    #[serde(rename = "type")]
+    pub kind: &'static str,
+    pub id: String,
+}
+impl From<&user_auth_webauthn::Model> for CredentialDescriptor {
+    fn from(model: &user_auth_webauthn::Model) -> Self {
+        Self {
+            kind: "public-key",
+            id: model.credential_id.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AuthenticatorSelection {
+    pub resident_key: &'static str,
+    pub user_verification: &'static str,
+}
+
+/// Response of `navigator.credentials.create()`, in the form of `PublicKeyCredential.toJSON()`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RegistrationCredential {
+    pub id: String,
+    pub response: AttestationResponse,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AttestationResponse {
+    #[serde(rename = "clientDataJSON")]
+    pub client_data_json: String,
+    pub attestation_object: String,
+}
+
+/// Response of `navigator.credentials.get()`, in the form of `PublicKeyCredential.toJSON()`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AssertionCredential {
+    pub id: String,
+    pub response: AssertionResponse,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AssertionResponse {
+    #[serde(rename = "clientDataJSON")]
+    pub client_data_json: String,
+    pub authenticator_data: String,
+    pub signature: String,
+    pub user_handle: Option<String>,
+}
+
+/// Purpose of a ceremony challenge.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Purpose {
+    /// Registering a credential of the user.
+    Register,
+
+    /// Logging in without a password.
+    Login,
+
+    /// Completing a login challenge.
+    SecondFactor,
+}
+impl Purpose {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Register => "register",
+            Self::Login => "login",
+            Self::SecondFactor => "second_factor",
+        }
+    }
+}
+
+/// Collected client data, signed by the authenticator.
+#[derive(Debug, Clone, Deserialize)]
+struct ClientData {
+    #[serde(rename = "type")]
+    kind: String,
+    challenge: String,
+    origin: String,
+}
+impl ClientData {
+    /// Parses client data, and checks that it's of the ceremony type and from the origin of the relying party.
+    fn parse(data: &str, rp: &RelyingParty, kind: &str) -> Result<Self, Error> {
+        let client_data: Self = serde_json::from_slice(&decode_base64(data)?)
+            .map_err(|_| Error::bad_request("malformed client data"))?;
+        if client_data.kind != kind || client_data.origin != rp.origin {
+            return Err(Error::bad_request("mismatched client data"));
+        }
+
+        Ok(client_data)
+    }
+}
+
+/// Authenticator data.
+#[derive(Debug, Clone)]
+struct AuthenticatorData {
+    flags: u8,
+    sign_count: u32,
+
+    /// ID and public key of the credential, which are present in registration ceremonies.
+    attested: Option<(Vec<u8>, VerifyingKey)>,
+}
+impl AuthenticatorData {
+    /// Parses authenticator data, and checks that it's scoped to the relying party with the user present.
+    fn parse(data: &[u8], rp: &RelyingParty) -> Result<Self, Error> {
+        let malformed = || Error::bad_request("malformed authenticator data");
+        if data.len() < 37 {
+            return Err(malformed());
+        }
+        if data[..32] != Sha256::digest(rp.id.as_bytes())[..] {
+            return Err(Error::bad_request("mismatched RP ID"));
+        }
+        let flags = data[32];
+        if flags & FLAG_USER_PRESENT == 0 {
+            return Err(Error::bad_request("user not present"));
+        }
+        let sign_count = u32::from_be_bytes(data[33..37].try_into().unwrap());
+
+        let attested = if flags & FLAG_ATTESTED != 0 {
+            // AAGUID, followed by the length of the credential ID.
+            let rest = data.get(37 + 16..).ok_or_else(malformed)?;
+            let (length, rest) = rest.split_at_checked(2).ok_or_else(malformed)?;
+            let length = u16::from_be_bytes([length[0], length[1]]) as usize;
+            let (credential_id, mut rest) = rest.split_at_checked(length).ok_or_else(malformed)?;
+
+            // Extensions may follow the key, so the key is read as a single CBOR item.
+            let value: ciborium::Value =
+                ciborium::from_reader(&mut rest).map_err(|_| malformed())?;
+            let key = CoseKey::from_cbor_value(value).map_err(|_| malformed())?;
+            Some((credential_id.to_vec(), decode_public_key(&key)?))
+        } else {
+            None
+        };
+
+        Ok(Self {
+            flags,
+            sign_count,
+            attested,
+        })
+    }
+}
+
+/// Decodes an ES256 public key in COSE format.
+fn decode_public_key(key: &CoseKey) -> Result<VerifyingKey, Error> {
+    let unsupported =
+        || Error::bad_request("unsupported credential algorithm, only ES256 is supported");
+    if key.kty != KeyType::Assigned(iana::KeyType::EC2)
+        || key
+            .alg
+            .as_ref()
+            .is_some_and(|x| *x != coset::Algorithm::Assigned(iana::Algorithm::ES256))
+    {
+        return Err(unsupported());
+    }
+    let param = |label: iana::Ec2KeyParameter| {
+        key.params
+            .iter()
+            .find(|(x, _)| *x == Label::Int(label.to_i64()))
+            .map(|(_, value)| value)
+    };
+    if param(iana::Ec2KeyParameter::Crv).and_then(|x| x.as_integer())
+        != Some(iana::EllipticCurve::P_256.to_i64().into())
+    {
+        return Err(unsupported());
+    }
+    let coordinate = |label| {
+        param(label)
+            .and_then(|x| x.as_bytes())
+            .filter(|x| x.len() == 32)
+            .ok_or_else(|| Error::bad_request("malformed credential public key"))
+    };
+
+    let mut point = vec![0x04];
+    point.extend_from_slice(coordinate(iana::Ec2KeyParameter::X)?);
+    point.extend_from_slice(coordinate(iana::Ec2KeyParameter::Y)?);
+    VerifyingKey::from_sec1_bytes(&point)
+        .map_err(|_| Error::bad_request("malformed credential public key"))
+}
+
+/// Returns authenticator data in an attestation object encoded in URL-safe Base64.
+///
+/// The attestation statement is not verified, so it's not parsed either.
+fn attested_auth_data(attestation_object: &str) -> Result<Vec<u8>, Error> {
+    let malformed = || Error::bad_request("malformed attestation object");
+    let value: ciborium::Value =
+        ciborium::from_reader(decode_base64(attestation_object)?.as_slice())
+            .map_err(|_| malformed())?;
+
+    value
+        .into_map()
+        .map_err(|_| malformed())?
+        .into_iter()
+        .find(|(key, _)| key.as_text() == Some("authData"))
+        .and_then(|(_, value)| value.into_bytes().ok())
+        .ok_or_else(malformed)
+}
+
+/// Decodes URL-safe Base64, with or without padding, as sent by browsers.
+fn decode_base64(data: &str) -> Result<Vec<u8>, Error> {
+    BASE64_URL_SAFE_NO_PAD
+        .decode(data.trim_end_matches('='))
+        .map_err(|_| Error::bad_request("malformed Base64"))
+}
+
+/// Returns the user handle of a user, which is their UID in big endian.
+fn user_handle(uid: Uid) -> String {
+    BASE64_URL_SAFE_NO_PAD.encode(uid.0.to_be_bytes())
+}
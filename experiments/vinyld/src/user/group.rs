@@ -1,2 +1,9 @@
+use crate::error::Error;
+use vinutie::def_verify;
+
 /// The server administrators' group.
 pub const WHEEL: &str = "wheel";
+
+def_verify!(pub GroupLike<str>(err: Error = Error::bad_request("malformed group name")) = |x: &str| {
+    !x.is_empty() && x.len() <= 32 && x.chars().all(|y| y.is_ascii_lowercase() || y.is_ascii_digit() || y == '-' || y == '_')
+});
@@ -1,12 +1,26 @@
 use super::{
-    Uid, Users,
-    auth::password,
+    Uid, User, Users,
+    auth::{email, password, phone},
+    group::WHEEL,
     session::{AccessToken, Permissions, RefreshToken},
 };
-use crate::{database::entity::user_auth_password, error::Error};
+use crate::{
+    app_settings::{MandatoryWheelTwoFactor, RateLimits},
+    database::entity::{login_challenge, user_auth_email, user_auth_password, user_auth_phone},
+    error::Error,
+    util::secret,
+};
 use argon2::password_hash::{PasswordHash, PasswordVerifier};
-use sea_orm::EntityTrait;
+use chrono::{DateTime, Utc};
+use sea_orm::{ActiveValue::Set, ColumnTrait, EntityTrait, QueryFilter, sea_query::Expr};
 use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// Time before a login challenge expires.
+const CHALLENGE_VALID_IN: Duration = Duration::from_secs(5 * 60);
+
+/// Number of wrong second factors after which a login challenge is no longer accepted.
+const MAX_CHALLENGE_ATTEMPTS: i32 = 5;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct LoginResponse {
@@ -20,6 +34,43 @@ pub struct LoginResponse {
 #[serde(rename_all = "snake_case")]
 pub enum UserProber {
     Username(String),
+
+    /// A verified email address of the user.
+    Email(String),
+
+    /// A verified phone number of the user.
+    Phone(String),
+}
+
+/// Result of the first step of logging in.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum LoginStep {
+    /// The user is logged in.
+    Done(LoginResponse),
+
+    /// The user has enabled two-factor authentication, and must complete the login with a second factor.
+    SecondFactorRequired(SecondFactorChallenge),
+}
+
+/// A login waiting for a second factor.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SecondFactorChallenge {
+    /// Opaque token identifying the login, which is passed back with the second factor.
+    pub challenge: String,
+
+    /// Second factors that the user may complete the login with.
+    pub methods: Vec<SecondFactor>,
+    pub expires_at: DateTime<Utc>,
+}
+
+/// A kind of second factor.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SecondFactor {
+    Totp,
+    RecoveryCode,
+    Webauthn,
 }
 
 impl Users<'_> {
@@ -28,34 +79,223 @@ impl Users<'_> {
         prober: &UserProber,
         password: &str,
         source: String,
+    ) -> Result<LoginStep, Error> {
+        let user = self.check_password(prober, password).await?;
+        self.start_login(&user, source).await
+    }
+
+    /// Completes a login with a TOTP or recovery code.
+    pub async fn complete_login_via_code(
+        &self,
+        challenge: &str,
+        code: &str,
     ) -> Result<LoginResponse, Error> {
-        let user = match prober {
-            UserProber::Username(username) => self
-                .find_by_username(username)
-                .await
-                .map_err(|_| Error::login_incorrect())?,
+        let model = self.attempt_challenge(challenge).await?;
+        self.check_second_factor(Uid(model.uid), code).await?;
+        self.finish_challenge(model).await
+    }
+
+    /// Logs the user in after the first factor has been checked, or issues a challenge if a second factor is
+    /// required.
+    pub(in crate::user) async fn start_login(
+        &self,
+        user: &User,
+        source: String,
+    ) -> Result<LoginStep, Error> {
+        let methods = self.second_factors(user.uid).await?;
+        if methods.is_empty() {
+            let permissions = self.login_permissions(user).await;
+            let (refresh_token, access_token) =
+                self.put_session(user.uid, permissions, source).await?;
+            return Ok(LoginStep::Done(LoginResponse {
+                refresh_token,
+                access_token,
+                uid: user.uid,
+            }));
+        }
+
+        let challenge = secret::generate();
+        let expires_at = Utc::now() + CHALLENGE_VALID_IN;
+        login_challenge::Entity::insert(login_challenge::ActiveModel {
+            challenge_hash: Set(secret::hash(&challenge)),
+            uid: Set(user.uid.0),
+            source: Set(source),
+            attempts: Set(0),
+            expires_at: Set(expires_at.naive_utc()),
+        })
+        .exec(&*self.0.database.conn)
+        .await?;
+
+        Ok(LoginStep::SecondFactorRequired(SecondFactorChallenge {
+            challenge,
+            methods,
+            expires_at,
+        }))
+    }
+
+    /// Returns whether the user has enabled two-factor authentication, by either TOTP or WebAuthn.
+    pub async fn two_factor_enabled(&self, uid: Uid) -> Result<bool, Error> {
+        Ok(self.totp_enabled(uid).await? || self.webauthn_enabled(uid).await?)
+    }
+
+    /// Returns second factors that the user may complete a login with, which is empty if two-factor authentication
+    /// is not enabled.
+    async fn second_factors(&self, uid: Uid) -> Result<Vec<SecondFactor>, Error> {
+        let mut methods = Vec::new();
+        if self.totp_enabled(uid).await? {
+            methods.extend([SecondFactor::Totp, SecondFactor::RecoveryCode]);
+        }
+        if self.webauthn_enabled(uid).await? {
+            methods.push(SecondFactor::Webauthn);
+        }
+        Ok(methods)
+    }
+
+    /// Finds a login challenge, counting an attempt of completing it.
+    pub(in crate::user) async fn attempt_challenge(
+        &self,
+        challenge: &str,
+    ) -> Result<login_challenge::Model, Error> {
+        let challenge_hash = secret::hash(challenge);
+        let model = login_challenge::Entity::find_by_id(&challenge_hash)
+            .one(&*self.0.database.conn)
+            .await?
+            .ok_or_else(Error::login_incorrect)?;
+        if model.expires_at.and_utc() < Utc::now() {
+            return Err(Error::login_incorrect());
+        }
+
+        let counted = login_challenge::Entity::update_many()
+            .col_expr(
+                login_challenge::Column::Attempts,
+                Expr::col(login_challenge::Column::Attempts).add(1),
+            )
+            .filter(login_challenge::Column::ChallengeHash.eq(&challenge_hash))
+            .filter(login_challenge::Column::Attempts.lt(MAX_CHALLENGE_ATTEMPTS))
+            .exec(&*self.0.database.conn)
+            .await?;
+        if counted.rows_affected == 0 {
+            return Err(Error::login_incorrect());
+        }
+
+        Ok(model)
+    }
+
+    /// Consumes a login challenge whose second factor has been checked, and logs the user in.
+    pub(in crate::user) async fn finish_challenge(
+        &self,
+        model: login_challenge::Model,
+    ) -> Result<LoginResponse, Error> {
+        let deleted = login_challenge::Entity::delete_by_id(&model.challenge_hash)
+            .exec(&*self.0.database.conn)
+            .await?;
+        if deleted.rows_affected == 0 {
+            // The challenge was completed concurrently.
+            return Err(Error::login_incorrect());
+        }
+
+        let uid = Uid(model.uid);
+        let (refresh_token, access_token) = self
+            .put_session(uid, Permissions::all(), model.source)
+            .await?;
+        Ok(LoginResponse {
+            refresh_token,
+            access_token,
+            uid,
+        })
+    }
+
+    /// Returns permissions of a session created by logging in without a second factor.
+    ///
+    /// While [`MandatoryWheelTwoFactor`] is set, members of the wheel group can't do administration work before
+    /// enabling two-factor authentication, though they can still log in to enable it.
+    pub(crate) async fn login_permissions(&self, user: &User) -> Permissions {
+        if user.groups.iter().any(|x| x == WHEEL)
+            && self.0.app_settings().get::<MandatoryWheelTwoFactor>().await
+        {
+            Permissions::all() - Permissions::ADMINISTRATION
+        } else {
+            Permissions::all()
+        }
+    }
+
+    /// Finds the user identified by the prober.
+    ///
+    /// Email addresses and phone numbers only identify users once they are verified.
+    pub async fn find_by_prober(&self, prober: &UserProber) -> Result<User, Error> {
+        let uid = match prober {
+            UserProber::Username(username) => return self.find_by_username(username).await,
+            UserProber::Email(email) => {
+                user_auth_email::Entity::find()
+                    .filter(user_auth_email::Column::Email.eq(email::normalize(email)?))
+                    .filter(user_auth_email::Column::VerifiedAt.is_not_null())
+                    .one(&*self.0.database.conn)
+                    .await?
+                    .ok_or_else(Error::not_found)?
+                    .uid
+            }
+            UserProber::Phone(phone) => {
+                user_auth_phone::Entity::find()
+                    .filter(user_auth_phone::Column::Phone.eq(phone::normalize(phone)?))
+                    .filter(user_auth_phone::Column::VerifiedAt.is_not_null())
+                    .one(&*self.0.database.conn)
+                    .await?
+                    .ok_or_else(Error::not_found)?
+                    .uid
+            }
         };
-        let passwd_rec = user_auth_password::Entity::find_by_id(user.uid.0)
+
+        self.find_by_uid(Uid(uid)).await
+    }
+
+    /// Returns the user if the password is correct, without creating a session.
+    ///
+    /// Logins are locked out for a while after too many wrong passwords, as configured by [`RateLimits`].
+    pub async fn check_password(&self, prober: &UserProber, password: &str) -> Result<User, Error> {
+        let user = self.find_by_prober(prober).await.ok();
+        // Probers identifying no user are locked out as well, so that lockouts don't tell which users exist.
+        let key = match (&user, prober) {
+            (Some(user), _) => format!("uid/{}", user.uid.0),
+            (None, UserProber::Username(x)) => format!("username/{x}"),
+            (None, UserProber::Email(x)) => format!("email/{}", x.to_lowercase()),
+            (None, UserProber::Phone(x)) => format!("phone/{x}"),
+        };
+        self.0.rate_limiter.check_lockout(&key)?;
+
+        match self.verify_password(user, password).await? {
+            Some(user) => {
+                self.0.rate_limiter.clear_failures(&key);
+                Ok(user)
+            }
+            None => {
+                let limits = self.0.app_settings().get::<RateLimits>().await;
+                self.0.rate_limiter.record_failure(&key, &limits.lockout);
+                Err(Error::login_incorrect())
+            }
+        }
+    }
+
+    /// Returns the user if it has a password and the password is correct.
+    async fn verify_password(
+        &self,
+        user: Option<User>,
+        password: &str,
+    ) -> Result<Option<User>, Error> {
+        let Some(user) = user else {
+            return Ok(None);
+        };
+        let Some(passwd_rec) = user_auth_password::Entity::find_by_id(user.uid.0)
             .one(&*self.0.database.conn)
             .await?
-            .ok_or_else(|| Error::login_incorrect())?;
+        else {
+            return Ok(None);
+        };
 
         let parsed_hash =
             PasswordHash::new(&passwd_rec.password).map_err(|err| Error::internal(err))?;
-        if password::hasher()
+        Ok(password::hasher()
             .verify_password(password.as_bytes(), &parsed_hash)
             .is_ok()
-        {
-            let (refresh_token, access_token) = self
-                .put_session(user.uid, Permissions::all(), source)
-                .await?;
-            Ok(LoginResponse {
-                refresh_token,
-                access_token,
-                uid: user.uid,
-            })
-        } else {
-            Err(Error::login_incorrect())
-        }
+            .then_some(user))
     }
 }
@@ -1,3 +1,5 @@
+pub mod account;
+pub mod admin;
 pub mod auth;
 pub mod extract;
 pub mod group;
@@ -6,7 +8,7 @@ pub mod register;
 pub mod session;
 
 use crate::{AppState, database::entity::user, error::Error};
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, NaiveDate, Utc};
 use sea_orm::{ColumnTrait, EntityTrait, QueryFilter};
 use serde::{Deserialize, Serialize};
 use vinioss::ObjectKey;
@@ -32,6 +34,24 @@ impl Users<'_> {
             .map(User::from)
     }
 
+    /// Returns the profile of the user, as seen by the viewer.
+    ///
+    /// Personal details, namely gender, date of birth and city, are only shown to the user themselves.
+    pub async fn profile(&self, uid: Uid, viewer: Option<Uid>) -> Result<Profile, Error> {
+        let mut profile = user::Entity::find_by_id(uid.0)
+            .one(&*self.0.database.conn)
+            .await?
+            .ok_or_else(|| Error::not_found())
+            .map(Profile::from)?;
+        if viewer != Some(uid) {
+            profile.gender = None;
+            profile.date_of_birth = None;
+            profile.city = None;
+        }
+
+        Ok(profile)
+    }
+
     pub async fn avatar_hq(&self, uid: Uid) -> Result<ObjectKey, Error> {
         user::Entity::find_by_id(uid.0)
             .one(&*self.0.database.conn)
@@ -41,6 +61,16 @@ impl Users<'_> {
             .map(ObjectKey)
             .ok_or_else(|| Error::not_found())
     }
+
+    pub async fn banner_hq(&self, uid: Uid) -> Result<ObjectKey, Error> {
+        user::Entity::find_by_id(uid.0)
+            .one(&*self.0.database.conn)
+            .await?
+            .ok_or_else(|| Error::not_found())?
+            .banner_image
+            .map(ObjectKey)
+            .ok_or_else(|| Error::not_found())
+    }
 }
 
 /// Representation of a UID.
@@ -51,15 +81,42 @@ impl Users<'_> {
 #[repr(transparent)]
 pub struct Uid(pub i64);
 
-/// Public profile of a user.
+/// Profile of a user.
+///
+/// `gender`, `date_of_birth` and `city` are personal details, which are unset unless the user views their own profile.
 #[derive(Debug, Clone, Serialize, Deserialize)]
-#[repr(transparent)]
-#[serde(transparent)]
-pub struct Profile(User);
-impl From<User> for Profile {
-    fn from(mut user: User) -> Self {
-        user.groups.clear();
-        Self(user)
+pub struct Profile {
+    pub uid: Uid,
+    pub username: String,
+
+    /// Whether the username was generated on registration, which the user may still replace once.
+    pub username_generated: bool,
+    pub nickname: String,
+    pub gender: Option<String>,
+    pub date_of_birth: Option<NaiveDate>,
+    pub country: Option<String>,
+    pub city: Option<String>,
+    pub signature: Option<String>,
+    pub has_avatar: bool,
+    pub has_banner: bool,
+    pub banned: bool,
+}
+impl From<user::Model> for Profile {
+    fn from(model: user::Model) -> Self {
+        Self {
+            uid: Uid(model.uid),
+            username: model.username,
+            username_generated: model.username_generated,
+            nickname: model.nickname,
+            gender: model.gender,
+            date_of_birth: model.date_of_birth,
+            country: model.country,
+            city: model.city,
+            signature: model.signature,
+            has_avatar: model.avatar.is_some(),
+            has_banner: model.banner_image.is_some(),
+            banned: model.banned.is_some(),
+        }
     }
 }
 
@@ -104,7 +161,7 @@ impl AppState {
 }
 
 def_verify!(pub UsernameLike<str>(err: Error = Error::invalid_username()) = |x: &str| {
-    x.len() <= 24 && x.chars().all(|y| y.is_ascii_alphanumeric() || y == '-' || y == '_')
+    !x.is_empty() && x.len() <= 24 && x.chars().all(|y| y.is_ascii_alphanumeric() || y == '-' || y == '_')
 });
 def_verify!(pub NicknameLike<str>(err: Error = Error::invalid_nickname()) = |x: &str| {
     x.chars().count() <= 24
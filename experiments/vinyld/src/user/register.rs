@@ -1,32 +1,47 @@
 use super::{
     NicknameLike, Uid, UsernameLike, Users,
-    auth::password::{self, PasswordLike},
+    auth::{
+        email,
+        password::{self, PasswordLike},
+        phone,
+    },
     login::LoginResponse,
     session::Permissions,
 };
 use crate::{
-    app_settings::RegisterRequirements,
+    app_settings::{MandatoryEmailVerification, RegisterRequirements},
     database::entity::{user, user_auth_password},
     error::Error,
 };
-use argon2::{PasswordHasher, password_hash::SaltString};
 use bitflags::bitflags;
 use chrono::Utc;
+use rand::{Rng, distr::Alphanumeric};
 use sea_orm::{
     ActiveValue::{NotSet, Set},
-    EntityTrait,
+    EntityTrait, TransactionTrait,
     sea_query::OnConflict,
 };
 use serde::{Deserialize, Serialize};
 use vinutie::verify::verify_option;
 
+/// Number of retries of generating a username before giving up, when the generated one is taken.
+const GENERATED_USERNAME_ATTEMPTS: usize = 8;
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RegisterRequest {
+    /// Username of the user, which is generated if unset and not required. The user may claim another username
+    /// later with [`Users::claim_username`].
     pub username: Option<String>,
     pub nickname: Option<String>,
     pub password: Option<String>,
     pub email: Option<String>,
+
+    /// Code sent to `email` by [`Users::send_register_code`], proving its ownership.
+    pub email_code: Option<String>,
     pub phone: Option<String>,
+
+    /// Code sent to `phone` by [`Users::send_phone_register_code`], proving its ownership.
+    pub phone_code: Option<String>,
     pub source: String,
 }
 
@@ -41,7 +56,8 @@ bitflags! {
 
         /// Require the user to set an email address on registration.
         ///
-        /// Note that this does not enable mandatory email-based verification.
+        /// Note that this does not enable mandatory email-based verification, which is controlled by
+        /// [`MandatoryEmailVerification`].
         const EMAIL = 2 << 2;
 
         /// Require the user to set a phone number on registration.
@@ -64,37 +80,50 @@ impl Users<'_> {
             password,
             nickname,
             email,
+            email_code,
             phone,
+            phone_code,
             source,
         }: RegisterRequest,
     ) -> Result<LoginResponse, Error> {
         let requires = self.0.app_settings().get::<RegisterRequirements>().await;
+        let email_verification = self
+            .0
+            .app_settings()
+            .get::<MandatoryEmailVerification>()
+            .await;
 
         verify_option::<UsernameLike, _>(username.as_deref())?;
         verify_option::<NicknameLike, _>(nickname.as_deref())?;
         verify_option::<PasswordLike, _>(password.as_deref())?;
 
-        let username = match username {
-            Some(x) => x,
-            None => {
-                if requires.contains(RegisterRequires::USERNAME) {
-                    return Err(Error::registration_form_not_filled());
-                } else {
-                    todo!();
-                }
+        if username.is_none() && requires.contains(RegisterRequires::USERNAME) {
+            return Err(Error::registration_form_not_filled());
+        }
+
+        let email = email.as_deref().map(email::normalize).transpose()?;
+        let email_verified = match (&email, &email_code) {
+            (Some(_), Some(_)) => true,
+            (None, _) if requires.contains(RegisterRequires::EMAIL) || email_verification => {
+                return Err(Error::registration_form_not_filled());
             }
+            (Some(_), None) if email_verification => {
+                return Err(Error::registration_form_not_filled());
+            }
+            _ => false,
+        };
+
+        let phone = phone.as_deref().map(phone::normalize).transpose()?;
+        let phone_verified = match (&phone, &phone_code) {
+            (Some(_), Some(_)) => true,
+            (None, _) if requires.contains(RegisterRequires::PHONE) => {
+                return Err(Error::registration_form_not_filled());
+            }
+            _ => false,
         };
 
         let hashed_password = match &password {
-            Some(x) => Some(
-                password::hasher()
-                    .hash_password(
-                        x.as_bytes(),
-                        &SaltString::generate(&mut argon2::password_hash::rand_core::OsRng),
-                    )
-                    .map_err(|_| Error::invalid_password())?
-                    .to_string(),
-            ),
+            Some(x) => Some(password::hash(x)?),
             None => {
                 if requires.contains(RegisterRequires::PASSWORD) {
                     return Err(Error::registration_form_not_filled());
@@ -103,45 +132,97 @@ impl Users<'_> {
             }
         };
 
-        let user = user::Entity::insert(user::ActiveModel {
-            uid: NotSet,
-            username: Set(username.clone()),
-            nickname: Set(nickname.unwrap_or(username)),
-            avatar: Set(None),
-            gender: Set(None),
-            date_of_birth: Set(None),
-            country: Set(None),
-            city: Set(None),
-            signature: Set(None),
-            banner_image: Set(None),
-            banned: Set(None),
-            groups: Set(Vec::new()),
-            created_at: Set(Utc::now().naive_utc()),
-            last_logined_at: Set(Utc::now().naive_utc()),
-        })
-        .on_conflict(
-            OnConflict::column(user::Column::Username)
-                .do_nothing()
-                .to_owned(),
-        )
-        .exec(&*self.0.database.conn)
-        .await
-        .map_err(|err| match err {
-            sea_orm::DbErr::RecordNotInserted => Error::username_conflict(),
-            err => Error::internal(err),
-        })?;
-
-        let uid = Uid(user.last_insert_id);
+        // Attempts on the phone code are counted before the transaction, so that a failed registration can't undo
+        // them.
+        let phone_code = match (&phone, &phone_code) {
+            (Some(phone), Some(code)) => Some(self.check_phone_register_code(phone, code).await?),
+            _ => None,
+        };
+
+        // Codes are consumed along with creating the user, so that a failed registration leaves them usable.
+        let txn = self.0.database.conn.begin().await?;
+        if let (Some(email), Some(code)) = (&email, &email_code) {
+            self.consume_register_code(&txn, email, code).await?;
+        }
+        if let Some(x) = &phone_code {
+            self.take_phone_code(&txn, x).await?;
+        }
+
+        // Generated usernames are retried on collision, while chosen ones are reported as conflicts.
+        let mut attempts = 0;
+        let uid = loop {
+            let generated = username.is_none();
+            let candidate = username.clone().unwrap_or_else(generate_username);
+            let inserted = user::Entity::insert(user::ActiveModel {
+                uid: NotSet,
+                username: Set(candidate.clone()),
+                nickname: Set(nickname.clone().unwrap_or(candidate)),
+                avatar: Set(None),
+                gender: Set(None),
+                date_of_birth: Set(None),
+                country: Set(None),
+                city: Set(None),
+                signature: Set(None),
+                banner_image: Set(None),
+                banned: Set(None),
+                groups: Set(Vec::new()),
+                created_at: Set(Utc::now().naive_utc()),
+                last_logined_at: Set(Utc::now().naive_utc()),
+                username_generated: Set(generated),
+            })
+            .on_conflict(
+                OnConflict::column(user::Column::Username)
+                    .do_nothing()
+                    .to_owned(),
+            )
+            .exec(&txn)
+            .await;
+
+            match inserted {
+                Ok(x) => break Uid(x.last_insert_id),
+                Err(sea_orm::DbErr::RecordNotInserted)
+                    if generated && attempts < GENERATED_USERNAME_ATTEMPTS =>
+                {
+                    attempts += 1;
+                }
+                Err(sea_orm::DbErr::RecordNotInserted) => return Err(Error::username_conflict()),
+                Err(err) => return Err(Error::internal(err)),
+            }
+        };
 
         if let Some(x) = hashed_password {
             user_auth_password::Entity::insert(user_auth_password::ActiveModel {
                 uid: Set(uid.0),
                 password: Set(x),
             })
-            .exec(&*self.0.database.conn)
+            .exec(&txn)
             .await?;
         }
 
+        match &email {
+            Some(email) if email_verified => self.mark_verified(&txn, uid, email).await?,
+            Some(email) => self.store_email(&txn, uid, email).await?,
+            None => (),
+        }
+        match &phone {
+            Some(phone) if phone_verified => self.mark_phone_verified(&txn, uid, phone).await?,
+            Some(phone) => self.store_phone(&txn, uid, phone).await?,
+            None => (),
+        }
+        txn.commit().await?;
+
+        // The user is registered even if codes for verifying addresses can't be sent, since they can be resent later.
+        if let Some(email) = email.as_deref().filter(|_| !email_verified)
+            && let Err(err) = self.send_verify_code(uid, email).await
+        {
+            tracing::warn!("failed to send verification code to `{email}`: {err}");
+        }
+        if let Some(phone) = phone.as_deref().filter(|_| !phone_verified)
+            && let Err(err) = self.send_phone_verify_code(uid, phone).await
+        {
+            tracing::warn!("failed to send verification code to `{phone}`: {err}");
+        }
+
         let (refresh_token, access_token) =
             self.put_session(uid, Permissions::all(), source).await?;
 
@@ -152,3 +233,13 @@ impl Users<'_> {
         })
     }
 }
+
+/// Generates a username for users who registered without one, like `user_4k2v9q0x7m1c`.
+fn generate_username() -> String {
+    let suffix = rand::rng()
+        .sample_iter(Alphanumeric)
+        .take(12)
+        .map(|x| (x as char).to_ascii_lowercase())
+        .collect::<String>();
+    format!("user_{suffix}")
+}
@@ -1,21 +1,22 @@
 use super::{Ban, Uid, Users};
 use crate::{
-    database::entity::{session, user},
+    audit::Actor,
+    database::entity::{rotated_refresh_token, session, user},
     error::Error,
 };
 use base64::{Engine, prelude::BASE64_URL_SAFE};
 use bitflags::bitflags;
-use chrono::Utc;
+use chrono::{DateTime, Utc};
 use rand::RngCore;
 use sea_orm::{
     ActiveModelTrait, ActiveValue::NotSet, ColumnTrait, EntityTrait, IntoActiveModel, QueryFilter,
-    Set,
+    QueryOrder, Set, TransactionTrait,
 };
 use serde::{Deserialize, Serialize};
 use std::time::Duration;
 
 bitflags! {
-    #[derive(Debug)]
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
     pub struct Permissions: u32 {
         /// Indicates that the session may be renewed.
         const REFRESH = 2 << 0;
@@ -36,11 +37,17 @@ bitflags! {
 
 impl Users<'_> {
     pub async fn get_session(&self, access_token: &AccessToken) -> Result<Session, Error> {
+        if !access_token.0.starts_with(TOKEN_VERSION) {
+            return Err(Error::unauthorized());
+        }
         let session_model = session::Entity::find()
-            .filter(session::Column::AccessToken.eq(&access_token.0))
+            .filter(session::Column::AccessTokenHash.eq(self.hash_token(&access_token.0)))
             .one(&*self.0.database.conn)
             .await?
             .ok_or_else(|| Error::unauthorized())?;
+        if session_model.access_expiry.and_utc() < Utc::now() {
+            return Err(Error::unauthorized());
+        }
         let user_model = user::Entity::find_by_id(session_model.uid)
             .one(&*self.0.database.conn)
             .await?
@@ -58,8 +65,10 @@ impl Users<'_> {
         }
 
         Ok(Session {
+            numeral: SessionNumeral(session_model.numeral),
             user: user_model.into(),
             permissions: Permissions::from_bits_retain(session_model.permissions as _),
+            source: session_model.source,
         })
     }
 
@@ -69,63 +78,264 @@ impl Users<'_> {
         permissions: Permissions,
         source: String,
     ) -> Result<(RefreshToken, AccessToken), Error> {
-        let model = session::ActiveModel {
+        let refresh_token = RefreshToken::generate(uid);
+        let access_token = AccessToken::generate(uid);
+        session::ActiveModel {
             numeral: NotSet,
             uid: Set(uid.0),
-            refresh_token: Set(RefreshToken::generate(uid).0),
-            access_token: Set(AccessToken::generate(uid).0),
+            refresh_token_hash: Set(self.hash_token(&refresh_token.0)),
+            access_token_hash: Set(self.hash_token(&access_token.0)),
             refresh_expiry: Set(Utc::now().naive_utc() + RefreshToken::VALID_IN),
             access_expiry: Set(Utc::now().naive_utc() + AccessToken::VALID_IN),
             permissions: Set(permissions.bits() as _),
             source: Set(source),
-        };
-
-        let model = model.insert(&*self.0.database.conn).await?;
+            created_at: Set(Some(Utc::now().naive_utc())),
+        }
+        .insert(&*self.0.database.conn)
+        .await?;
 
-        Ok((
-            RefreshToken(model.refresh_token),
-            AccessToken(model.access_token),
-        ))
+        Ok((refresh_token, access_token))
     }
 
+    /// Rotates the refresh token of a session, issuing a new pair of tokens.
+    ///
+    /// Rotated refresh tokens are remembered until they would have expired. Presenting one again means that it has
+    /// leaked, so the session is revoked and the reuse is recorded in the audit log. Tokens rotated no longer than
+    /// [`RefreshToken::REUSE_GRACE`] ago are still accepted, since clients retry refreshes whose responses were lost.
     pub async fn refresh_token(
         &self,
         refresh_token: &RefreshToken,
     ) -> Result<(RefreshToken, AccessToken), Error> {
+        if !refresh_token.0.starts_with(TOKEN_VERSION) {
+            return Err(Error::unauthorized());
+        }
+        let token_hash = self.hash_token(&refresh_token.0);
         let record = session::Entity::find()
-            .filter(session::Column::RefreshToken.eq(&refresh_token.0))
+            .filter(session::Column::RefreshTokenHash.eq(&token_hash))
             .one(&*self.0.database.conn)
-            .await?
-            .ok_or_else(|| Error::unauthorized())?;
+            .await?;
+        let record = match record {
+            Some(x) => x,
+            None => self.session_of_rotated(&token_hash).await?,
+        };
         if record.refresh_expiry.and_utc() < Utc::now() {
             return Err(Error::unauthorized());
         }
         if !Permissions::from_bits_retain(record.permissions as _).contains(Permissions::REFRESH) {
             return Err(Error::restricted_session());
         }
-        let uid = record.uid;
+        let uid = Uid(record.uid);
+        let new_refresh_token = RefreshToken::generate(uid);
+        let new_access_token = AccessToken::generate(uid);
+
+        // Rotating only if nobody else has, so that concurrent refreshes can't both succeed.
+        let txn = self.0.database.conn.begin().await?;
+        let updated = session::Entity::update_many()
+            .set(session::ActiveModel {
+                refresh_token_hash: Set(self.hash_token(&new_refresh_token.0)),
+                refresh_expiry: Set(Utc::now().naive_utc() + RefreshToken::VALID_IN),
+                access_token_hash: Set(self.hash_token(&new_access_token.0)),
+                access_expiry: Set(Utc::now().naive_utc() + AccessToken::VALID_IN),
+                ..Default::default()
+            })
+            .filter(session::Column::Numeral.eq(record.numeral))
+            .filter(session::Column::RefreshTokenHash.eq(&record.refresh_token_hash))
+            .exec(&txn)
+            .await?;
+        if updated.rows_affected == 0 {
+            return Err(Error::unauthorized());
+        }
+        rotated_refresh_token::Entity::insert(rotated_refresh_token::ActiveModel {
+            token_hash: Set(record.refresh_token_hash),
+            session: Set(record.numeral),
+            rotated_at: Set(Utc::now().naive_utc()),
+            expires_at: Set(record.refresh_expiry),
+        })
+        .exec(&txn)
+        .await?;
+        txn.commit().await?;
+
+        Ok((new_refresh_token, new_access_token))
+    }
+
+    /// Returns the session that a rotated refresh token, given by its hash, belonged to if it was rotated within the
+    /// grace window.
+    ///
+    /// Otherwise the session is revoked as the token has been reused.
+    async fn session_of_rotated(&self, token_hash: &str) -> Result<session::Model, Error> {
+        let (rotated, record) = rotated_refresh_token::Entity::find_by_id(token_hash)
+            .filter(rotated_refresh_token::Column::ExpiresAt.gt(Utc::now().naive_utc()))
+            .find_also_related(session::Entity)
+            .one(&*self.0.database.conn)
+            .await?
+            .ok_or_else(Error::unauthorized)?;
+        let record = record.ok_or_else(Error::unauthorized)?;
+        if rotated.rotated_at.and_utc() + RefreshToken::REUSE_GRACE >= Utc::now() {
+            return Ok(record);
+        }
+
+        tracing::warn!(
+            "refresh token of session {} reused, revoking the session",
+            record.numeral
+        );
+        session::Entity::delete_by_id(record.numeral)
+            .exec(&*self.0.database.conn)
+            .await?;
+        self.0
+            .audit_log()
+            .record(
+                &Actor {
+                    uid: Uid(record.uid),
+                    source: record.source.clone(),
+                },
+                "session.refresh_token_reused",
+                format!("session/{}", record.numeral),
+                serde_json::json!({"rotated_at": rotated.rotated_at.and_utc()}),
+                serde_json::Value::Null,
+            )
+            .await?;
 
-        let mut record: session::ActiveModel = record.into();
-        record.refresh_token = Set(RefreshToken::generate(Uid(uid)).0);
-        record.refresh_expiry = Set(Utc::now().naive_utc() + RefreshToken::VALID_IN);
-        record.access_token = Set(AccessToken::generate(Uid(uid)).0);
-        record.access_expiry = Set(Utc::now().naive_utc() + AccessToken::VALID_IN);
-        let record = record.update(&*self.0.database.conn).await.unwrap();
+        Err(Error::unauthorized())
+    }
+
+    /// Lists sessions of the user, from the newest.
+    pub async fn list_sessions(&self, current: &Session) -> Result<Vec<SessionInfo>, Error> {
+        current.will_manage_sessions()?;
 
-        Ok((
-            RefreshToken(record.refresh_token),
-            AccessToken(record.access_token),
-        ))
+        Ok(session::Entity::find()
+            .filter(session::Column::Uid.eq(current.user.uid.0))
+            .order_by_desc(session::Column::Numeral)
+            .all(&*self.0.database.conn)
+            .await?
+            .into_iter()
+            .map(|x| SessionInfo {
+                numeral: SessionNumeral(x.numeral),
+                permissions: Permissions::from_bits_retain(x.permissions as _),
+                source: x.source,
+                created_at: x.created_at.map(|x| x.and_utc()),
+                refresh_expiry: x.refresh_expiry.and_utc(),
+                access_expiry: x.access_expiry.and_utc(),
+                current: x.numeral == current.numeral.0,
+            })
+            .collect())
+    }
+
+    /// Revokes a session of the user. The session may be the current one.
+    pub async fn revoke_session(
+        &self,
+        current: &Session,
+        numeral: SessionNumeral,
+    ) -> Result<(), Error> {
+        current.will_manage_sessions()?;
+
+        let result = session::Entity::delete_many()
+            .filter(session::Column::Numeral.eq(numeral.0))
+            .filter(session::Column::Uid.eq(current.user.uid.0))
+            .exec(&*self.0.database.conn)
+            .await?;
+        if result.rows_affected == 0 {
+            return Err(Error::not_found());
+        }
+
+        Ok(())
+    }
+
+    /// Mints a personal access token, which is a session derived from the current one.
+    ///
+    /// The token holds a subset of the current session's permissions, and cannot be refreshed, so it never gains
+    /// broader rights or outlives the requested expiry. Tokens cannot mint tokens themselves, since it would let them
+    /// renew their own expiry.
+    pub async fn mint_token(
+        &self,
+        current: &Session,
+        request: MintRequest,
+    ) -> Result<MintedToken, Error> {
+        current.will_manage_sessions()?;
+        if !current.permissions.contains(Permissions::REFRESH) {
+            return Err(Error::restricted_session());
+        }
+        if request.permissions.contains(Permissions::REFRESH) {
+            return Err(Error::bad_request(
+                "personal access tokens cannot be refreshed",
+            ));
+        }
+        if !current.permissions.contains(request.permissions) {
+            return Err(Error::restricted_session());
+        }
+        if request.expires_in_secs == 0 || request.expires_in_secs > MAX_TOKEN_VALID_IN.as_secs() {
+            return Err(Error::bad_request("invalid token expiry"));
+        }
+
+        let uid = current.user.uid;
+        let now = Utc::now().naive_utc();
+        let access_expiry = now + Duration::from_secs(request.expires_in_secs);
+        let access_token = AccessToken::generate(uid);
+        let model = session::ActiveModel {
+            numeral: NotSet,
+            uid: Set(uid.0),
+            // The refresh token is never handed out, and is expired from the start.
+            refresh_token_hash: Set(self.hash_token(&RefreshToken::generate(uid).0)),
+            access_token_hash: Set(self.hash_token(&access_token.0)),
+            refresh_expiry: Set(now),
+            access_expiry: Set(access_expiry),
+            permissions: Set(request.permissions.bits() as _),
+            source: Set(request.source),
+            created_at: Set(Some(now)),
+        }
+        .insert(&*self.0.database.conn)
+        .await?;
+
+        Ok(MintedToken {
+            numeral: SessionNumeral(model.numeral),
+            access_token,
+            access_expiry: access_expiry.and_utc(),
+        })
+    }
+
+    /// Hashes a token for storage and lookup.
+    fn hash_token(&self, token: &str) -> String {
+        self.0.local_data.token_key.hash(token)
+    }
+
+    /// Revokes every session of the user except the current one.
+    pub async fn revoke_other_sessions(&self, current: &Session) -> Result<(), Error> {
+        current.will_manage_sessions()?;
+
+        session::Entity::delete_many()
+            .filter(session::Column::Uid.eq(current.user.uid.0))
+            .filter(session::Column::Numeral.ne(current.numeral.0))
+            .exec(&*self.0.database.conn)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Revokes the current session.
+    pub async fn logout(&self, current: &Session) -> Result<(), Error> {
+        self.revoke_session(current, current.numeral).await
     }
 }
 
 /// A session.
 #[derive(Debug)]
 pub struct Session {
+    pub numeral: SessionNumeral,
     pub user: super::User,
     pub permissions: Permissions,
+
+    /// Where the session was created from, as told by the client on login.
+    pub source: String,
 }
 impl Session {
+    /// Succeeds if the session may manage sessions of its user.
+    pub fn will_manage_sessions(&self) -> Result<(), Error> {
+        if !self.permissions.contains(Permissions::MANAGE_SESSIONS) {
+            Err(Error::restricted_session())
+        } else {
+            Ok(())
+        }
+    }
+
     /// Succeeds if the session may do administration work.
     pub fn will_administrate(&self) -> Result<(), Error> {
         if !self.permissions.contains(Permissions::ADMINISTRATION) {
@@ -156,6 +366,61 @@ impl Session {
     }
 }
 
+/// Representation of a session numeral.
+///
+/// A session numeral identifies a session uniquely, and is kept when the session's tokens are refreshed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(transparent)]
+#[repr(transparent)]
+pub struct SessionNumeral(pub i64);
+
+/// Information of a session, as shown to its user.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SessionInfo {
+    pub numeral: SessionNumeral,
+    pub permissions: Permissions,
+
+    /// Where the session was created from, as told by the client on login.
+    pub source: String,
+
+    /// Time when the session was created. This is `None` for sessions created before creation times were recorded.
+    pub created_at: Option<DateTime<Utc>>,
+    pub refresh_expiry: DateTime<Utc>,
+    pub access_expiry: DateTime<Utc>,
+
+    /// Whether this is the session that requested the information.
+    pub current: bool,
+}
+
+/// Maximum lifetime of a personal access token.
+pub const MAX_TOKEN_VALID_IN: Duration = Duration::from_secs(366 * 24 * 60 * 60);
+
+/// Request of minting a personal access token.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MintRequest {
+    /// Permissions of the token, which must be held by the current session.
+    pub permissions: Permissions,
+
+    /// Label of the token, like the name of the app or script using it.
+    pub source: String,
+
+    /// Lifetime of the token in seconds.
+    pub expires_in_secs: u64,
+}
+
+/// A minted personal access token.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MintedToken {
+    pub numeral: SessionNumeral,
+    pub access_token: AccessToken,
+    pub access_expiry: DateTime<Utc>,
+}
+
+/// Prefix of tokens in the current format.
+///
+/// Tokens are stored as keyed hashes since this format, and tokens without the prefix are rejected.
+const TOKEN_VERSION: &str = "v1.";
+
 /// Representation of a refresh token.
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
 #[serde(transparent)]
@@ -164,13 +429,16 @@ pub struct RefreshToken(pub String);
 impl RefreshToken {
     pub const VALID_IN: Duration = Duration::from_secs(6 * 31 * 24 * 60 * 60);
 
+    /// Time after rotation in which a refresh token is still accepted, for clients retrying lost refreshes.
+    pub const REUSE_GRACE: Duration = Duration::from_secs(30);
+
     pub fn generate(uid: Uid) -> Self {
         let mut data = [0u8; 128];
         let timestamp = Utc::now().timestamp_micros();
         data[0..8].copy_from_slice(&timestamp.to_le_bytes());
         data[8..16].copy_from_slice(&uid.0.to_le_bytes());
         rand::rng().fill_bytes(&mut data[16..]);
-        Self(BASE64_URL_SAFE.encode(&data))
+        Self(format!("{TOKEN_VERSION}{}", BASE64_URL_SAFE.encode(data)))
     }
 }
 
@@ -188,6 +456,6 @@ impl AccessToken {
         data[0..8].copy_from_slice(&timestamp.to_le_bytes());
         data[8..16].copy_from_slice(&uid.0.to_le_bytes());
         rand::rng().fill_bytes(&mut data[16..]);
-        Self(BASE64_URL_SAFE.encode(&data))
+        Self(format!("{TOKEN_VERSION}{}", BASE64_URL_SAFE.encode(data)))
     }
 }
@@ -1,13 +1,24 @@
 use crate::error::Error;
 use axum::body::Bytes;
-use image::{ImageReader, imageops};
+use image::{DynamicImage, ImageFormat, ImageReader, imageops};
 use std::io::Cursor;
 
 /// Recompresses an image to the specified resolution in the AVIF format.
 pub fn recompress(data: Bytes, expected_resolution: (u32, u32)) -> Result<Bytes, Error> {
-    let mut buffer = Cursor::new(Vec::with_capacity(data.len()));
+    encode(&resize(data, expected_resolution)?, ImageFormat::Avif)
+}
+
+/// Recompresses an image to the specified resolution in the JPEG format, for embedding into files whose readers may
+/// not support AVIF.
+pub fn recompress_jpeg(data: Bytes, expected_resolution: (u32, u32)) -> Result<Bytes, Error> {
+    // JPEG has no alpha channel.
+    let image = DynamicImage::ImageRgb8(resize(data, expected_resolution)?.into_rgb8());
+    encode(&image, ImageFormat::Jpeg)
+}
 
-    ImageReader::new(Cursor::new(data))
+/// Decodes an image and resizes it to the specified resolution.
+fn resize(data: Bytes, expected_resolution: (u32, u32)) -> Result<DynamicImage, Error> {
+    Ok(ImageReader::new(Cursor::new(data))
         .with_guessed_format()
         .map_err(Error::bad_request)?
         .decode()
@@ -16,8 +27,14 @@ pub fn recompress(data: Bytes, expected_resolution: (u32, u32)) -> Result<Bytes,
             expected_resolution.0,
             expected_resolution.1,
             imageops::FilterType::Gaussian,
-        )
-        .write_to(&mut buffer, image::ImageFormat::Avif)
+        ))
+}
+
+/// Encodes an image in the format.
+fn encode(image: &DynamicImage, format: ImageFormat) -> Result<Bytes, Error> {
+    let mut buffer = Cursor::new(Vec::new());
+    image
+        .write_to(&mut buffer, format)
         .map_err(Error::internal)?;
 
     Ok(Bytes::from(buffer.into_inner()))
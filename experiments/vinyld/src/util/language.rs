@@ -0,0 +1,23 @@
+//! Language tags.
+
+use crate::error::Error;
+
+/// Validates a BCP 47 language tag, like `en` or `zh-Hans`, and returns its lowercase form for comparison.
+///
+/// Only the syntax is checked, i.e. alphanumeric subtags of 1 to 8 characters separated by `-`, with a primary
+/// language subtag of letters.
+pub fn normalize(tag: &str) -> Result<String, Error> {
+    let mut subtags = tag.split('-');
+    let primary = subtags.next().unwrap_or_default();
+    let valid = (2..=8).contains(&primary.len())
+        && primary.bytes().all(|x| x.is_ascii_alphabetic())
+        && subtags
+            .all(|x| (1..=8).contains(&x.len()) && x.bytes().all(|x| x.is_ascii_alphanumeric()));
+    if !valid {
+        return Err(Error::bad_request(format!(
+            "`{tag}` is not a valid language tag"
+        )));
+    }
+
+    Ok(tag.to_ascii_lowercase())
+}
@@ -1,2 +1,5 @@
 pub mod image;
+pub mod language;
 pub mod listener;
+pub mod page;
+pub mod secret;
@@ -0,0 +1,116 @@
+//! Cursor pagination.
+
+use crate::error::Error;
+use base64::{Engine, prelude::BASE64_URL_SAFE_NO_PAD};
+use serde::{Deserialize, Serialize};
+
+/// Number of items in a page if the client doesn't specify one.
+const DEFAULT_LIMIT: u64 = 20;
+
+/// Maximum number of items in a page.
+const MAX_LIMIT: u64 = 100;
+
+/// Maximum number of batches that a listing filtering items in Rust reads for a page.
+///
+/// If items are mostly filtered out, the listing returns a partial page, with a cursor after the last scanned item,
+/// instead of reading the whole table in one request.
+pub const MAX_SCAN_BATCHES: usize = 8;
+
+/// Request of a page.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PageQuery {
+    /// Cursor returned by the previous page. If this is `None`, the first page is requested.
+    pub cursor: Option<String>,
+
+    /// Maximum number of items in the page.
+    pub limit: Option<u64>,
+}
+impl PageQuery {
+    /// Returns the number of items in the page.
+    pub fn limit(&self) -> u64 {
+        self.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT)
+    }
+
+    /// Returns the position that the cursor points to.
+    pub fn position(&self) -> Result<Option<i64>, Error> {
+        let Some(cursor) = &self.cursor else {
+            return Ok(None);
+        };
+
+        BASE64_URL_SAFE_NO_PAD
+            .decode(cursor)
+            .ok()
+            .and_then(|x| String::from_utf8(x).ok())
+            .and_then(|x| x.parse().ok())
+            .map(Some)
+            .ok_or_else(|| Error::bad_request("malformed cursor"))
+    }
+}
+
+/// A page of items.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Page<T> {
+    /// Items in the page.
+    pub items: Vec<T>,
+
+    /// Cursor of the next page. This is `None` if there are no more items, while a page with fewer items than
+    /// requested may still have one.
+    ///
+    /// Cursors are opaque to clients.
+    pub next_cursor: Option<String>,
+}
+impl<T> Page<T> {
+    /// Creates a page, whose next page starts after `next_position`.
+    pub fn new(items: Vec<T>, next_position: Option<i64>) -> Self {
+        Self {
+            items,
+            next_cursor: next_position.map(|x| BASE64_URL_SAFE_NO_PAD.encode(x.to_string())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn query(cursor: Option<&str>, limit: Option<u64>) -> PageQuery {
+        PageQuery {
+            cursor: cursor.map(String::from),
+            limit,
+        }
+    }
+
+    #[test]
+    fn cursors_round_trip() {
+        for position in [0, 1, 42, -7, i64::MAX, i64::MIN] {
+            let page = Page::<()>::new(Vec::new(), Some(position));
+            let cursor = page.next_cursor.unwrap();
+            assert_eq!(
+                query(Some(&cursor), None).position().unwrap(),
+                Some(position)
+            );
+        }
+    }
+
+    #[test]
+    fn last_page_has_no_cursor() {
+        assert_eq!(Page::<()>::new(Vec::new(), None).next_cursor, None);
+        assert_eq!(query(None, None).position().unwrap(), None);
+    }
+
+    #[test]
+    fn malformed_cursors() {
+        let not_a_number = BASE64_URL_SAFE_NO_PAD.encode("abc");
+        for cursor in ["!!!", "", not_a_number.as_str()] {
+            assert!(query(Some(cursor), None).position().is_err(), "{cursor}");
+        }
+    }
+
+    #[test]
+    fn limits_are_clamped() {
+        assert_eq!(query(None, None).limit(), DEFAULT_LIMIT);
+        assert_eq!(query(None, Some(0)).limit(), 1);
+        assert_eq!(query(None, Some(50)).limit(), 50);
+        assert_eq!(query(None, Some(u64::MAX)).limit(), MAX_LIMIT);
+    }
+}
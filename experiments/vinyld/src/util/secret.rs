@@ -0,0 +1,20 @@
+//! One-time secrets, like authorization and verification codes.
+
+use base64::{Engine, prelude::BASE64_URL_SAFE_NO_PAD};
+use rand::RngCore;
+use sha2::{Digest, Sha256};
+
+/// Generates a random secret of 256 bits, encoded in URL-safe Base64.
+pub fn generate() -> String {
+    let mut data = [0u8; 32];
+    rand::rng().fill_bytes(&mut data);
+    BASE64_URL_SAFE_NO_PAD.encode(data)
+}
+
+/// Hashes a secret for storage.
+///
+/// Secrets are random and of high entropy, so a fast unsalted hash is enough to keep them from being used if the
+/// database leaks.
+pub fn hash(secret: &str) -> String {
+    BASE64_URL_SAFE_NO_PAD.encode(Sha256::digest(secret))
+}